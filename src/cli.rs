@@ -6,16 +6,46 @@ mod write_tree;
 mod commit_tree;
 mod config;
 mod status;
+mod update_ref;
+mod reflog;
+mod rev_parse;
+mod branch;
+mod tag;
+mod for_each_ref;
+mod show_ref;
+mod pack_refs;
+mod rev_list;
+mod symbolic_ref;
+mod pack_objects;
+mod repack;
+mod prune;
+mod gc;
+mod fsck;
 
+use branch::BranchArgs;
 use cat_file::CatFileArgs;
 use commit_tree::CommitTreeArgs;
 use config::ConfigArgs;
 use hash_object::HashObjectArgs;
 use init::InitArgs;
+use reflog::ReflogArgs;
+use rev_parse::RevParseArgs;
 
 use clap::{Parser, Subcommand};
 use status::StatusArgs;
+use tag::TagArgs;
+use for_each_ref::ForEachRefArgs;
+use show_ref::ShowRefArgs;
+use pack_refs::PackRefsArgs;
+use pack_objects::PackObjectsArgs;
+use repack::RepackArgs;
+use prune::PruneArgs;
+use gc::GcArgs;
+use fsck::FsckArgs;
+use rev_list::RevListArgs;
+use symbolic_ref::SymbolicRefArgs;
 use update_index::UpdateIndexArgs;
+use update_ref::UpdateRefArgs;
 use write_tree::WriteTreeArgs;
 use std::process::ExitCode;
 
@@ -43,6 +73,12 @@ pub struct CliArgs {
     pub subcommand: CliSubcommand,
 }
 
+impl CliArgs {
+    pub fn execute(&self) -> ExitType {
+        self.subcommand.execute()
+    }
+}
+
 #[derive(Subcommand)]
 pub enum CliSubcommand {
     // descriptions copied/inspired by git manpages
@@ -63,6 +99,36 @@ pub enum CliSubcommand {
     Config(ConfigArgs),
     /// Show the working tree status
     Status(StatusArgs),
+    /// Update the object name stored in a ref safely
+    UpdateRef(UpdateRefArgs),
+    /// Manage reflog information
+    Reflog(ReflogArgs),
+    /// Pick out and massage revision parameters
+    RevParse(RevParseArgs),
+    /// List, create, or delete branches
+    Branch(BranchArgs),
+    /// Create, list, or delete tags
+    Tag(TagArgs),
+    /// List refs matching patterns, with a custom format
+    ForEachRef(ForEachRefArgs),
+    /// List refs and the objects they point to
+    ShowRef(ShowRefArgs),
+    /// Move loose refs into the packed-refs file
+    PackRefs(PackRefsArgs),
+    /// List commits reachable from some revisions, but not others
+    RevList(RevListArgs),
+    /// Read, set, or delete symbolic refs (like HEAD)
+    SymbolicRef(SymbolicRefArgs),
+    /// Create a packed archive of objects
+    PackObjects(PackObjectsArgs),
+    /// Pack unpacked objects in a repository
+    Repack(RepackArgs),
+    /// Prune all unreachable objects from the object database
+    Prune(PruneArgs),
+    /// Cleanup unnecessary files and optimize the local repository
+    Gc(GcArgs),
+    /// Verify the connectivity and validity of the objects in the database
+    Fsck(FsckArgs),
 }
 
 pub trait MushSubcommand {
@@ -82,6 +148,21 @@ impl std::ops::Deref for CliSubcommand {
             Self::CommitTree(args) => args,
             Self::Config(args) => args,
             Self::Status(args) => args,
+            Self::UpdateRef(args) => args,
+            Self::Reflog(args) => args,
+            Self::RevParse(args) => args,
+            Self::Branch(args) => args,
+            Self::Tag(args) => args,
+            Self::ForEachRef(args) => args,
+            Self::ShowRef(args) => args,
+            Self::PackRefs(args) => args,
+            Self::RevList(args) => args,
+            Self::SymbolicRef(args) => args,
+            Self::PackObjects(args) => args,
+            Self::Repack(args) => args,
+            Self::Prune(args) => args,
+            Self::Gc(args) => args,
+            Self::Fsck(args) => args,
         }
     }
 }
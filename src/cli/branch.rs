@@ -0,0 +1,234 @@
+use colored::Colorize;
+
+use crate::cli::{CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::object::store::RepositoryObjectStore;
+use crate::revision::walk::is_ancestor;
+use crate::refs::transaction::RefTransaction;
+use crate::refs::{current_branch, list_refs, ExpectedValue, HeadState, Ref, HEAD};
+use crate::revision::RevisionSpec;
+
+#[derive(clap::Args)]
+pub struct BranchArgs {
+    #[command(flatten)]
+    action: BranchActionArgs,
+
+    /// Allow overwriting an existing branch when creating
+    #[arg(short, long)]
+    force: bool,
+
+    /// Branch name(s) (and start point, when creating)
+    names: Vec<String>,
+}
+
+/// BranchAction (as mutually exclusive flags):
+#[derive(clap::Args)]
+struct BranchActionArgs {
+    /// List branches (the default, when no names are given)
+    #[arg(short, long, group = "action")]
+    list: bool,
+
+    /// Delete fully merged branches
+    #[arg(short, group = "action")]
+    delete: bool,
+
+    /// Delete branches, even if they aren't merged
+    #[arg(short = 'D', group = "action")]
+    force_delete: bool,
+
+    /// Rename a branch (and its reflog)
+    #[arg(short = 'm', group = "action")]
+    rename: bool,
+
+    /// Rename a branch, even if the new name already exists
+    #[arg(short = 'M', group = "action")]
+    force_rename: bool,
+
+    /// Print the name of the current branch
+    #[arg(long, group = "action")]
+    show_current: bool,
+}
+
+enum BranchAction {
+    List,
+    Create,
+    Delete { force: bool },
+    Rename { force: bool },
+    ShowCurrent,
+}
+
+impl BranchArgs {
+    fn to_enum(&self) -> BranchAction {
+        let action = &self.action;
+        match (action.list, action.delete, action.force_delete, action.rename, action.force_rename, action.show_current) {
+            (false, false, false, false, false, false) if self.names.is_empty() => BranchAction::List,
+            (false, false, false, false, false, false) => BranchAction::Create,
+            (true, false, false, false, false, false) => BranchAction::List,
+            (false, true, false, false, false, false) => BranchAction::Delete { force: false },
+            (false, false, true, false, false, false) => BranchAction::Delete { force: true },
+            (false, false, false, true, false, false) => BranchAction::Rename { force: false },
+            (false, false, false, false, true, false) => BranchAction::Rename { force: true },
+            (false, false, false, false, false, true) => BranchAction::ShowCurrent,
+            _ => panic!("Clap invariant violated: args not mutually exclusive"),
+        }
+    }
+}
+
+/// `refs/heads/<name>`, rejecting names that git would also reject for branches
+fn branch_ref(name: &str) -> CliResult<Ref> {
+    if name == HEAD || name.starts_with('-') {
+        return Err(format!("`{name}` is not a valid branch name"));
+    }
+
+    Ref::branch(name)
+        .map_err(|_| format!("`{name}` is not a valid branch name"))
+}
+
+fn existing_branch_ref(name: &str) -> CliResult<Ref> {
+    let reff = branch_ref(name)?;
+
+    if !reff.exists()? {
+        return Err(format!("Branch `{name}` not found"));
+    }
+
+    Ok(reff)
+}
+
+fn list() -> CliResult<()> {
+    let current = current_branch()?;
+
+    if let head_state @ HeadState::Detached(_) = HeadState::read()? {
+        println!("* {}", format!("({head_state})").green());
+    }
+
+    for reff in list_refs("refs/heads/")? {
+        if Some(&reff) == current.as_ref() {
+            println!("* {}", reff.short_name().green());
+        } else {
+            println!("  {}", reff.short_name());
+        }
+    }
+
+    Ok(())
+}
+
+fn create(name: &str, start_point: &str, force: bool) -> CliResult<()> {
+    let reff = branch_ref(name)?;
+    let hash = RevisionSpec::parse(start_point)?.dereference_to_commit(&RepositoryObjectStore::current()?)?;
+
+    if force && current_branch()?.as_ref() == Some(&reff) {
+        return Err(format!("Cannot force update the current branch `{name}`"));
+    }
+
+    if !force && reff.exists()? {
+        return Err(format!("A branch named `{name}` already exists"));
+    }
+
+    let expected = if force { ExpectedValue::Any } else { ExpectedValue::Missing };
+    reff.update(&hash, &expected, false, &format!("branch: Created from {start_point}"))
+}
+
+fn delete(name: &str, force: bool) -> CliResult<()> {
+    let reff = existing_branch_ref(name)?;
+    let hash = reff.to_hash()?
+        .ok_or(format!("Branch `{name}` doesn't point to an object"))?;
+
+    if current_branch()?.as_ref() == Some(&reff) {
+        return Err(format!("Cannot delete branch `{name}`: it's the current branch"));
+    }
+
+    if !force {
+        let is_merged = match Ref::head().to_hash()? {
+            Some(head_hash) => is_ancestor(&RepositoryObjectStore::current()?, &hash, &head_hash)?,
+            None => false,
+        };
+
+        if !is_merged {
+            return Err(format!(
+                "The branch `{name}` is not fully merged.\nIf you are sure you want to delete it, run `mush branch -D {name}`"
+            ));
+        }
+    }
+
+    reff.delete(&ExpectedValue::Hash(hash.clone()), false)?;
+    println!("Deleted branch {name} (was {}).", hash.abbreviated());
+
+    Ok(())
+}
+
+fn rename(old_name: &str, new_name: &str, force: bool) -> CliResult<()> {
+    let old_ref = existing_branch_ref(old_name)?;
+    let new_ref = branch_ref(new_name)?;
+    let hash = old_ref.to_hash()?
+        .ok_or(format!("Branch `{old_name}` doesn't point to an object"))?;
+    let was_current = current_branch()?.as_ref() == Some(&old_ref);
+
+    if old_ref == new_ref {
+        return Ok(());
+    }
+
+    if new_ref.exists()? && !force {
+        return Err(format!("A branch named `{new_name}` already exists"));
+    }
+
+    if current_branch()?.as_ref() == Some(&new_ref) {
+        return Err(format!("Cannot force update the current branch `{new_name}`"));
+    }
+
+    if let Some(conflict) = new_ref.name_conflict(Some(&old_ref))? {
+        return Err(format!("Cannot rename `{old_name}` to `{new_name}`: `{conflict}` exists"));
+    }
+
+    let reason = format!("branch: renamed {old_ref} to {new_ref}");
+    let expected = if force { ExpectedValue::Any } else { ExpectedValue::Missing };
+    let mut transaction = RefTransaction::new();
+    if was_current {
+        transaction.update_symbolic(Ref::head(), old_ref.clone(), new_ref.clone());
+    }
+    transaction.rename(old_ref, new_ref, hash, expected, &reason);
+    transaction.commit()
+}
+
+impl MushSubcommand for BranchArgs {
+    fn execute(&self) -> ExitType {
+        match self.to_enum() {
+            BranchAction::List => {
+                cli_expect!(list());
+            },
+            BranchAction::Create => {
+                let (name, start_point) = match &self.names[..] {
+                    [name] => (name, HEAD),
+                    [name, start_point] => (name, start_point.as_str()),
+                    _ => { crate::cli_panic!("Usage: mush branch <name> [<start-point>]"); },
+                };
+                cli_expect!(create(name, start_point, self.force));
+            },
+            BranchAction::Delete { force } => {
+                if self.names.is_empty() {
+                    crate::cli_panic!("Branch name required");
+                }
+
+                for name in self.names.iter() {
+                    cli_expect!(delete(name, force));
+                }
+            },
+            BranchAction::Rename { force } => {
+                let current = cli_expect!(current_branch());
+                let (old_name, new_name) = match (&self.names[..], current) {
+                    ([new_name], Some(current)) => (String::from(current.short_name()), new_name),
+                    ([_], None) => { crate::cli_panic!("Cannot rename the current branch: HEAD is detached"); },
+                    ([old_name, new_name], _) => (old_name.clone(), new_name),
+                    _ => { crate::cli_panic!("Usage: mush branch -m [<old-name>] <new-name>"); },
+                };
+                cli_expect!(rename(&old_name, new_name, force));
+            },
+            BranchAction::ShowCurrent => {
+                if let Some(current) = cli_expect!(current_branch()) {
+                    println!("{}", current.short_name());
+                }
+            },
+        }
+
+        ExitType::Ok
+    }
+}
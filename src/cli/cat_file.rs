@@ -1,8 +1,10 @@
 use crate::cli::ExitType;
 use crate::cli::MushSubcommand;
 use crate::cli_expect;
+use crate::io::open_object;
 use crate::io::read_object;
-use crate::io::read_object_header;
+use crate::object::store::{ObjectStore, RepositoryObjectStore};
+use crate::object::ObjectType;
 use crate::revision::RevisionSpec;
 
 #[derive(clap::Args)]
@@ -58,8 +60,9 @@ enum CatFileVariant {
 impl MushSubcommand for CatFileArgs {
     fn execute(&self) -> ExitType {
         let revision_spec = crate::cli_expect!(RevisionSpec::parse(&self.object));
-        let hash = crate::cli_expect!(revision_spec.dereference());
-        let header = cli_expect!(read_object_header(&hash));
+        let store = cli_expect!(RepositoryObjectStore::current());
+        let hash = crate::cli_expect!(revision_spec.dereference(&store));
+        let header = cli_expect!(store.read_header(&hash));
 
         match self.variant.to_enum() {
             CatFileVariant::Type => {
@@ -69,6 +72,14 @@ impl MushSubcommand for CatFileArgs {
             CatFileVariant::Size => {
                 println!("{}", header.size);
             }
+            CatFileVariant::PrettyPrint if header.tipe == ObjectType::Blob => {
+                // copied as it's decompressed, so that big blobs don't have to fit in memory
+                let (_, mut contents) = cli_expect!(open_object(&hash));
+                cli_expect!(
+                    std::io::copy(&mut contents, &mut std::io::stdout().lock())
+                        .map_err(|io_err| format!("Failed to print object `{}`: {io_err}", hash.as_str()))
+                );
+            }
             CatFileVariant::PrettyPrint => {
                 let object = cli_expect!(read_object(&hash));
                 print!("{}", cli_expect!(object.pretty_print()));
@@ -0,0 +1,361 @@
+// Docs for git for-each-ref:
+// https://git-scm.com/docs/git-for-each-ref
+
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::io::Write;
+
+use crate::cli::{CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::hash::{Hash, DEFAULT_ABBREVIATION_LENGTH};
+use crate::io::{read_object, read_object_header, unique_abbreviation};
+use crate::object::commit::PersonTime;
+use crate::object::{Object, ObjectHeader};
+use crate::refs::{current_branch, list_refs, Ref};
+
+const DEFAULT_FORMAT: &str = "%(objectname) %(objecttype)\t%(refname)";
+const DEFAULT_SORT_KEY: &str = "refname";
+const DATE_FORMAT_STRING: &str = "%a %b %-d %H:%M:%S %Y %z";
+
+#[derive(clap::Args)]
+pub struct ForEachRefArgs {
+    /// Format of each line, with `%(<field>)` placeholders
+    /// (e.g. `%(refname:short)`, `%(objectname:short)`, `%(objecttype)`, `%(authorname)`, `%(subject)`)
+    #[arg(long, default_value = DEFAULT_FORMAT)]
+    format: String,
+
+    /// Field to sort by (prefix with `-` for descending order); the last key given takes precedence
+    #[arg(long, value_name = "KEY")]
+    sort: Vec<String>,
+
+    /// Stop after showing N refs
+    #[arg(long, value_name = "N")]
+    count: Option<usize>,
+
+    /// Only show refs that match a glob pattern, or start with a pattern (up to a `/`)
+    patterns: Vec<String>,
+}
+
+enum Field {
+    Refname,
+    Objectname,
+    Objecttype,
+    Objectsize,
+    Authorname,
+    Authoremail,
+    Authordate,
+    Taggername,
+    Taggeremail,
+    Taggerdate,
+    Subject,
+    Body,
+    Head,
+}
+
+enum Modifier {
+    None,
+    /// `:short`, or `:short=<length>` (for `objectname`)
+    Short(Option<usize>),
+}
+
+/// A `%(<field>[:<modifier>])` placeholder
+struct Atom {
+    field: Field,
+    modifier: Modifier,
+}
+
+impl Atom {
+    fn parse(string: &str) -> CliResult<Self> {
+        let (name, modifier) = match string.split_once(':') {
+            Some((name, modifier)) => (name, Some(modifier)),
+            None => (string, None),
+        };
+
+        let field = match name {
+            "refname" => Field::Refname,
+            "objectname" => Field::Objectname,
+            "objecttype" => Field::Objecttype,
+            "objectsize" => Field::Objectsize,
+            "authorname" => Field::Authorname,
+            "authoremail" => Field::Authoremail,
+            "authordate" => Field::Authordate,
+            "taggername" => Field::Taggername,
+            "taggeremail" => Field::Taggeremail,
+            "taggerdate" => Field::Taggerdate,
+            "subject" => Field::Subject,
+            "body" => Field::Body,
+            "HEAD" => Field::Head,
+            _ => return Err(format!("Unknown field name: `{name}`")),
+        };
+
+        let modifier = match (&field, modifier) {
+            (_, None) => Modifier::None,
+            (Field::Refname | Field::Objectname, Some("short")) => Modifier::Short(None),
+            (Field::Objectname, Some(modifier)) if modifier.starts_with("short=") => {
+                let length = modifier["short=".len()..].parse::<usize>()
+                    .map_err(|_| format!("Bad length in `%({string})`"))?;
+                Modifier::Short(Some(length))
+            },
+            (_, Some(modifier)) => return Err(format!("Unknown modifier `{modifier}` in `%({string})`")),
+        };
+
+        Ok(Atom { field, modifier })
+    }
+}
+
+/// The value of an `Atom` for one ref
+enum Value {
+    Text(String),
+    /// Sizes and dates sort numerically
+    Number(i64, String),
+}
+
+impl Value {
+    fn display(&self) -> &str {
+        match self {
+            Self::Text(text) => text,
+            Self::Number(_, text) => text,
+        }
+    }
+
+    fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Number(a, _), Self::Number(b, _)) => a.cmp(b),
+            _ => self.display().cmp(other.display()),
+        }
+    }
+}
+
+enum FormatPiece {
+    /// Raw bytes, as `%xx` escapes needn't make valid UTF-8 on their own (e.g. `%c3%a9`)
+    Literal(Vec<u8>),
+    Atom(Atom),
+}
+
+fn parse_format(format: &str) -> CliResult<Vec<FormatPiece>> {
+    let mut pieces = Vec::new();
+    let mut literal = Vec::new();
+    let mut rest = format;
+
+    while let Some(percent_index) = rest.find('%') {
+        literal.extend_from_slice(&rest.as_bytes()[..percent_index]);
+        rest = &rest[percent_index + 1..];
+
+        if let Some(after_percent) = rest.strip_prefix('%') {
+            literal.push(b'%');
+            rest = after_percent;
+        } else if let Some(after_paren) = rest.strip_prefix('(') {
+            let close_index = after_paren.find(')')
+                .ok_or(format!("Malformed format string `{format}`: missing `)`"))?;
+
+            if !literal.is_empty() {
+                pieces.push(FormatPiece::Literal(std::mem::take(&mut literal)));
+            }
+            pieces.push(FormatPiece::Atom(Atom::parse(&after_paren[..close_index])?));
+            rest = &after_paren[close_index + 1..];
+        } else if let Some(byte) = rest.get(..2)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit())) // `from_str_radix` also takes a sign
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            // `%xx`: the byte with hex value xx (e.g. `%00`)
+            literal.push(byte);
+            rest = &rest[2..];
+        } else {
+            literal.push(b'%');
+        }
+    }
+
+    literal.extend_from_slice(rest.as_bytes());
+    if !literal.is_empty() {
+        pieces.push(FormatPiece::Literal(literal));
+    }
+
+    Ok(pieces)
+}
+
+/// A ref and its (lazily read) object
+struct RefInfo {
+    reff: Ref,
+    hash: Hash,
+    header: ObjectHeader,
+    object: OnceCell<Object<'static>>,
+}
+
+impl RefInfo {
+    fn new(reff: Ref) -> CliResult<Option<Self>> {
+        let Some(hash) = reff.to_hash()? else {
+            return Ok(None); // dangling symbolic ref
+        };
+        let header = read_object_header(&hash)
+            .map_err(|msg| format!("Failed to read object of `{reff}`: {msg}"))?;
+
+        Ok(Some(RefInfo { reff, hash, header, object: OnceCell::new() }))
+    }
+
+    fn object(&self) -> CliResult<&Object<'static>> {
+        if self.object.get().is_none() {
+            let object = read_object(&self.hash)?;
+            let _ = self.object.set(object);
+        }
+
+        Ok(self.object.get().unwrap())
+    }
+
+    fn author(&self) -> CliResult<Option<&PersonTime>> {
+        Ok(match self.object()? {
+            Object::Commit(commit_object) => Some(&commit_object.author),
+            _ => None,
+        })
+    }
+
+    fn tagger(&self) -> CliResult<Option<&PersonTime>> {
+        Ok(match self.object()? {
+            Object::Tag(tag_object) => Some(&tag_object.tagger),
+            _ => None,
+        })
+    }
+
+    fn message(&self) -> CliResult<&str> {
+        Ok(match self.object()? {
+            Object::Commit(commit_object) => &commit_object.message,
+            Object::Tag(tag_object) => &tag_object.message,
+            _ => "",
+        })
+    }
+
+    fn evaluate(&self, atom: &Atom, head: Option<&Ref>) -> CliResult<Value> {
+        fn text(string: &str) -> Value {
+            Value::Text(String::from(string))
+        }
+
+        fn date(person: Option<&PersonTime>) -> Value {
+            match person {
+                Some(person) => Value::Number(
+                    person.timestamp.timestamp(),
+                    person.timestamp.format(DATE_FORMAT_STRING).to_string(),
+                ),
+                None => Value::Number(0, String::new()),
+            }
+        }
+
+        Ok(match (&atom.field, &atom.modifier) {
+            (Field::Refname, Modifier::Short(_)) => text(self.reff.short_name()),
+            (Field::Refname, _) => text(self.reff.name()),
+            (Field::Objectname, Modifier::Short(length)) => Value::Text(
+                unique_abbreviation(&self.hash, length.unwrap_or(DEFAULT_ABBREVIATION_LENGTH))?
+            ),
+            (Field::Objectname, _) => text(self.hash.as_str()),
+            (Field::Objecttype, _) => text(self.header.tipe.to_str()),
+            (Field::Objectsize, _) => Value::Number(self.header.size as i64, self.header.size.to_string()),
+            (Field::Authorname, _) => text(self.author()?.map(|person| person.name.as_str()).unwrap_or("")),
+            (Field::Authoremail, _) => Value::Text(self.author()?.map(|person| format!("<{}>", person.email)).unwrap_or_default()),
+            (Field::Authordate, _) => date(self.author()?),
+            (Field::Taggername, _) => text(self.tagger()?.map(|person| person.name.as_str()).unwrap_or("")),
+            (Field::Taggeremail, _) => Value::Text(self.tagger()?.map(|person| format!("<{}>", person.email)).unwrap_or_default()),
+            (Field::Taggerdate, _) => date(self.tagger()?),
+            (Field::Subject, _) => Value::Text(subject(self.message()?)),
+            (Field::Body, _) => text(body(self.message()?)),
+            (Field::Head, _) => text(if head == Some(&self.reff) { "*" } else { " " }),
+        })
+    }
+}
+
+/// The first paragraph of a message, joined into a single line
+fn subject(message: &str) -> String {
+    message.lines()
+        .take_while(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Everything after the first paragraph of a message
+fn body(message: &str) -> &str {
+    match message.split_once("\n\n") {
+        Some((_, body)) => body.trim_start_matches('\n'),
+        None => "",
+    }
+}
+
+/// Like fnmatch with FNM_PATHNAME: `*` and `?` don't match `/`
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_matches(&pattern[1..], name) ||
+                (name.first().is_some_and(|c| *c != b'/') && glob_matches(pattern, &name[1..]))
+        },
+        (Some(b'?'), Some(c)) if *c != b'/' => glob_matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(c)) if p == c => glob_matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Whether `name` matches `pattern`, either as a glob, or literally up to a `/`
+/// (`refs/heads` matches `refs/heads/main`, but not `refs/headstart`)
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let prefix = pattern.trim_end_matches('/');
+
+    name == prefix ||
+        name.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/')) ||
+        glob_matches(pattern.as_bytes(), name.as_bytes())
+}
+
+impl ForEachRefArgs {
+    fn run(&self) -> CliResult<()> {
+        let format = parse_format(&self.format)?;
+
+        let sort_keys = if self.sort.is_empty() { vec![String::from(DEFAULT_SORT_KEY)] } else { self.sort.clone() };
+        let sort_atoms = sort_keys.iter()
+            .map(|key| match key.strip_prefix('-') {
+                Some(key) => Ok((Atom::parse(key)?, true)),
+                None => Ok((Atom::parse(key)?, false)),
+            })
+            .collect::<CliResult<Vec<_>>>()?;
+
+        let head = current_branch()?;
+
+        let mut infos = list_refs("refs/")?
+            .into_iter()
+            .filter(|reff| self.patterns.is_empty() || self.patterns.iter().any(|pattern| matches_pattern(reff.name(), pattern)))
+            .filter_map(|reff| RefInfo::new(reff).transpose())
+            .collect::<CliResult<Vec<_>>>()?;
+
+        // Stable sorts, so that the last key is the primary key
+        for (atom, descending) in sort_atoms.iter() {
+            let mut keyed = infos.into_iter()
+                .map(|info| Ok((info.evaluate(atom, head.as_ref())?, info)))
+                .collect::<CliResult<Vec<_>>>()?;
+
+            keyed.sort_by(|(a, _), (b, _)| {
+                let ordering = a.compare(b);
+                if *descending { ordering.reverse() } else { ordering }
+            });
+
+            infos = keyed.into_iter().map(|(_, info)| info).collect();
+        }
+
+        let mut stdout = std::io::stdout().lock();
+        for info in infos.iter().take(self.count.unwrap_or(usize::MAX)) {
+            let mut line = Vec::new();
+            for piece in format.iter() {
+                match piece {
+                    FormatPiece::Literal(literal) => line.extend_from_slice(literal),
+                    FormatPiece::Atom(atom) => line.extend_from_slice(info.evaluate(atom, head.as_ref())?.display().as_bytes()),
+                }
+            }
+            line.push(b'\n');
+
+            stdout.write_all(&line)
+                .map_err(|io_err| format!("Failed to print ref `{}`: {io_err}", info.reff))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MushSubcommand for ForEachRefArgs {
+    fn execute(&self) -> ExitType {
+        cli_expect!(self.run());
+        ExitType::Ok
+    }
+}
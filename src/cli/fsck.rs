@@ -0,0 +1,261 @@
+// Docs for git fsck:
+// https://git-scm.com/docs/git-fsck
+
+use std::collections::{HashMap, HashSet};
+
+use crate::cli::{with_context, CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::hash::{Hash, ObjectFormat};
+use crate::io::read_index;
+use crate::object::store::{ObjectStore, RepositoryObjectStore};
+use crate::object::{Object, ObjectType};
+use crate::refs::reflog::list_reflogs;
+use crate::refs::{list_refs, Ref};
+
+#[derive(clap::Args)]
+pub struct FsckArgs {
+    /// Report every unreachable object, instead of only the dangling ones
+    /// (those that no other object points to)
+    #[arg(long)]
+    unreachable: bool,
+
+    /// Don't report dangling objects
+    #[arg(long)]
+    no_dangling: bool,
+
+    /// Don't consider reflog entries as keeping objects reachable
+    #[arg(long)]
+    no_reflogs: bool,
+}
+
+/// File type bits of the modes allowed in trees.
+//< mush records the full permission bits (git normalizes them to 100644/100755/40000),
+//< so only the file type is checked.
+const TREE_ENTRY_FILE_TYPES: &[u32] = &[
+    0o100000, // regular file
+    0o120000, // symlink
+    0o040000, // directory
+    0o160000, // submodule
+];
+
+/// What was learned about the objects in the database
+#[derive(Default)]
+struct Findings {
+    /// Type of every object that exists (even if it is corrupt)
+    types: HashMap<Hash, ObjectType>,
+    /// Objects each object points to, with the type they should have
+    links: HashMap<Hash, Vec<(Hash, ObjectType)>>,
+    /// Whether anything is wrong with the repository
+    has_errors: bool,
+}
+
+impl Findings {
+    fn error(&mut self, message: String) {
+        eprintln!("error: {message}");
+        self.has_errors = true;
+    }
+
+    /// Re-hash and parse the object stored (in `location`) as `hash`
+    fn check_object(&mut self, hash: &Hash, location: &str, raw_object: CliResult<(ObjectType, Vec<u8>)>, object_format: ObjectFormat) {
+        let (tipe, contents) = match raw_object {
+            Ok(raw_object) => raw_object,
+            Err(msg) => return self.error(format!("{} ({location}) is corrupt: {msg}", hash.as_str())),
+        };
+
+        self.types.insert(hash.clone(), tipe);
+
+        let actual_hash = match object_format.try_digest(&[format!("{} {}\0", tipe.to_str(), contents.len()).as_bytes(), &contents].concat()) {
+            Ok(actual_hash) => Hash::from_bytes(&actual_hash),
+            Err(msg) => return self.error(format!("{} ({location}): {msg}", hash.as_str())),
+        };
+        if actual_hash != *hash {
+            return self.error(format!("hash mismatch for {} ({location}): contents hash to {}", hash.as_str(), actual_hash.as_str()));
+        }
+
+        let object = match Object::from_contents(tipe, contents, object_format) {
+            Ok(object) => object,
+            Err(msg) => return self.error(format!("{} {}: {msg}", tipe.to_str(), hash.as_str())),
+        };
+
+        let links = match object {
+            Object::Blob(_) => vec![],
+            Object::Tree(entries) => {
+                for problem in tree_problems(&entries) {
+                    self.error(format!("tree {}: {problem}", hash.as_str()));
+                }
+
+                entries.iter()
+                    .filter_map(|entry| entry.object_type().map(|tipe| (entry.hash().clone(), tipe)))
+                    .collect()
+            },
+            Object::Commit(commit_object) => {
+                std::iter::once((commit_object.tree_hash, ObjectType::Tree))
+                    .chain(commit_object.parent_hashes.into_iter().map(|parent| (parent, ObjectType::Commit)))
+                    .collect()
+            },
+            Object::Tag(tag_object) => vec![(tag_object.target_hash, tag_object.target_type)],
+        };
+
+        self.links.insert(hash.clone(), links);
+    }
+}
+
+/// Problems with the entries of a tree: bad modes or filenames, and
+/// entries that aren't strictly sorted by filename
+//< git sorts directories as if their names ended with `/`; mush sorts by the plain name.
+fn tree_problems(entries: &[crate::object::TreeEntry]) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for entry in entries {
+        let filename = entry.filename();
+        if !TREE_ENTRY_FILE_TYPES.contains(&(entry.mode() & 0o170000)) || entry.mode() > 0o177777 {
+            problems.push(format!("bad mode {:o} for `{filename}`", entry.mode()));
+        }
+        if filename.is_empty() || filename == "." || filename == ".." || filename.contains('/') {
+            problems.push(format!("bad filename `{filename}`"));
+        }
+    }
+
+    for pair in entries.windows(2) {
+        let (previous, next) = (pair[0].filename(), pair[1].filename());
+        match previous.cmp(next) {
+            std::cmp::Ordering::Less => (),
+            std::cmp::Ordering::Equal => problems.push(format!("duplicate entry `{next}`")),
+            std::cmp::Ordering::Greater => problems.push(format!("entries out of order: `{previous}` before `{next}`")),
+        }
+    }
+
+    problems
+}
+
+impl FsckArgs {
+    fn run(&self) -> CliResult<bool> {
+        let mut findings = Findings::default();
+
+        let store = RepositoryObjectStore::current()?;
+        for hash in store.loose().hashes()? {
+            findings.check_object(&hash, &hash.path(), store.loose().read_raw(&hash), store.object_format());
+        }
+
+        for index in store.list_pack_indexes()? {
+            if let Err(msg) = index.verify_checksums() {
+                findings.error(msg);
+            }
+
+            let location = std::path::Path::new(index.pack_filename()).file_name().unwrap().to_string_lossy().into_owned();
+            for hash in index.hashes()? {
+                let raw_object = index.read_object(&hash, &store)
+                    .and_then(|raw_object| raw_object.ok_or(String::from("missing from its pack")));
+                findings.check_object(&hash, &location, raw_object, store.object_format());
+            }
+        }
+
+        // links between objects
+        let mut missing = HashMap::new();
+        let mut referenced = HashSet::new();
+        let mut objects = findings.types.clone().into_iter().collect::<Vec<_>>();
+        objects.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        for (hash, tipe) in objects {
+            for (target, expected_type) in findings.links.get(&hash).cloned().unwrap_or_default() {
+                referenced.insert(target.clone());
+
+                match findings.types.get(&target) {
+                    None => {
+                        println!("broken link from {} {} to {} {}", tipe.to_str(), hash.as_str(), expected_type.to_str(), target.as_str());
+                        missing.insert(target, expected_type);
+                    },
+                    Some(actual_type) if *actual_type != expected_type => findings.error(format!(
+                        "{} {}: {} is a {}, not a {}",
+                        tipe.to_str(), hash.as_str(), target.as_str(), actual_type.to_str(), expected_type.to_str()
+                    )),
+                    Some(_) => (),
+                }
+            }
+        }
+
+        let mut missing = missing.into_iter().collect::<Vec<_>>();
+        missing.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        for (hash, tipe) in missing.iter() {
+            println!("missing {} {}", tipe.to_str(), hash.as_str());
+        }
+        findings.has_errors |= !missing.is_empty();
+
+        // roots: HEAD and the other refs, reflogs, and the index
+        let mut roots = Vec::new();
+
+        for reff in std::iter::once(Ref::head()).chain(list_refs("")?) {
+            if let Some(hash) = reff.to_hash()? {
+                roots.push((hash, format!("{reff}")));
+            }
+        }
+
+        if !self.no_reflogs {
+            for reff in list_reflogs()? {
+                let entries = match reff.read_reflog() {
+                    Ok(entries) => entries,
+                    Err(msg) => {
+                        findings.error(msg);
+                        continue;
+                    },
+                };
+                for (n, entry) in entries.iter().rev().enumerate() {
+                    roots.extend(
+                        [&entry.old_hash, &entry.new_hash].into_iter()
+                            .filter(|hash| !hash.is_zero())
+                            .map(|hash| (hash.clone(), format!("{reff}@{{{n}}}")))
+                    );
+                }
+            }
+        }
+
+        match with_context("read index", read_index()) {
+            Ok(Some(index)) => {
+                for (filename, entry) in index.into_entries() {
+                    roots.push((entry.hash().clone(), format!("index entry `{}`", filename.as_str())));
+                }
+            },
+            Ok(None) => (),
+            Err(msg) => findings.error(msg),
+        }
+
+        let mut reachable = HashSet::new();
+        let mut frontier = Vec::new();
+        for (hash, source) in roots {
+            match findings.types.contains_key(&hash) {
+                true => frontier.push(hash),
+                false => findings.error(format!("{source}: invalid pointer {}", hash.as_str())),
+            }
+        }
+
+        while let Some(hash) = frontier.pop() {
+            if findings.types.contains_key(&hash) && reachable.insert(hash.clone()) {
+                frontier.extend(findings.links.get(&hash).into_iter().flatten().map(|(target, _)| target.clone()));
+            }
+        }
+
+        // dangling objects: unreachable, and not pointed to by any other object
+        let mut unreachable = findings.types.iter()
+            .filter(|(hash, _)| !reachable.contains(*hash))
+            .collect::<Vec<_>>();
+        unreachable.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+        for (hash, tipe) in unreachable {
+            if self.unreachable {
+                println!("unreachable {} {}", tipe.to_str(), hash.as_str());
+            } else if !self.no_dangling && !referenced.contains(hash) {
+                println!("dangling {} {}", tipe.to_str(), hash.as_str());
+            }
+        }
+
+        Ok(!findings.has_errors)
+    }
+}
+
+impl MushSubcommand for FsckArgs {
+    fn execute(&self) -> ExitType {
+        match cli_expect!(self.run()) {
+            true => ExitType::Ok,
+            false => ExitType::Fatal,
+        }
+    }
+}
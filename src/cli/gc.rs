@@ -0,0 +1,62 @@
+// Docs for git gc:
+// https://git-scm.com/docs/git-gc
+
+use crate::cli::{with_context, CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::config::read_config_option;
+use crate::maintenance::{parse_expiry_date, prune, repack, RepackOptions, DEFAULT_PRUNE_EXPIRE};
+use crate::object::pack::write::{read_big_file_threshold, PackOptions};
+use crate::object::store::RepositoryObjectStore;
+use crate::refs::packed::pack_refs;
+
+#[derive(clap::Args)]
+pub struct GcArgs {
+    /// Prune unreachable objects older than this
+    /// (default: the `gc.pruneExpire` option, or 2 weeks ago)
+    #[arg(long, value_name = "TIME", conflicts_with = "no_prune")]
+    prune: Option<String>,
+
+    /// Don't prune any unreachable objects
+    #[arg(long)]
+    no_prune: bool,
+}
+
+impl GcArgs {
+    fn run(&self) -> CliResult<()> {
+        let expire = match (&self.prune, self.no_prune) {
+            (_, true) => None,
+            (Some(expire), _) => Some(parse_expiry_date(expire)?),
+            (None, _) => {
+                let expire = with_context("read gc.pruneExpire", read_config_option("gc.pruneExpire"))?
+                    .unwrap_or(String::from(DEFAULT_PRUNE_EXPIRE));
+                Some(parse_expiry_date(expire.trim())?)
+            },
+        };
+
+        pack_refs(true, true)?;
+
+        let store = RepositoryObjectStore::current()?;
+        repack(&store, &RepackOptions {
+            all: true,
+            loosen_unreachable: true,
+            delete_redundant: true,
+            pack_options: PackOptions {
+                big_file_threshold: read_big_file_threshold()?,
+                ..PackOptions::default()
+            },
+        })?;
+
+        if let Some(expire) = expire {
+            prune(&store, Some(expire), false)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MushSubcommand for GcArgs {
+    fn execute(&self) -> ExitType {
+        cli_expect!(self.run());
+        ExitType::Ok
+    }
+}
@@ -1,8 +1,10 @@
 use crate::cli::ExitType;
 use crate::cli::MushSubcommand;
+use crate::cli::{with_context, CliResult};
 use crate::cli_expect;
-use crate::io::read_filename_or_stdin_to_str;
-use crate::io::write_object;
+use crate::hash::{Hash, ObjectFormat};
+use crate::io::{file_metadata, open_filename, read_stdin_to_bytes, write_object};
+use crate::object::store::{hash_blob, LooseObjectStore, ObjectStore};
 use crate::object::Object;
 
 use std::borrow::Cow;
@@ -16,19 +18,34 @@ pub struct HashObjectArgs {
     filenames: Vec<String>,
 }
 
+impl HashObjectArgs {
+    fn hash_file(&self, filename: &str) -> CliResult<Hash> {
+        if filename == "-" {
+            // the size of stdin isn't known in advance, so it's read whole
+            let content = with_context("compute hash of object", read_stdin_to_bytes())?;
+            let object = Object::Blob(Cow::Borrowed(&content));
+            return match self.write_result_to_database {
+                true => write_object(&object),
+                false => object.hash(ObjectFormat::load()?),
+            };
+        }
+
+        // files are read a chunk at a time, so that big ones don't have to fit in memory
+        let mut file = with_context("compute hash of object", open_filename(filename))?;
+        let size = with_context("compute hash of object", file_metadata(filename))?.len();
+
+        match self.write_result_to_database {
+            true => LooseObjectStore::current()?.write_blob(&mut file, size),
+            false => hash_blob(&mut file, size, ObjectFormat::load()?),
+        }
+    }
+}
+
 impl MushSubcommand for HashObjectArgs {
     fn execute(&self) -> ExitType {
         for filename in self.filenames.iter() {
-            let content =
-                crate::cli_expect!(read_filename_or_stdin_to_str(filename), "compute hash of object");
-            let object = Object::Blob(Cow::Borrowed(content.as_bytes()));
-            let hash = object.hash();
-
+            let hash = cli_expect!(self.hash_file(filename));
             println!("{}", hash.as_str()); // Not a debug print
-
-            if self.write_result_to_database {
-                cli_expect!(write_object(&object))
-            }
         }
 
         ExitType::Ok
@@ -1,17 +1,22 @@
 use std::path::Path;
 
 use crate::cli::{ExitType, MushSubcommand};
-use crate::io::{create_directories_no_overwrite, create_directory_all, create_file_no_overwrite};
+use crate::hash::{ObjectFormat, OBJECT_FORMAT_OPTION};
+use crate::io::{create_directories_no_overwrite, create_directory_all, create_file_all_no_overwrite, create_file_no_overwrite};
+use crate::refs::{Ref, RefContents, DEFAULT_BRANCH};
 
 #[derive(clap::Args)]
 pub struct InitArgs {
+    /// Hash algorithm used to name the objects of the repository
+    #[arg(long, value_enum, default_value_t = ObjectFormat::Sha1)]
+    object_format: ObjectFormat,
+
     directory: Option<String>,
 }
 
 impl MushSubcommand for InitArgs {
     fn execute(&self) -> ExitType {
         const REASON: &'static str = "initialize repo";
-        const DEFAULT_HEAD: &'static str = "[[default-head-placeholder]]";
 
         let directory = match self.directory {
             Some(ref dir) => {
@@ -30,13 +35,21 @@ impl MushSubcommand for InitArgs {
                     format!("{directory}/.mush").as_str(),
                     format!("{directory}/.mush/objects").as_str(),
                     format!("{directory}/.mush/refs").as_str(),
+                    format!("{directory}/.mush/refs/heads").as_str(),
+                    format!("{directory}/.mush/refs/tags").as_str(),
                     format!("{directory}/.mush/config").as_str(),
                 ].iter()
             ),
             REASON
         );
 
-        crate::cli_expect!(create_file_no_overwrite(&format!("{directory}/.mush/HEAD"), DEFAULT_HEAD.as_bytes()), REASON);
+        // HEAD points to a branch that doesn't exist yet (it's created by the first commit)
+        let default_branch = crate::cli_expect!(Ref::branch(DEFAULT_BRANCH));
+        let default_head = RefContents::Symbolic(String::from(default_branch.name())).serialize();
+        crate::cli_expect!(create_file_no_overwrite(&format!("{directory}/.mush/HEAD"), default_head.as_bytes()), REASON);
+
+        let object_format_filename = format!("{directory}/.mush/config/{}", OBJECT_FORMAT_OPTION.replace('.', "/"));
+        crate::cli_expect!(create_file_all_no_overwrite(&object_format_filename, self.object_format.name().as_bytes()), REASON);
 
         ExitType::Ok
     }
@@ -0,0 +1,93 @@
+// Docs for git pack-objects:
+// https://git-scm.com/docs/git-pack-objects
+
+use crate::cli::{with_context, CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::hash::{Hash, ObjectFormat};
+use crate::io::read_stdin_to_str;
+use crate::object::pack::write::{read_big_file_threshold, write_pack, PackOptions};
+use crate::object::store::RepositoryObjectStore;
+use crate::revision::walk::{walk_commits, walk_objects, WalkOptions};
+use crate::revision::RevisionRange;
+
+#[derive(clap::Args)]
+pub struct PackObjectsArgs {
+    /// Read revisions from stdin (one per line, like `rev-list` arguments),
+    /// and pack every object reachable from them, instead of reading object hashes
+    #[arg(long)]
+    revs: bool,
+
+    /// Number of objects to try as delta bases for each object
+    #[arg(long, default_value_t = PackOptions::default().window)]
+    window: usize,
+
+    /// Longest allowed chain of deltas
+    #[arg(long, default_value_t = PackOptions::default().depth)]
+    depth: usize,
+
+    /// Write `<base-name>-<checksum>.pack` and `<base-name>-<checksum>.idx`
+    base_name: String,
+}
+
+/// Objects listed as `<hash> [<path>]`, one per line (as output by `rev-list --objects`)
+fn parse_object_list(input: &str) -> CliResult<Vec<(Hash, String)>> {
+    let object_format = ObjectFormat::load()?;
+    input.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line_idx, line)| {
+            let (hash, path) = line.split_once(' ').unwrap_or((line, ""));
+            object_format.parse_hash(hash)
+                .map(|hash| (hash, String::from(path)))
+                .ok_or(format!("Bad object name on line {}: `{hash}`", line_idx + 1))
+        })
+        .collect()
+}
+
+/// All objects reachable from the revisions listed one per line
+fn walk_revisions(store: &RepositoryObjectStore, input: &str) -> CliResult<Vec<(Hash, String)>> {
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+
+    for revision in input.lines().filter(|line| !line.is_empty()) {
+        let (mut range_included, mut range_excluded) = RevisionRange::parse(revision)?.dereference(store)?;
+        included.append(&mut range_included);
+        excluded.append(&mut range_excluded);
+    }
+
+    let commits = walk_commits(store, &included, &excluded, &WalkOptions::default())?;
+    let objects = walk_objects(store, &commits, &excluded)?;
+
+    Ok(
+        commits.into_iter()
+            .map(|hash| (hash, String::new()))
+            .chain(objects)
+            .collect()
+    )
+}
+
+impl PackObjectsArgs {
+    fn run(&self) -> CliResult<Hash> {
+        let store = RepositoryObjectStore::current()?;
+        let input = with_context("read objects from stdin", read_stdin_to_str())?;
+
+        let objects = match self.revs {
+            true => walk_revisions(&store, &input)?,
+            false => parse_object_list(&input)?,
+        };
+
+        write_pack(&store, &objects, &self.base_name, &PackOptions {
+            window: self.window,
+            depth: self.depth,
+            big_file_threshold: read_big_file_threshold()?,
+        })
+    }
+}
+
+impl MushSubcommand for PackObjectsArgs {
+    fn execute(&self) -> ExitType {
+        let checksum = cli_expect!(self.run());
+        println!("{}", checksum.as_str());
+        ExitType::Ok
+    }
+}
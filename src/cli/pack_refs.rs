@@ -0,0 +1,24 @@
+// Docs for git pack-refs:
+// https://git-scm.com/docs/git-pack-refs
+
+use crate::cli::{ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::refs::packed::pack_refs;
+
+#[derive(clap::Args)]
+pub struct PackRefsArgs {
+    /// Pack all refs (by default, only tags are packed)
+    #[arg(long)]
+    all: bool,
+
+    /// Keep the loose ref files of refs that were packed
+    #[arg(long)]
+    no_prune: bool,
+}
+
+impl MushSubcommand for PackRefsArgs {
+    fn execute(&self) -> ExitType {
+        cli_expect!(pack_refs(self.all, !self.no_prune));
+        ExitType::Ok
+    }
+}
@@ -0,0 +1,42 @@
+// Docs for git prune:
+// https://git-scm.com/docs/git-prune
+
+use crate::cli::{ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::maintenance::{parse_expiry_date, prune};
+use crate::object::store::RepositoryObjectStore;
+
+#[derive(clap::Args)]
+pub struct PruneArgs {
+    /// Don't remove anything, just report what would be removed
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Report all removed objects
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Only remove unreachable objects older than this (e.g. `2.weeks.ago`, `now`, `never`)
+    #[arg(long, value_name = "TIME")]
+    expire: Option<String>,
+}
+
+impl MushSubcommand for PruneArgs {
+    fn execute(&self) -> ExitType {
+        let expire = match &self.expire {
+            Some(expire) => Some(cli_expect!(parse_expiry_date(expire))),
+            None => None,
+        };
+
+        let store = cli_expect!(RepositoryObjectStore::current());
+        let pruned = cli_expect!(prune(&store, expire, self.dry_run));
+
+        if self.dry_run || self.verbose {
+            for (hash, tipe) in pruned {
+                println!("{} {}", hash.as_str(), tipe.to_str());
+            }
+        }
+
+        ExitType::Ok
+    }
+}
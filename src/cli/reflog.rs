@@ -0,0 +1,95 @@
+use crate::cli::{CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::refs::reflog::list_reflogs;
+use crate::refs::{Ref, HEAD};
+
+/// Number of days after which `reflog expire` prunes entries (same as git's default)
+const DEFAULT_EXPIRE_DAYS: u64 = 90;
+
+#[derive(clap::Args)]
+pub struct ReflogArgs {
+    /// (defaults to `show HEAD`)
+    #[command(subcommand)]
+    action: Option<ReflogAction>,
+}
+
+#[derive(clap::Subcommand)]
+enum ReflogAction {
+    /// Show the log of a ref, newest entry first
+    Show {
+        /// Ref whose log to show
+        #[arg(value_name = "REF", default_value = HEAD)]
+        reff: String,
+    },
+    /// List all refs that have a log
+    List,
+    /// Check whether a ref has a log
+    Exists {
+        #[arg(value_name = "REF")]
+        reff: String,
+    },
+    /// Prune entries older than the given number of days
+    Expire {
+        /// Prune entries older than this many days
+        #[arg(long, value_name = "DAYS", default_value_t = DEFAULT_EXPIRE_DAYS)]
+        expire: u64,
+
+        /// Process the logs of all refs
+        #[arg(long)]
+        all: bool,
+
+        /// Refs whose logs to process
+        #[arg(value_name = "REF", required_unless_present = "all")]
+        refs: Vec<String>,
+    },
+}
+
+fn resolve_reflog_ref(name: &str) -> CliResult<Ref> {
+    Ref::dwim_reflog(name)?
+        .ok_or(format!("No reflog for `{name}`"))
+}
+
+impl MushSubcommand for ReflogArgs {
+    fn execute(&self) -> ExitType {
+        let default_action = ReflogAction::Show { reff: String::from(HEAD) };
+
+        match self.action.as_ref().unwrap_or(&default_action) {
+            ReflogAction::Show { reff: name } => {
+                let reff = cli_expect!(resolve_reflog_ref(name));
+                let entries = cli_expect!(reff.read_reflog());
+
+                entries.iter()
+                    .rev()
+                    .enumerate()
+                    .for_each(|(n, entry)| {
+                        println!("{} {name}@{{{n}}}: {}", entry.new_hash.abbreviated(), entry.reason);
+                    });
+            },
+            ReflogAction::List => {
+                cli_expect!(list_reflogs())
+                    .iter()
+                    .for_each(|reff| println!("{reff}"));
+            },
+            ReflogAction::Exists { reff: name } => {
+                if cli_expect!(Ref::dwim_reflog(name)).is_none() {
+                    return ExitType::Fatal;
+                }
+            },
+            ReflogAction::Expire { expire, all, refs } => {
+                let refs = if *all {
+                    cli_expect!(list_reflogs())
+                } else {
+                    cli_expect!(refs.iter().map(|name| resolve_reflog_ref(name)).collect::<CliResult<Vec<_>>>())
+                };
+
+                let cutoff = chrono::Local::now() - chrono::TimeDelta::days(*expire as i64);
+
+                for reff in refs {
+                    cli_expect!(reff.expire_reflog(|entry| entry.identity.timestamp >= cutoff));
+                }
+            },
+        }
+
+        ExitType::Ok
+    }
+}
@@ -0,0 +1,53 @@
+// Docs for git repack:
+// https://git-scm.com/docs/git-repack
+
+use crate::cli::{ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::maintenance::{repack, RepackOptions};
+use crate::object::pack::write::{read_big_file_threshold, PackOptions};
+use crate::object::store::RepositoryObjectStore;
+
+#[derive(clap::Args)]
+pub struct RepackArgs {
+    /// Pack all reachable objects into a single pack, instead of only the loose ones
+    #[arg(short)]
+    a: bool,
+
+    /// Like `-a`, but with `-d`, unreachable objects of the old packs are made loose (for `prune`) instead of deleted
+    #[arg(short = 'A', conflicts_with = "a")]
+    all_loosen_unreachable: bool,
+
+    /// Delete packs made redundant by the new one, and loose objects that are packed
+    #[arg(short)]
+    d: bool,
+
+    /// Number of objects to try as delta bases for each object
+    #[arg(long, default_value_t = PackOptions::default().window)]
+    window: usize,
+
+    /// Longest allowed chain of deltas
+    #[arg(long, default_value_t = PackOptions::default().depth)]
+    depth: usize,
+}
+
+impl MushSubcommand for RepackArgs {
+    fn execute(&self) -> ExitType {
+        let store = cli_expect!(RepositoryObjectStore::current());
+        let checksum = cli_expect!(repack(&store, &RepackOptions {
+            all: self.a || self.all_loosen_unreachable,
+            loosen_unreachable: self.all_loosen_unreachable,
+            delete_redundant: self.d,
+            pack_options: PackOptions {
+                window: self.window,
+                depth: self.depth,
+                big_file_threshold: cli_expect!(read_big_file_threshold()),
+            },
+        }));
+
+        if checksum.is_none() {
+            println!("Nothing new to pack.");
+        }
+
+        ExitType::Ok
+    }
+}
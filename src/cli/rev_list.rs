@@ -0,0 +1,88 @@
+// Docs for git rev-list:
+// https://git-scm.com/docs/git-rev-list
+
+use crate::cli::{CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::object::store::RepositoryObjectStore;
+use crate::revision::walk::{walk_commits, walk_objects, WalkOptions, WalkOrder};
+use crate::revision::RevisionRange;
+
+#[derive(clap::Args)]
+pub struct RevListArgs {
+    /// Stop after N commits
+    #[arg(short = 'n', long, value_name = "N")]
+    max_count: Option<usize>,
+
+    /// Show no parent before all of its children, and avoid interleaving lines of history
+    #[arg(long, conflicts_with = "date_order")]
+    topo_order: bool,
+
+    /// Show no parent before all of its children, but otherwise order by date
+    #[arg(long)]
+    date_order: bool,
+
+    /// Output commits in reverse order (after applying `--max-count`)
+    #[arg(long)]
+    reverse: bool,
+
+    /// Only follow the first parent of merge commits
+    #[arg(long)]
+    first_parent: bool,
+
+    /// Print the number of commits (and objects), instead of their hashes
+    #[arg(long)]
+    count: bool,
+
+    /// Also list the trees and blobs reachable from the listed commits (with their paths)
+    #[arg(long)]
+    objects: bool,
+
+    /// Revisions (`<rev>`), exclusions (`^<rev>`), and ranges (`<rev1>..<rev2>`, `<rev1>...<rev2>`)
+    #[arg(required = true, allow_hyphen_values = true)]
+    revisions: Vec<String>,
+}
+
+impl RevListArgs {
+    fn run(&self) -> CliResult<()> {
+        let store = RepositoryObjectStore::current()?;
+        let mut included = Vec::new();
+        let mut excluded = Vec::new();
+
+        for revision in self.revisions.iter() {
+            let (mut range_included, mut range_excluded) = RevisionRange::parse(revision)?.dereference(&store)?;
+            included.append(&mut range_included);
+            excluded.append(&mut range_excluded);
+        }
+
+        let options = WalkOptions {
+            order: match (self.topo_order, self.date_order) {
+                (true, _) => WalkOrder::Topological,
+                (_, true) => WalkOrder::Date,
+                _ => WalkOrder::Chronological,
+            },
+            first_parent: self.first_parent,
+            max_count: self.max_count,
+            reverse: self.reverse,
+        };
+
+        let commits = walk_commits(&store, &included, &excluded, &options)?;
+        let objects = if self.objects { walk_objects(&store, &commits, &excluded)? } else { vec![] };
+
+        if self.count {
+            println!("{}", commits.len() + objects.len());
+        } else {
+            commits.iter().for_each(|hash| println!("{}", hash.as_str()));
+            // same format as git (including the trailing space for root trees)
+            objects.iter().for_each(|(hash, path)| println!("{} {path}", hash.as_str()));
+        }
+
+        Ok(())
+    }
+}
+
+impl MushSubcommand for RevListArgs {
+    fn execute(&self) -> ExitType {
+        cli_expect!(self.run());
+        ExitType::Ok
+    }
+}
@@ -0,0 +1,112 @@
+use crate::cli::{with_context, CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::hash::{Hash, DEFAULT_ABBREVIATION_LENGTH};
+use crate::io::{dot_mush_folder, read_object_header, repo_folder, unique_abbreviation};
+use crate::object::store::RepositoryObjectStore;
+use crate::refs::Ref;
+use crate::revision::RevisionSpec;
+
+#[derive(clap::Args)]
+pub struct RevParseArgs {
+    /// Require exactly one revision, which must name an existing object
+    #[arg(long)]
+    verify: bool,
+
+    /// With `--verify`, exit non-zero silently instead of reporting errors
+    #[arg(short, long, requires = "verify")]
+    quiet: bool,
+
+    /// Print the shortest unique abbreviation of each hash (at least N digits)
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with = "abbrev_ref",
+    )]
+    short: Option<Option<usize>>,
+
+    /// Print the short name of each ref (following symbolic refs, e.g. HEAD -> main)
+    #[arg(long)]
+    abbrev_ref: bool,
+
+    /// Print the absolute path of the top-level directory of the working tree
+    #[arg(long)]
+    show_toplevel: bool,
+
+    /// Print the path of the `.mush` directory
+    #[arg(long)]
+    mush_dir: bool,
+
+    /// The revisions to resolve
+    revisions: Vec<String>,
+}
+
+impl RevParseArgs {
+    fn resolve(&self, revision: &str) -> CliResult<String> {
+        if self.abbrev_ref {
+            let reff = Ref::dwim(revision)?
+                .ok_or(format!("Not a ref: `{revision}`"))?;
+            let (target, _) = reff.resolve()?;
+
+            return Ok(String::from(target.short_name()));
+        }
+
+        let hash = RevisionSpec::parse(revision)?.dereference(&RepositoryObjectStore::current()?)?;
+
+        if self.verify || self.short.is_some() {
+            verify_exists(&hash)?;
+        }
+
+        match self.short {
+            Some(min_length) => unique_abbreviation(&hash, min_length.unwrap_or(DEFAULT_ABBREVIATION_LENGTH)),
+            None => Ok(String::from(hash.as_str())),
+        }
+    }
+}
+
+fn verify_exists(hash: &Hash) -> CliResult<()> {
+    read_object_header(hash)
+        .map(|_| ())
+        .map_err(|_| format!("No such object: {}", hash.as_str()))
+}
+
+impl MushSubcommand for RevParseArgs {
+    fn execute(&self) -> ExitType {
+        if self.show_toplevel {
+            println!("{}", cli_expect!(with_context("show top-level directory", repo_folder())));
+        }
+
+        if self.mush_dir {
+            println!("{}", cli_expect!(with_context("show .mush directory", dot_mush_folder())));
+        }
+
+        if self.verify {
+            if self.revisions.len() != 1 {
+                if !self.quiet {
+                    eprintln!("Needed a single revision");
+                }
+                return ExitType::Fatal;
+            }
+
+            return match self.resolve(&self.revisions[0]) {
+                Ok(resolved) => {
+                    println!("{resolved}");
+                    ExitType::Ok
+                },
+                Err(message) => {
+                    if !self.quiet {
+                        eprintln!("{message}");
+                    }
+                    ExitType::Fatal
+                },
+            };
+        }
+
+        for revision in self.revisions.iter() {
+            println!("{}", cli_expect!(self.resolve(revision)));
+        }
+
+        ExitType::Ok
+    }
+}
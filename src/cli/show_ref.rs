@@ -0,0 +1,162 @@
+// Docs for git show-ref:
+// https://git-scm.com/docs/git-show-ref
+
+use crate::cli::{CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::hash::Hash;
+use crate::io::unique_abbreviation;
+use crate::object::store::{ObjectStore, RepositoryObjectStore};
+use crate::object::ObjectType;
+use crate::refs::{list_refs, Ref, HEAD};
+use crate::revision::peel_tags;
+
+#[derive(clap::Args)]
+pub struct ShowRefArgs {
+    /// Show HEAD, even if it doesn't match the patterns
+    #[arg(long)]
+    head: bool,
+
+    /// Only show branches (refs/heads)
+    #[arg(long)]
+    heads: bool,
+
+    /// Only show tags (refs/tags)
+    #[arg(long)]
+    tags: bool,
+
+    /// Also show the peeled object of each annotated tag (as `<tag>^{}`)
+    #[arg(short, long)]
+    dereference: bool,
+
+    /// Only show the hash (abbreviated to at least N digits, if given)
+    #[arg(
+        short = 's',
+        long = "hash",
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+    )]
+    hash: Option<Option<usize>>,
+
+    /// Require that each pattern is an exact (full) ref name
+    #[arg(long)]
+    verify: bool,
+
+    /// Don't print anything (only set the exit status)
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// Only show refs whose names end with a pattern (at a `/` boundary)
+    patterns: Vec<String>,
+}
+
+/// Whether `name` ends with `pattern`, at a `/` boundary
+/// (`main` matches `refs/heads/main` but not `refs/heads/domain`)
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    name == pattern ||
+        name.strip_suffix(pattern).is_some_and(|prefix| prefix.ends_with('/'))
+}
+
+impl ShowRefArgs {
+    fn show(&self, name: &str, hash: &Hash) -> CliResult<()> {
+        if self.quiet {
+            return Ok(());
+        }
+
+        let hash_str = match self.hash {
+            Some(Some(min_length)) => unique_abbreviation(hash, min_length)?,
+            _ => String::from(hash.as_str()),
+        };
+
+        if self.hash.is_some() {
+            println!("{hash_str}");
+        } else {
+            println!("{hash_str} {name}");
+        }
+
+        Ok(())
+    }
+
+    /// Show `reff`, and its peeled object if requested (and `reff` is an annotated tag)
+    fn show_ref(&self, reff: &Ref) -> CliResult<bool> {
+        let Some(hash) = reff.to_hash()? else {
+            return Ok(false);
+        };
+
+        self.show(reff.name(), &hash)?;
+
+        let store = RepositoryObjectStore::current()?;
+        if self.dereference && store.read_header(&hash)?.tipe == ObjectType::Tag {
+            let peeled = match reff.packed_peeled()? {
+                Some(peeled) => peeled,
+                None => peel_tags(&store, hash)?,
+            };
+            self.show(&format!("{}^{{}}", reff.name()), &peeled)?;
+        }
+
+        Ok(true)
+    }
+
+    fn verify(&self) -> CliResult<()> {
+        if self.patterns.is_empty() {
+            return Err(String::from("`--verify` requires a ref name"));
+        }
+
+        for name in self.patterns.iter() {
+            let reff = Ref::new(name)
+                .ok()
+                .filter(|reff| reff.name() == HEAD || reff.name().starts_with("refs/"));
+
+            let shown = match reff {
+                Some(reff) => self.show_ref(&reff)?,
+                None => false,
+            };
+
+            if !shown {
+                return Err(format!("`{name}` - not a valid ref"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether any ref was shown
+    fn list(&self) -> CliResult<bool> {
+        let mut any_shown = false;
+
+        if self.head {
+            any_shown |= self.show_ref(&Ref::head())?;
+        }
+
+        for reff in list_refs("refs/")? {
+            let type_matches = (!self.heads && !self.tags) ||
+                (self.heads && reff.is_branch()) ||
+                (self.tags && reff.is_tag());
+            let pattern_matches = self.patterns.is_empty() ||
+                self.patterns.iter().any(|pattern| matches_pattern(reff.name(), pattern));
+
+            if type_matches && pattern_matches {
+                any_shown |= self.show_ref(&reff)?;
+            }
+        }
+
+        Ok(any_shown)
+    }
+}
+
+impl MushSubcommand for ShowRefArgs {
+    fn execute(&self) -> ExitType {
+        if self.verify {
+            if let Err(message) = self.verify() {
+                if !self.quiet {
+                    eprintln!("{message}");
+                }
+                return ExitType::Fatal;
+            }
+        } else if !cli_expect!(self.list()) {
+            return ExitType::Fatal;
+        }
+
+        ExitType::Ok
+    }
+}
@@ -2,6 +2,7 @@ use crate::cli::{ExitType, MushSubcommand};
 use crate::cli_expect;
 use crate::index::status::IndexStatus;
 use crate::io::{cwd_iter, read_index};
+use crate::refs::HeadState;
 use colored::Colorize;
 
 #[derive(clap::Args)]
@@ -10,6 +11,8 @@ pub struct StatusArgs {
 
 impl MushSubcommand for StatusArgs {
     fn execute(&self) -> ExitType {
+        println!("{}", cli_expect!(HeadState::read()));
+
         let index = cli_expect!(read_index(), "read index")
             .unwrap_or(crate::index::Index::empty());
 
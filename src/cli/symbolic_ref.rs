@@ -0,0 +1,79 @@
+// Docs for git symbolic-ref:
+// https://git-scm.com/docs/git-symbolic-ref
+
+use crate::cli::{CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::refs::{ExpectedValue, Ref, RefContents, HEAD};
+
+#[derive(clap::Args)]
+pub struct SymbolicRefArgs {
+    /// Delete the symbolic ref
+    #[arg(short, long, conflicts_with_all = ["target", "short"])]
+    delete: bool,
+
+    /// When reading, exit non-zero silently if NAME isn't a symbolic ref
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// When reading, print the short name of the target (e.g. `main` instead of `refs/heads/main`)
+    #[arg(long)]
+    short: bool,
+
+    /// The reason for the update, recorded in the reflog
+    /// (default: `checkout: moving from <old> to <new>` when switching HEAD, `symbolic-ref` otherwise)
+    #[arg(short, value_name = "REASON")]
+    message: Option<String>,
+
+    /// The symbolic ref, e.g. `HEAD`
+    name: String,
+
+    /// The ref to point NAME at (if not given, NAME's current target is printed)
+    target: Option<String>,
+}
+
+impl SymbolicRefArgs {
+    fn read(&self, reff: &Ref) -> CliResult<()> {
+        match reff.read()? {
+            Some(RefContents::Symbolic(_)) => {
+                // print the end of the symbolic chain (which might not exist yet)
+                let (target, _) = reff.resolve()?;
+                println!("{}", if self.short { target.short_name() } else { target.name() });
+                Ok(())
+            },
+            Some(RefContents::Hash(_)) => Err(format!("Ref `{reff}` is not a symbolic ref")),
+            None => Err(format!("No such ref: `{reff}`")),
+        }
+    }
+
+    fn delete(&self, reff: &Ref) -> CliResult<()> {
+        if reff.name() == HEAD {
+            return Err(String::from("Refusing to delete HEAD"));
+        }
+
+        match reff.read()? {
+            Some(RefContents::Symbolic(_)) => reff.delete(&ExpectedValue::Any, false),
+            Some(RefContents::Hash(_)) => Err(format!("Cannot delete `{reff}`: not a symbolic ref")),
+            None => Err(format!("No such ref: `{reff}`")),
+        }
+    }
+}
+
+impl MushSubcommand for SymbolicRefArgs {
+    fn execute(&self) -> ExitType {
+        let reff = cli_expect!(Ref::new(&self.name));
+
+        if self.delete {
+            cli_expect!(self.delete(&reff));
+        } else if let Some(target) = &self.target {
+            let target = cli_expect!(Ref::new(target));
+            cli_expect!(reff.update_symbolic(&target, self.message.as_deref()));
+        } else if let Err(message) = self.read(&reff) {
+            if !self.quiet {
+                eprintln!("{message}");
+            }
+            return ExitType::Fatal;
+        }
+
+        ExitType::Ok
+    }
+}
@@ -0,0 +1,127 @@
+use crate::cli::{CliResult, ExitType, MushSubcommand};
+use crate::cli_expect;
+use crate::config::force_get_user;
+use crate::io::{read_stdin_to_str, write_object};
+use crate::object::store::{ObjectStore, RepositoryObjectStore};
+use crate::object::tag::TagObject;
+use crate::refs::{list_refs, ExpectedValue, Ref, HEAD};
+use crate::revision::RevisionSpec;
+
+#[derive(clap::Args)]
+pub struct TagArgs {
+    #[command(flatten)]
+    action: TagActionArgs,
+
+    /// Create an annotated tag object (the message is read from stdin, unless given with `-m`)
+    #[arg(short)]
+    annotate: bool,
+
+    /// Message of the annotated tag (implies `-a`)
+    #[arg(short, value_name = "MESSAGE")]
+    message: Option<String>,
+
+    /// Replace an existing tag
+    #[arg(short, long)]
+    force: bool,
+
+    /// Tag name(s) (and the object to tag, when creating)
+    names: Vec<String>,
+}
+
+/// TagAction (as mutually exclusive flags):
+#[derive(clap::Args)]
+struct TagActionArgs {
+    /// List tags (the default, when no names are given)
+    #[arg(short, long, group = "action")]
+    list: bool,
+
+    /// Delete tags
+    #[arg(short, group = "action")]
+    delete: bool,
+}
+
+enum TagAction {
+    List,
+    Create,
+    Delete,
+}
+
+impl TagArgs {
+    fn to_enum(&self) -> TagAction {
+        match (self.action.list, self.action.delete) {
+            (false, false) if self.names.is_empty() => TagAction::List,
+            (false, false) => TagAction::Create,
+            (true, false) => TagAction::List,
+            (false, true) => TagAction::Delete,
+            _ => panic!("Clap invariant violated: args not mutually exclusive"),
+        }
+    }
+
+    fn create(&self, name: &str, object: &str) -> CliResult<()> {
+        let reff = Ref::tag(name)
+            .map_err(|_| format!("`{name}` is not a valid tag name"))?;
+
+        if !self.force && reff.exists()? {
+            return Err(format!("Tag `{name}` already exists"));
+        }
+
+        let store = RepositoryObjectStore::current()?;
+        let target_hash = RevisionSpec::parse(object)?.dereference(&store)?;
+        let target_type = store.read_header(&target_hash)?.tipe;
+
+        let hash = if self.annotate || self.message.is_some() {
+            let message = match &self.message {
+                Some(message) if message.ends_with('\n') => message.clone(),
+                Some(message) => format!("{message}\n"),
+                None => crate::cli::with_context("get tag message", read_stdin_to_str())?,
+            };
+
+            let object = TagObject::new(target_hash, target_type, String::from(name), force_get_user()?, message).into();
+            write_object(&object)?
+        } else {
+            target_hash
+        };
+
+        let expected = if self.force { ExpectedValue::Any } else { ExpectedValue::Missing };
+        reff.update(&hash, &expected, false, &format!("tag: tagging {object}"))
+    }
+}
+
+fn delete(name: &str) -> CliResult<()> {
+    let reff = Ref::tag(name)
+        .map_err(|_| format!("`{name}` is not a valid tag name"))?;
+    let hash = reff.to_hash()?
+        .ok_or(format!("Tag `{name}` not found"))?;
+
+    reff.delete(&ExpectedValue::Hash(hash.clone()), false)?;
+    println!("Deleted tag {name} (was {}).", hash.abbreviated());
+
+    Ok(())
+}
+
+impl MushSubcommand for TagArgs {
+    fn execute(&self) -> ExitType {
+        match self.to_enum() {
+            TagAction::List => {
+                cli_expect!(list_refs("refs/tags/"))
+                    .iter()
+                    .for_each(|reff| println!("{}", reff.short_name()));
+            },
+            TagAction::Create => {
+                let (name, object) = match &self.names[..] {
+                    [name] => (name, HEAD),
+                    [name, object] => (name, object.as_str()),
+                    _ => { crate::cli_panic!("Usage: mush tag [-a] [-m <message>] <name> [<object>]"); },
+                };
+                cli_expect!(self.create(name, object));
+            },
+            TagAction::Delete => {
+                for name in self.names.iter() {
+                    cli_expect!(delete(name));
+                }
+            },
+        }
+
+        ExitType::Ok
+    }
+}
@@ -1,7 +1,7 @@
 use crate::cli::ExitType;
 use crate::cli::MushSubcommand;
 use crate::cli_expect;
-use crate::hash::Hash;
+use crate::hash::ObjectFormat;
 use crate::io::repo_canononicalize;
 use crate::index::Index;
 use crate::index::IndexEntry;
@@ -48,6 +48,7 @@ impl UpdateIndexActionArgs {
 impl MushSubcommand for UpdateIndexArgs {
     fn execute(&self) -> ExitType {
         let index_file_name = cli_expect!(dot_mush_slash("index"), "resolve path");
+        let object_format = cli_expect!(ObjectFormat::load());
         let mut index = cli_expect!(read_index(), "update index")
             .unwrap_or(Index::empty()) ;
 
@@ -56,7 +57,7 @@ impl MushSubcommand for UpdateIndexArgs {
                 let metadata = cli_expect!(file_metadata(&self.file), "read file metadata");
                 let filename = cli_expect!(repo_canononicalize(&self.file), "canonicalize filename");
                 let hash = cli_expect!(
-                    Hash::try_from_str(&hash)
+                    object_format.parse_hash(&hash)
                         .ok_or(format!("Bad hash: `{}`", hash))
                 );
 
@@ -77,7 +78,7 @@ impl MushSubcommand for UpdateIndexArgs {
         }
 
         cli_expect!(
-            create_file(&index_file_name, index.serialize().as_slice()),
+            create_file(&index_file_name, index.serialize(object_format).as_slice()),
             "write index"
         );
 
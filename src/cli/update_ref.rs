@@ -0,0 +1,152 @@
+use crate::cli::{ExitType, MushSubcommand};
+use crate::cli::CliResult;
+use crate::cli_expect;
+use crate::hash::{Hash, ObjectFormat};
+use crate::io::read_stdin_to_str;
+use crate::object::store::{ObjectStore, RepositoryObjectStore};
+use crate::refs::transaction::RefTransaction;
+use crate::refs::{ExpectedValue, Ref};
+use crate::revision::RevisionSpec;
+
+#[derive(clap::Args)]
+pub struct UpdateRefArgs {
+    /// Delete the ref (after verifying that it still contains OLD_VALUE, if given)
+    #[arg(short)]
+    delete: bool,
+
+    /// Read updates from stdin, and apply them all in one transaction (all or nothing).
+    /// Each line is one of:
+    /// `update <ref> <new> [<old>]`, `create <ref> <new>`, `delete <ref> [<old>]`,
+    /// `verify <ref> [<old>]`, or `option no-deref` (which applies to the next line only)
+    #[arg(long, conflicts_with_all = ["delete", "reff"])]
+    stdin: bool,
+
+    /// Update the ref itself, rather than the ref it points to (if it's symbolic)
+    #[arg(long)]
+    no_deref: bool,
+
+    /// The reason for the update, recorded in the reflog
+    #[arg(short, value_name = "REASON", default_value = "update-ref")]
+    message: String,
+
+    /// The full name of the ref to update, e.g. `refs/heads/main`
+    #[arg(value_name = "REF", required_unless_present = "stdin")]
+    reff: Option<String>,
+
+    /// The revision to point the ref at (with `-d`, this is OLD_VALUE instead)
+    #[arg(required_unless_present_any = ["delete", "stdin"])]
+    new_value: Option<String>,
+
+    /// Only update the ref if it currently points to this revision
+    /// (all zeros means "only if the ref doesn't exist")
+    old_value: Option<String>,
+}
+
+fn parse_expected_value(old_value: Option<&str>) -> CliResult<ExpectedValue> {
+    match old_value {
+        None => Ok(ExpectedValue::Any),
+        Some(string) => match ObjectFormat::load()?.parse_hash(string) {
+            Some(hash) if hash.is_zero() => Ok(ExpectedValue::Missing),
+            _ => RevisionSpec::parse(string)?
+                .dereference(&RepositoryObjectStore::current()?)
+                .map(ExpectedValue::Hash),
+        },
+    }
+}
+
+fn parse_new_value(reff: &Ref, new_value: &str) -> CliResult<Hash> {
+    let store = RepositoryObjectStore::current()?;
+    let new_hash = RevisionSpec::parse(new_value)?.dereference(&store)?;
+
+    if store.read_header(&new_hash).is_err() {
+        return Err(format!(
+            "Cannot update ref `{reff}`: trying to write ref with nonexistent object {}",
+            new_hash.as_str()
+        ));
+    }
+
+    Ok(new_hash)
+}
+
+/// Queue the commands read by `--stdin` (see `UpdateRefArgs::stdin`) in a transaction
+fn parse_stdin_commands(input: &str, reason: &str, no_deref: bool) -> CliResult<RefTransaction> {
+    let mut transaction = RefTransaction::new();
+    let mut no_deref_next = false;
+
+    for (line_index, line) in input.lines().enumerate() {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+
+        if words.is_empty() {
+            continue;
+        }
+
+        if words == ["option", "no-deref"] {
+            no_deref_next = true;
+            continue;
+        }
+
+        let deref = !no_deref && !std::mem::take(&mut no_deref_next);
+        let bad_command = || format!("Bad command on line {}: `{line}`", line_index + 1);
+
+        match words[..] {
+            ["update", reff, new_value] | ["update", reff, new_value, _] => {
+                let reff = Ref::new(reff)?;
+                let new_hash = parse_new_value(&reff, new_value)?;
+                let expected = parse_expected_value(words.get(3).copied())?;
+                transaction.update(reff, new_hash, expected, deref, reason);
+            },
+            ["create", reff, new_value] => {
+                let reff = Ref::new(reff)?;
+                let new_hash = parse_new_value(&reff, new_value)?;
+                transaction.update(reff, new_hash, ExpectedValue::Missing, deref, reason);
+            },
+            ["delete", reff] | ["delete", reff, _] => {
+                let expected = parse_expected_value(words.get(2).copied())?;
+                transaction.delete(Ref::new(reff)?, expected, deref);
+            },
+            ["verify", reff] | ["verify", reff, _] => {
+                let expected = match words.get(2) {
+                    None => ExpectedValue::Missing, // a missing old value means the ref must not exist
+                    Some(old_value) => parse_expected_value(Some(old_value))?,
+                };
+                transaction.verify(Ref::new(reff)?, expected, deref);
+            },
+            _ => return Err(bad_command()),
+        }
+    }
+
+    Ok(transaction)
+}
+
+impl MushSubcommand for UpdateRefArgs {
+    fn execute(&self) -> ExitType {
+        if self.stdin {
+            let input = cli_expect!(read_stdin_to_str(), "read updates from stdin");
+            let transaction = cli_expect!(parse_stdin_commands(&input, &self.message, self.no_deref));
+            cli_expect!(transaction.commit());
+            return ExitType::Ok;
+        }
+
+        let reff = cli_expect!(Ref::new(
+            self.reff.as_ref().expect("Clap invariant violated: REF required unless --stdin")
+        ));
+        let deref = !self.no_deref;
+
+        if self.delete {
+            if self.old_value.is_some() {
+                crate::cli_panic!("Too many arguments for `update-ref -d`");
+            }
+
+            let expected = cli_expect!(parse_expected_value(self.new_value.as_deref()));
+            cli_expect!(reff.delete(&expected, deref));
+        } else {
+            let new_value = self.new_value.as_ref()
+                .expect("Clap invariant violated: new_value required unless deleting");
+            let new_hash = cli_expect!(parse_new_value(&reff, new_value));
+            let expected = cli_expect!(parse_expected_value(self.old_value.as_deref()));
+            cli_expect!(reff.update(&new_hash, &expected, deref, &self.message));
+        }
+
+        ExitType::Ok
+    }
+}
@@ -1,6 +1,7 @@
 use crate::cli::{ExitType, MushSubcommand};
 use crate::cli_expect;
 use crate::io::read_index;
+use crate::object::store::{LooseObjectStore, ObjectStore};
 use crate::object::tree::FilenameTree;
 
 #[derive(clap::Args)]
@@ -12,11 +13,12 @@ impl MushSubcommand for WriteTreeArgs {
         let index = cli_expect!(read_index(), "read index")
             .unwrap_or(crate::index::Index::empty());
 
-        let object_tree = cli_expect!(FilenameTree::from_index(index).into_object_tree());
+        let store = cli_expect!(LooseObjectStore::current());
+        let object_tree = cli_expect!(FilenameTree::from_index(index).into_object_tree(store.object_format()));
 
-        cli_expect!(object_tree.write());
+        cli_expect!(object_tree.write(&store));
 
-        println!("{}", object_tree.root().hash().as_str());
+        println!("{}", cli_expect!(object_tree.hash(store.object_format())).as_str());
 
         ExitType::Ok
     }
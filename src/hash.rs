@@ -1,45 +1,192 @@
-use sha1::{digest::generic_array::GenericArray, Digest};
+use std::sync::OnceLock;
 
-#[derive(PartialEq)]
-pub struct Hash {
-    bytes: [u8; 20],
-    string: String,
+use sha2::Digest;
+
+use crate::cli::{with_context, CliResult};
+use crate::config::read_config_option;
+use crate::io::repo_folder;
+
+/// Number of hex digits shown for abbreviated hashes (same as git's default)
+pub const DEFAULT_ABBREVIATION_LENGTH: usize = 7;
+
+/// Shortest prefix that will be looked up as an abbreviated hash
+pub const MIN_ABBREVIATION_LENGTH: usize = 4;
+
+/// Config option holding the hash algorithm of a repository (same name as in git)
+pub const OBJECT_FORMAT_OPTION: &str = "extensions.objectFormat";
+
+/// The hash algorithm used to name objects (and to checksum the index and packs)
+#[derive(PartialEq, Eq, Clone, Copy, Debug, clap::ValueEnum)]
+pub enum ObjectFormat {
+    Sha1,
+    Sha256,
 }
 
-impl Hash {
-    pub fn digest(data: &impl AsRef<[u8]>) -> Self {
-        let bytes: GenericArray<u8, generic_array::typenum::U20> = sha1::Sha1::digest(data);
-        Hash {
-            bytes: bytes.into(),
-            string: hex::encode(bytes),
+static OBJECT_FORMAT: OnceLock<ObjectFormat> = OnceLock::new();
+
+impl ObjectFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
         }
     }
 
-    pub fn from_bytes(bytes: [u8; 20]) -> Self {
-        Hash {
-            bytes,
-            string: hex::encode(bytes),
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sha1" => Some(Self::Sha1),
+            "sha256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Number of bytes in a hash
+    pub fn hash_size(self) -> usize {
+        match self {
+            Self::Sha1 => 20,
+            Self::Sha256 => 32,
         }
     }
 
-    pub fn try_from_str(string: &str) -> Option<Self> {
+    /// Number of hex digits in a (full) hash
+    pub fn hex_length(self) -> usize {
+        2 * self.hash_size()
+    }
+
+    /// Hash `data`, and report whether it looks like part of a collision attack
+    fn checked_digest(self, data: &[u8]) -> (Vec<u8>, bool) {
+        let mut hasher = Hasher::new(self);
+        hasher.update(data);
+        hasher.checked_finalize()
+    }
+
+    /// Hash `data`, refusing input that shows a collision attack pattern
+    pub fn try_digest(self, data: &[u8]) -> CliResult<Vec<u8>> {
+        let mut hasher = Hasher::new(self);
+        hasher.update(data);
+        hasher.try_finalize().map(|hash| hash.bytes)
+    }
+
+    /// Hash `data` (e.g. a checksum); use `try_digest` to name objects
+    pub fn digest(self, data: &[u8]) -> Hash {
+        Hash::from_bytes(&self.checked_digest(data).0)
+    }
+
+    /// The all-zeros hash, used (e.g. by `update-ref`) to mean "no object"
+    pub fn zero_hash(self) -> Hash {
+        Hash::from_bytes(&vec![0; self.hash_size()])
+    }
+
+    /// Parse a full hex hash of this format (in either case; kept in lowercase)
+    pub fn parse_hash(self, string: &str) -> Option<Hash> {
         hex::decode(string).ok()
-            .and_then(|byte_vec| {
-                byte_vec.try_into()
-                    .ok()
-                    .map(|bytes| {
-                        Hash {
-                            bytes,
-                            string: String::from(string),
-                        }
-                    })
+            .filter(|bytes| bytes.len() == self.hash_size())
+            .map(|bytes| {
+                Hash {
+                    bytes,
+                    string: string.to_ascii_lowercase(),
+                }
             })
     }
 
+    /// The object format of the current repository, for commands working in it:
+    /// read from its config on first use (SHA-1 if it isn't set, or outside of a repository),
+    /// and used from then on. Code that isn't tied to the current repository
+    /// (e.g. `Index`, `PackIndex`, object stores) takes the object format explicitly instead
+    pub fn load() -> CliResult<Self> {
+        if let Some(object_format) = OBJECT_FORMAT.get() {
+            return Ok(*object_format);
+        }
+
+        let object_format = match repo_folder() {
+            Err(_) => Self::Sha1, // not in a repository (e.g. `init`)
+            Ok(_) => match with_context("read object format", read_config_option(OBJECT_FORMAT_OPTION))? {
+                None => Self::Sha1, // a repository from before SHA-256 support
+                Some(name) => Self::from_name(name.trim())
+                    .ok_or(format!("Unknown object format in `{OBJECT_FORMAT_OPTION}`: `{}`", name.trim()))?,
+            },
+        };
+
+        Ok(*OBJECT_FORMAT.get_or_init(|| object_format))
+    }
+}
+
+/// Incremental hashing, for data that is read a chunk at a time
+pub enum Hasher {
+    /// SHA-1 with collision detection (as in git, the hash isn't altered when an attack is detected)
+    Sha1(Box<sha1_checked::Sha1>),
+    /// SHA-256 has no known attacks to detect
+    Sha256(sha2::Sha256),
+}
+
+impl Hasher {
+    pub fn new(object_format: ObjectFormat) -> Self {
+        match object_format {
+            ObjectFormat::Sha1 => Self::Sha1(Box::new(sha1_checked::Sha1::builder().safe_hash(false).build())),
+            ObjectFormat::Sha256 => Self::Sha256(sha2::Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The hash, and whether the data looks like part of a collision attack
+    fn checked_finalize(self) -> (Vec<u8>, bool) {
+        match self {
+            Self::Sha1(hasher) => {
+                let result = hasher.try_finalize();
+                (result.hash().to_vec(), result.has_collision())
+            },
+            Self::Sha256(hasher) => (hasher.finalize().to_vec(), false),
+        }
+    }
+
+    /// The hash of data that isn't an object (e.g. a checksum), like `ObjectFormat::digest`
+    pub fn finalize(self) -> Hash {
+        Hash::from_bytes(&self.checked_finalize().0)
+    }
+
+    /// The hash, refusing data that shows a collision attack pattern
+    pub fn try_finalize(self) -> CliResult<Hash> {
+        match self.checked_finalize() {
+            (bytes, false) => Ok(Hash::from_bytes(&bytes)),
+            (bytes, true) => Err(format!("SHA-1 appears to be part of a collision attack: {}", hex::encode(bytes))),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, std::hash::Hash)]
+pub struct Hash {
+    bytes: Vec<u8>,
+    string: String,
+}
+
+impl Hash {
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Hash {
+            bytes: bytes.to_vec(),
+            string: hex::encode(bytes),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.bytes.iter().all(|b| *b == 0)
+    }
+
     pub fn as_str(&self) -> &str {
         &self.string
     }
 
+    /// The first `DEFAULT_ABBREVIATION_LENGTH` hex digits
+    /// (not guaranteed to be unique within the database)
+    pub fn abbreviated(&self) -> &str {
+        &self.string[..DEFAULT_ABBREVIATION_LENGTH.min(self.string.len())]
+    }
+
     pub fn as_bytes(&self) -> &[u8] {
         &self.bytes
     }
@@ -5,7 +5,7 @@ pub mod status;
 
 use std::{collections::BTreeMap, os::unix::fs::MetadataExt};
 
-use crate::hash::Hash;
+use crate::hash::{Hash, ObjectFormat};
 use crate::object::TreeEntry;
 
 /// String newtype wrapper for a filename relative to the repo's base, no leading slash
@@ -60,7 +60,8 @@ pub struct IndexEntry {
 }
 
 impl Index {
-    pub fn serialize(&self) -> Vec<u8> {
+    /// The index file, checksummed with `object_format` (that of the entries' hashes)
+    pub fn serialize(&self, object_format: ObjectFormat) -> Vec<u8> {
         let mut byte_content = [
             &b"DIRC"[..], // signature, stands for "dircache"
             &2u32.to_be_bytes(), // version 2
@@ -72,27 +73,28 @@ impl Index {
             &0u16.to_be_bytes(), // size of extension
         ].concat();
 
-        let checksum = Hash::digest(&byte_content);
+        let checksum = object_format.digest(&byte_content);
         byte_content.extend(checksum.as_bytes());
 
         byte_content
     }
 
-    pub fn deserialize(bytes: &impl AsRef<[u8]>) -> Result<Self, String> {
+    pub fn deserialize(bytes: &impl AsRef<[u8]>, object_format: ObjectFormat) -> Result<Self, String> {
         let bytes = bytes.as_ref();
 
-        const MIN_POSSIBLE_BYTE_LENGTH: usize = 34;
+        let hash_size = object_format.hash_size();
+        let min_possible_byte_length = 14 + hash_size; // header, size of extension, checksum
 
-        if bytes.len() < MIN_POSSIBLE_BYTE_LENGTH {
+        if bytes.len() < min_possible_byte_length {
             return Err(String::from("Malformed index: header too small"));
         }
 
         let dirc = &bytes[..4];
         let version = u32::from_be_bytes(bytes[4..8].try_into().unwrap());
         let num_entries = u32::from_be_bytes(bytes[8..12].try_into().unwrap());
-        let checksum = Hash::from_bytes(bytes[bytes.len() - 20..].try_into().unwrap());
-        let checksum_input = &bytes[..bytes.len() - 20];
-        let mut entry_list_bytes = bytes[12..bytes.len() - 22].iter().map(|b| *b).peekable();
+        let checksum = Hash::from_bytes(&bytes[bytes.len() - hash_size..]);
+        let checksum_input = &bytes[..bytes.len() - hash_size];
+        let mut entry_list_bytes = bytes[12..bytes.len() - hash_size - 2].iter().copied().peekable();
 
         if dirc != b"DIRC" {
             return Err(format!("Malformed index: bad signature: {:?}", dirc));
@@ -102,14 +104,14 @@ impl Index {
             return Err(format!("Malformed index: bad version: {version} (expected 2)"));
         }
 
-        if Hash::digest(&checksum_input.to_vec()) != checksum {
+        if object_format.digest(checksum_input) != checksum {
             return Err(format!("Malformed index: failed checksum"));
         }
 
         let mut entries = BTreeMap::new();
 
         while entry_list_bytes.peek().is_some() {
-            let entry = IndexEntry::deserialize(&mut entry_list_bytes)?;
+            let entry = IndexEntry::deserialize(&mut entry_list_bytes, object_format)?;
             entries.insert(entry.file_name.clone(), entry);
         }
 
@@ -138,6 +140,10 @@ impl Index {
 }
 
 impl IndexEntry {
+    pub fn hash(&self) -> &Hash {
+        &self.hash
+    }
+
     fn serialize(&self) -> Vec<u8> {
         [
             &(self.metadata_change_time.0 as u32).to_be_bytes(),
@@ -162,12 +168,13 @@ impl IndexEntry {
         ].concat()
     }
 
-    pub fn deserialize(bytes: &mut impl Iterator<Item = u8>) -> Result<Self, String> {
-        const FIXED_FIELDS_BYTE_SIZE: usize = 62; // all fields except the file name (which is variable-length)
+    pub fn deserialize(bytes: &mut impl Iterator<Item = u8>, object_format: ObjectFormat) -> Result<Self, String> {
+        let hash_size = object_format.hash_size();
+        let fixed_fields_byte_size = 42 + hash_size; // all fields except the file name (which is variable-length)
 
-        let header = bytes.take(FIXED_FIELDS_BYTE_SIZE).collect::<Vec<_>>();
+        let header = bytes.take(fixed_fields_byte_size).collect::<Vec<_>>();
 
-        if header.len() < FIXED_FIELDS_BYTE_SIZE {
+        if header.len() < fixed_fields_byte_size {
             return Err(String::from("Malformed index entry: too small"))
         }
 
@@ -181,8 +188,8 @@ impl IndexEntry {
         let uid = u32::from_be_bytes(header[28..32].try_into().unwrap());
         let gid = u32::from_be_bytes(header[32..36].try_into().unwrap());
         let size = u32::from_be_bytes(header[36..40].try_into().unwrap());
-        let hash = Hash::from_bytes(header[40..60].try_into().unwrap());
-        let flags = u16::from_be_bytes(header[60..62].try_into().unwrap());
+        let hash = Hash::from_bytes(&header[40..40 + hash_size]);
+        let flags = u16::from_be_bytes(header[40 + hash_size..].try_into().unwrap());
         let filename_length = flags & 0xFFF;
 
         let filename_bytes = if filename_length < 0xFFF {
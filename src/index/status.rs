@@ -1,6 +1,6 @@
 use std::fs::ReadDir;
 
-use crate::{cli::CliResult, object::tree::{FilenameTree, ObjectTree}};
+use crate::{cli::CliResult, hash::ObjectFormat, object::tree::{FilenameTree, ObjectTree}};
 
 use super::{Index, RepoRelativeFilename};
 
@@ -67,7 +67,7 @@ pub struct IndexStatus {
 
 impl IndexStatus {
 	pub fn create_from_index_and_working_tree(index: Index, working_tree: ReadDir) -> CliResult<Self> {
-        let index_object_tree = FilenameTree::from_index(index).into_object_tree()?;
+        let _index_object_tree = FilenameTree::from_index(index).into_object_tree(ObjectFormat::load()?)?;
 
 
 		// todo!()
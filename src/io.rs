@@ -1,9 +1,12 @@
 use std::fs::ReadDir;
+use std::os::unix::fs::MetadataExt;
+use std::time::SystemTime;
 
-use crate::cli::{with_context, CliResult, ContextlessCliResult};
+use crate::cli::{CliResult, ContextlessCliResult};
 use crate::index::{Index, RepoRelativeFilename};
-use crate::object::{Object, ObjectHeader};
-use crate::hash::Hash;
+use crate::object::store::{ObjectStore, RepositoryObjectStore};
+use crate::object::{Object, ObjectHeader, ObjectType};
+use crate::hash::{Hash, ObjectFormat};
 
 pub fn create_directory_no_overwrite(directory: &str) -> ContextlessCliResult<()> {
     match std::fs::create_dir(directory) {
@@ -120,6 +123,27 @@ pub fn create_file_all(filename: &str, contents: &[u8]) -> ContextlessCliResult<
     Ok(())
 }
 
+pub fn append_to_file_all(filename: &str, contents: &[u8]) -> ContextlessCliResult<()> {
+    let path = std::path::Path::new(filename);
+    let directory = path.parent().unwrap_or(std::path::Path::new("."));
+
+    create_directory_all_idempotent(directory.to_str().unwrap())?;
+
+    std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(filename)
+        .and_then(|mut file| {
+            std::io::Write::write_all(&mut file, contents)
+        })
+        .map_err::<Box<dyn FnOnce(&str) -> String>, _>(|io_err| {
+            let filename = String::from(filename);
+            Box::new(move |reason: &str|
+                format!("Failed to {}: error while appending to file `{}`: {}", reason, filename, io_err)
+            )
+        })
+}
+
 pub fn read_file_to_str(file: std::fs::File, filename: &str) -> ContextlessCliResult<String> {
     let filename = String::from(filename);
     std::io::read_to_string(file)
@@ -169,6 +193,36 @@ pub fn open_file(path: &std::path::Path) -> ContextlessCliResult<std::fs::File>
         )
 }
 
+/// Identity, size and times of a file (or directory), to tell whether it changed since it was read.
+/// The inode and change time catch a file replaced by one of the same size
+/// within the resolution of the modification time.
+#[derive(PartialEq)]
+pub struct FileStamp {
+    modified: SystemTime,
+    size: u64,
+    inode: u64,
+    changed: (i64, i64),
+}
+
+impl FileStamp {
+    /// `Ok(None)` if the file doesn't exist
+    pub fn read(filename: &str) -> ContextlessCliResult<Option<Self>> {
+        match std::fs::metadata(filename).and_then(|metadata| Ok((metadata.modified()?, metadata))) {
+            Ok((modified, metadata)) => Ok(Some(FileStamp {
+                modified,
+                size: metadata.len(),
+                inode: metadata.ino(),
+                changed: (metadata.ctime(), metadata.ctime_nsec()),
+            })),
+            Err(io_err) if io_err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(io_err) => {
+                let filename = String::from(filename);
+                Err(Box::new(move |context| format!("Failed to {context}: error while reading metadata of `{filename}`: {io_err}")))
+            },
+        }
+    }
+}
+
 pub fn try_read_filename_to_str(filename: &str) -> ContextlessCliResult<Option<String>> {
     let file_opt = try_open_filename(filename)?;
 
@@ -193,6 +247,16 @@ pub fn read_stdin_to_str() -> ContextlessCliResult<String> {
         )
 }
 
+/// Read stdin whole, without requiring it to be UTF-8
+pub fn read_stdin_to_bytes() -> ContextlessCliResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes)
+        .map(|_| bytes)
+        .map_err::<Box<dyn FnOnce(&str) -> String>, _>(|io_err|
+            Box::new(move |reason| format!("Failed to {}: error while reading stdin: {}", reason, io_err))
+        )
+}
+
 pub fn read_filename_or_stdin_to_str(filename: &str) -> ContextlessCliResult<String> {
     if filename == "-" { // stdin
         std::io::read_to_string(std::io::stdin())
@@ -287,7 +351,8 @@ pub fn read_index() -> ContextlessCliResult<Option<Index>> {
     } else {
         let bytes = read_filename_to_bytes(&index_filename)?;
 
-        Index::deserialize(&bytes)
+        ObjectFormat::load()
+            .and_then(|object_format| Index::deserialize(&bytes, object_format))
             .map(|index| Some(index))
             .map_err::<Box<dyn FnOnce(&str) -> String>, _>( |err_str|
                 Box::new(move |reason| format!("Failed to {}: error while reading .mush/index: {}", reason, err_str))
@@ -315,24 +380,52 @@ pub fn repo_canononicalize(filename: &str) -> crate::cli::ContextlessCliResult<R
         )
 }
 
-pub fn write_object(object: &Object) -> CliResult<()> {
-    let target_file = with_context("resole path", dot_mush_slash(&object.hash().path()))?;
-    with_context("write object", create_file_all(&target_file, object.compressed().as_slice()))
+/// Write `object` as a loose object (if it isn't in the database already), and return its hash
+pub fn write_object(object: &Object) -> CliResult<Hash> {
+    RepositoryObjectStore::current()?.write(object)
 }
 
 pub fn read_object_header(hash: &Hash) -> CliResult<ObjectHeader> {
-    let object_filename = with_context("resolve path", dot_mush_slash(&hash.path()))?;
-    let file = with_context("get object header", open_filename(&object_filename))?;
-    ObjectHeader::extract_from_file(file, &hash)
+    RepositoryObjectStore::current()?.read_header(hash)
+}
+
+/// Type and contents of an object (loose or packed), without decoding the contents
+pub fn read_raw_object(hash: &Hash) -> CliResult<(ObjectType, Vec<u8>)> {
+    RepositoryObjectStore::current()?.read_raw(hash)
+}
+
+/// Header and a reader of the contents of an object (loose or packed)
+pub fn open_object(hash: &Hash) -> CliResult<(ObjectHeader, Box<dyn std::io::Read>)> {
+    RepositoryObjectStore::current()?.open(hash)
 }
 
 pub fn read_object(hash: &Hash) -> CliResult<Object<'static>> {
-    let object_filename = with_context("resolve path", dot_mush_slash(&hash.path()))?;
-    let object_contents_str = with_context("read object", read_filename_to_bytes(&object_filename))?;
-    Object::from_compressed_bytes(
-        &object_contents_str
-    )
-        .map_err(|msg| format!("Error while reading object: {msg}"))
+    RepositoryObjectStore::current()?.read(hash)
+}
+
+/// The shortest prefix of `hash` (at least `min_length` long) that
+/// no other object in the database starts with
+pub fn unique_abbreviation(hash: &Hash, min_length: usize) -> CliResult<String> {
+    use crate::hash::MIN_ABBREVIATION_LENGTH;
+
+    // every object sharing a prefix with `hash` has the same first two digits
+    let longest_shared_prefix = RepositoryObjectStore::current()?.find_by_prefix(&hash[..2])?
+        .iter()
+        .filter(|other| *other != hash)
+        .map(|other| {
+            other.chars().zip(hash.chars())
+                .take_while(|(a, b)| a == b)
+                .count()
+        })
+        .max()
+        .unwrap_or(0);
+
+    let length = (longest_shared_prefix + 1)
+        .max(min_length)
+        .max(MIN_ABBREVIATION_LENGTH)
+        .min(hash.len());
+
+    Ok(String::from(&hash[..length]))
 }
 
 pub fn cwd_iter() -> ContextlessCliResult<ReadDir> {
@@ -342,3 +435,119 @@ pub fn cwd_iter() -> ContextlessCliResult<ReadDir> {
             Box::new(move |reason| format!("Failed to {reason}: error while reading cwd: {io_err}"))
         )
 }
+
+/// An exclusive lock on `filename`, held by the existence of `<filename>.lock`.
+/// The new contents are written to the lock file, which is then renamed over the
+/// original (atomically), so readers never see a half-written file.
+/// The lock is released (and the lock file removed) on drop, if not committed.
+pub struct LockFile {
+    filename: String,
+    lock_filename: String,
+    file: Option<std::fs::File>,
+}
+
+impl LockFile {
+    pub fn acquire(filename: &str) -> ContextlessCliResult<Self> {
+        let lock_filename = format!("{filename}.lock");
+        let directory = std::path::Path::new(filename).parent().unwrap_or(std::path::Path::new("."));
+
+        create_directory_all_idempotent(directory.to_str().unwrap())?;
+
+        match std::fs::File::create_new(&lock_filename) {
+            Ok(file) => Ok(LockFile {
+                filename: String::from(filename),
+                lock_filename,
+                file: Some(file),
+            }),
+            Err(io_err) if matches!(io_err.kind(), std::io::ErrorKind::AlreadyExists) => {
+                Err(Box::new(move |reason| format!(
+                    "Failed to {}: unable to create lock file `{}`: file exists (another mush process seems to be running)",
+                    reason, lock_filename
+                )))
+            },
+            Err(io_err) => {
+                Err(Box::new(move |reason| format!(
+                    "Failed to {}: error while creating lock file `{}`: {}", reason, lock_filename, io_err
+                )))
+            },
+        }
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn write_all(&mut self, contents: &[u8]) -> ContextlessCliResult<()> {
+        let lock_filename = self.lock_filename.clone();
+        let file = self.file.as_mut()
+            .expect("LockFile invariant violated: write after commit");
+
+        std::io::Write::write_all(file, contents)
+            .and_then(|_| file.sync_all())
+            .map_err::<Box<dyn FnOnce(&str) -> String>, _>(|io_err|
+                Box::new(move |reason| format!("Failed to {}: error while writing lock file `{}`: {}", reason, lock_filename, io_err))
+            )
+    }
+
+    /// Replace the original file with the lock file's contents, releasing the lock
+    pub fn commit(self) -> ContextlessCliResult<()> {
+        let filename = self.filename.clone();
+        self.commit_as(&filename)
+    }
+
+    /// Move the lock file's contents to `filename` instead of the original, releasing the lock
+    /// (for files whose name is only known once written, like packs named after their checksum)
+    pub fn commit_as(mut self, filename: &str) -> ContextlessCliResult<()> {
+        let (lock_filename, filename) = (self.lock_filename.clone(), String::from(filename));
+        let file = self.file.take()
+            .expect("LockFile invariant violated: commit after commit");
+
+        // what was written through `Write` isn't synced yet
+        if let Err(io_err) = file.sync_all() {
+            let _ = std::fs::remove_file(&lock_filename);
+            return Err(Box::new(move |reason| format!("Failed to {}: error while writing lock file `{}`: {}", reason, lock_filename, io_err)));
+        }
+        drop(file); // close before renaming
+
+        std::fs::rename(&lock_filename, &filename)
+            .inspect_err(|_| { let _ = std::fs::remove_file(&lock_filename); })
+            .map_err::<Box<dyn FnOnce(&str) -> String>, _>(|io_err|
+                Box::new(move |reason| format!("Failed to {}: error while renaming `{}` to `{}`: {}", reason, lock_filename, filename, io_err))
+            )
+    }
+
+    /// Delete the original file (while holding the lock), then release the lock
+    pub fn commit_deletion(self) -> ContextlessCliResult<()> {
+        let filename = self.filename.clone();
+        match std::fs::remove_file(&filename) {
+            Err(io_err) if !matches!(io_err.kind(), std::io::ErrorKind::NotFound) => {
+                Err(Box::new(move |reason| format!("Failed to {}: error while deleting file `{}`: {}", reason, filename, io_err)))
+            },
+            _ => Ok(()),
+        }
+        // the lock file is removed on drop
+    }
+}
+
+/// Unlike `write_all`, writes aren't synced one by one, but once on `commit`
+impl std::io::Write for LockFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.as_mut()
+            .expect("LockFile invariant violated: write after commit")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.as_mut()
+            .expect("LockFile invariant violated: write after commit")
+            .flush()
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = std::fs::remove_file(&self.lock_filename);
+        }
+    }
+}
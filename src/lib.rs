@@ -1,10 +1,11 @@
 // Need a `lib.rs` file to make these modules accessible to
 // the integration tests.
 
-mod refs;
+pub mod refs;
 mod revision; // plural to avoid name collision with `ref` keyword
 mod index;
 mod config;
+mod maintenance;
 pub mod object;
 pub mod io;
 pub mod hash;
@@ -1,5 +1,5 @@
 use clap::Parser;
 
 fn main() -> std::process::ExitCode {
-    mush::cli::CliArgs::parse().subcommand.execute().into()
+    mush::cli::CliArgs::parse().execute().into()
 }
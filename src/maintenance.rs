@@ -0,0 +1,209 @@
+// Docs for git gc, repack, and prune:
+// https://git-scm.com/docs/git-gc
+// https://git-scm.com/docs/git-repack
+// https://git-scm.com/docs/git-prune
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::cli::{with_context, CliResult};
+use crate::hash::Hash;
+use crate::io::read_index;
+use crate::object::pack::write::{write_pack, PackOptions};
+use crate::object::pack::PackIndex;
+use crate::object::store::{ObjectStore, RepositoryObjectStore};
+use crate::object::ObjectType;
+use crate::refs::reflog::list_reflogs;
+use crate::refs::{list_refs, Ref};
+use crate::revision::date::parse_approximate_date;
+use crate::revision::walk::reachable_objects;
+
+/// Hashes that keep objects alive: refs, HEAD, reflog entries, and the index.
+/// Reflog entries may name objects that are already gone; those are skipped.
+fn reachability_roots(store: &RepositoryObjectStore) -> CliResult<Vec<Hash>> {
+    let mut roots = Vec::new();
+
+    for reff in std::iter::once(Ref::head()).chain(list_refs("")?) {
+        roots.extend(reff.to_hash()?);
+    }
+
+    if let Some(index) = with_context("read index", read_index())? {
+        roots.extend(index.into_entries().values().map(|entry| entry.hash().clone()));
+    }
+
+    for reff in list_reflogs()? {
+        for entry in reff.read_reflog()? {
+            for hash in [entry.old_hash, entry.new_hash] {
+                if !hash.is_zero() && store.has(&hash)? {
+                    roots.push(hash);
+                }
+            }
+        }
+    }
+
+    Ok(roots)
+}
+
+/// Every object that must be kept (reachable from the roots), with its path
+pub fn reachable(store: &RepositoryObjectStore) -> CliResult<Vec<(Hash, String)>> {
+    reachable_objects(store, &reachability_roots(store)?)
+}
+
+/// Default grace period of `gc`, for unreachable objects
+pub const DEFAULT_PRUNE_EXPIRE: &str = "2.weeks.ago";
+
+/// Parse an expiry date for `prune` (an approximate date, or `never`)
+pub fn parse_expiry_date(string: &str) -> CliResult<DateTime<FixedOffset>> {
+    match string {
+        "never" => Ok(DateTime::<chrono::Utc>::MIN_UTC.fixed_offset()),
+        _ => parse_approximate_date(string, chrono::Local::now().fixed_offset())
+            .ok_or(format!("Bad expiry date: `{string}`")),
+    }
+}
+
+pub struct RepackOptions {
+    /// Pack all reachable objects into one pack (instead of only the loose ones)
+    pub all: bool,
+    /// With `all` and `delete_redundant`: unreachable objects of the old packs are
+    /// made loose (and left to `prune`), instead of being deleted with their packs
+    pub loosen_unreachable: bool,
+    /// Delete packs that were made redundant, and loose objects that are packed
+    pub delete_redundant: bool,
+    pub pack_options: PackOptions,
+}
+
+fn in_any_pack(indexes: &[PackIndex], hash: &Hash) -> CliResult<bool> {
+    for index in indexes {
+        if index.find(hash)?.is_some() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Pack reachable objects into a new pack, and return its checksum
+/// (`None` if there was nothing to pack)
+pub fn repack(store: &RepositoryObjectStore, options: &RepackOptions) -> CliResult<Option<Hash>> {
+    let old_indexes = store.list_pack_indexes()?;
+    let reachable = reachable(store)?;
+
+    let mut objects = Vec::new();
+    for (hash, path) in reachable.iter() {
+        if options.all || !in_any_pack(&old_indexes, hash)? {
+            objects.push((hash.clone(), path.clone()));
+        }
+    }
+
+    let checksum = match objects.is_empty() {
+        true => None,
+        false => {
+            let base_name = format!("{}/pack", store.pack_directory());
+            Some(write_pack(store, &objects, &base_name, &options.pack_options)?)
+        },
+    };
+
+    if options.all && options.delete_redundant {
+        let reachable = reachable.into_iter().map(|(hash, _)| hash).collect::<HashSet<_>>();
+        let new_pack_suffix = checksum.as_ref().map(|checksum| format!("-{}.pack", checksum.as_str()));
+
+        for index in old_indexes {
+            if new_pack_suffix.as_ref().is_some_and(|suffix| index.pack_filename().ends_with(suffix)) {
+                continue; // the same objects were packed again
+            }
+
+            if options.loosen_unreachable {
+                loosen_unreachable(store, &index, &reachable)?;
+            }
+            index.delete()?;
+        }
+    }
+
+    if options.delete_redundant {
+        prune_packed(store, false)?;
+    }
+
+    Ok(checksum)
+}
+
+/// Write the unreachable objects of a pack as loose objects, dated like the pack
+/// (so that they're pruned once the pack would have been)
+fn loosen_unreachable(store: &RepositoryObjectStore, index: &PackIndex, reachable: &HashSet<Hash>) -> CliResult<()> {
+    let pack_modified = std::fs::metadata(index.pack_filename())
+        .and_then(|metadata| metadata.modified())
+        .map_err(|io_err| format!("Failed to read modification time of `{}`: {io_err}", index.pack_filename()))?;
+
+    for hash in index.hashes()? {
+        if reachable.contains(&hash) || store.loose().has(&hash)? {
+            continue;
+        }
+
+        // written as is: decoding and re-encoding the contents could change the hash
+        let (tipe, contents) = store.read_raw(&hash)?;
+        if store.loose().write_raw(tipe, &contents)? != hash {
+            return Err(format!("Object `{}` is corrupt: its contents don't match its hash", hash.as_str()));
+        }
+
+        let object_filename = store.loose().object_filename(&hash);
+
+        std::fs::File::options().write(true).open(&object_filename)
+            .and_then(|file| file.set_modified(pack_modified))
+            .map_err(|io_err| format!("Failed to set modification time of `{object_filename}`: {io_err}"))?;
+    }
+
+    Ok(())
+}
+
+/// Delete the loose objects that are also in a pack, and return their hashes
+/// (without deleting anything, if `dry_run`)
+pub fn prune_packed(store: &RepositoryObjectStore, dry_run: bool) -> CliResult<Vec<Hash>> {
+    let indexes = store.list_pack_indexes()?;
+    let mut pruned = Vec::new();
+
+    for hash in store.loose().hashes()? {
+        if in_any_pack(&indexes, &hash)? {
+            if !dry_run {
+                store.loose().delete(&hash)?;
+            }
+            pruned.push(hash);
+        }
+    }
+
+    Ok(pruned)
+}
+
+/// Delete the unreachable loose objects last modified before `expire` (all of them, if `None`),
+/// then the loose objects that are packed; return the unreachable objects that were deleted.
+/// Nothing is deleted if `dry_run`.
+pub fn prune(store: &RepositoryObjectStore, expire: Option<DateTime<FixedOffset>>, dry_run: bool) -> CliResult<Vec<(Hash, ObjectType)>> {
+    let reachable = reachable(store)?.into_iter().map(|(hash, _)| hash).collect::<HashSet<_>>();
+    let mut pruned = Vec::new();
+
+    for hash in store.loose().hashes()? {
+        if reachable.contains(&hash) {
+            continue;
+        }
+
+        if let Some(expire) = expire {
+            let object_filename = store.loose().object_filename(&hash);
+            let modified: DateTime<FixedOffset> = std::fs::metadata(&object_filename)
+                .and_then(|metadata| metadata.modified())
+                .map_err(|io_err| format!("Failed to read modification time of `{object_filename}`: {io_err}"))
+                .map(|modified| DateTime::<chrono::Local>::from(modified).into())?;
+
+            if modified >= expire {
+                continue; // within the grace period
+            }
+        }
+
+        let tipe = store.loose().read_header(&hash)?.tipe;
+        if !dry_run {
+            store.loose().delete(&hash)?;
+        }
+        pruned.push((hash, tipe));
+    }
+
+    prune_packed(store, dry_run)?;
+    Ok(pruned)
+}
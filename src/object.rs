@@ -1,21 +1,26 @@
 mod pretty_print;
 pub mod tree;
 pub mod commit;
+pub mod tag;
+pub mod pack;
+pub mod store;
 
 use commit::CommitObject;
+use tag::TagObject;
 
 use crate::cli::CliResult;
-use crate::hash::Hash;
+use crate::hash::{Hash, ObjectFormat};
 
 use std::borrow::Cow;
 
-const COMPRESSION_LEVEL: u8 = 1;
+pub(crate) const COMPRESSION_LEVEL: u8 = 1;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum ObjectType {
     Blob,
     Tree,
     Commit,
+    Tag,
 }
 
 impl ObjectType {
@@ -24,14 +29,16 @@ impl ObjectType {
             Self::Blob => "blob",
             Self::Tree => "tree",
             Self::Commit => "commit",
+            Self::Tag => "tag",
         }
     }
 
-    fn from_string(string: &str) -> CliResult<Self> {
+    pub fn from_string(string: &str) -> CliResult<Self> {
         match string {
             "blob" => Ok(Self::Blob),
             "tree" => Ok(Self::Tree),
             "commit" => Ok(Self::Commit),
+            "tag" => Ok(Self::Tag),
             _ => Err(format!("Bad object type: `{string}`")),
         }
     }
@@ -42,6 +49,7 @@ pub enum Object<'b> {
                          // under the same interface
     Tree(Vec<TreeEntry>),
     Commit(CommitObject),
+    Tag(TagObject),
 }
 
 pub struct TreeEntry {
@@ -59,6 +67,28 @@ impl TreeEntry {
         }
     }
 
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    pub fn hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    /// The type of object this entry points to, judging by its mode
+    /// (`None` for submodules, whose commits aren't in this repository)
+    pub fn object_type(&self) -> Option<ObjectType> {
+        match self.mode & 0o170000 {
+            0o040000 => Some(ObjectType::Tree),
+            0o160000 => None,
+            _ => Some(ObjectType::Blob),
+        }
+    }
+
     pub fn store(&self) -> Vec<u8> {
         [
             format!("{:o}", self.mode).as_bytes(),
@@ -69,12 +99,13 @@ impl TreeEntry {
         ].concat()
     }
 
-    fn unstore(bytes: &mut impl Iterator<Item = u8>) -> CliResult<Self> {
+    fn unstore(bytes: &mut impl Iterator<Item = u8>, object_format: ObjectFormat) -> CliResult<Self> {
         let mode = bytes.take_while(|b| *b != b' ').collect::<Vec<_>>();
         let filename = bytes.take_while(|b| *b != b'\0').collect::<Vec<_>>();
-        let hash = bytes.take(20).collect::<Vec<_>>();
+        let hash_size = object_format.hash_size();
+        let hash = bytes.take(hash_size).collect::<Vec<_>>();
 
-        if !matches!(mode.len(), 5..=6) || hash.len() != 20 {
+        if !matches!(mode.len(), 5..=6) || hash.len() != hash_size {
             return Err(String::from("Malformed tree object"));
         }
 
@@ -85,7 +116,7 @@ impl TreeEntry {
                 .map_err(|_| String::from("Malformed tree object: bad mode string"))?,
             filename: String::from_utf8(filename)
                     .map_err(|_| String::from("Malformed index: bad filename"))?,
-            hash: Hash::from_bytes(hash.as_slice().try_into().unwrap()),
+            hash: Hash::from_bytes(&hash),
         })
     }
 }
@@ -93,10 +124,7 @@ impl TreeEntry {
 impl<'b> Object<'b> {
     pub fn store(&self) -> Vec<u8> {
         match self {
-            Self::Blob(bytes) => {
-                let header = format!("blob {}", bytes.len());
-                [header.as_bytes(), &[b'\0'], bytes].concat()
-            },
+            Self::Blob(bytes) => Self::store_raw(ObjectType::Blob, bytes),
             Self::Tree(entries) => {
                 let net_entry_byte_size = entries.iter()
                     .map(|entry|
@@ -104,7 +132,7 @@ impl<'b> Object<'b> {
                         1 + // space
                         entry.filename.len() +
                         1 + // null-byte
-                        20 // hash
+                        entry.hash.as_bytes().len()
                     )
                     .sum::<usize>();
                 let header = format!("tree {}", net_entry_byte_size);
@@ -125,30 +153,51 @@ impl<'b> Object<'b> {
                     &[b'\0'],
                     entry.as_bytes(),
                 ].concat()
-            }
+            },
+            Self::Tag(tag_object) => {
+                let entry = tag_object.serialize();
+                let header = format!("tag {}", entry.len());
+                [
+                    header.as_bytes(),
+                    &[b'\0'],
+                    entry.as_bytes(),
+                ].concat()
+            },
         }
     }
 
-    fn unstore(mut bytes: Vec<u8>) -> CliResult<Self> {
-        fn decode_contents<'b>(tipe: ObjectType, contents: Vec<u8>) -> CliResult<Object<'b>> {
-            match tipe {
-                ObjectType::Blob => Ok(Object::Blob(Cow::Owned(contents))),
-                ObjectType::Tree => {
-                    let mut contents_iterator = contents.iter().map(|b| *b).peekable();
-                    let mut entries = Vec::new();
-
-                    while contents_iterator.peek().is_some() {
-                        entries.push(TreeEntry::unstore(&mut contents_iterator)?);
-                    }
+    /// Build an object from its type and (headerless) contents
+    /// (`object_format` is that of the hashes it refers to)
+    pub fn from_contents(tipe: ObjectType, contents: Vec<u8>, object_format: ObjectFormat) -> CliResult<Object<'b>> {
+        match tipe {
+            ObjectType::Blob => Ok(Object::Blob(Cow::Owned(contents))),
+            ObjectType::Tree => {
+                let mut contents_iterator = contents.iter().map(|b| *b).peekable();
+                let mut entries = Vec::new();
 
-                    Ok(Object::Tree(entries))
-                },
-                ObjectType::Commit => {
-                    Ok(Object::Commit(CommitObject::from_string(&String::from_utf8_lossy(&contents))?))
+                while contents_iterator.peek().is_some() {
+                    entries.push(TreeEntry::unstore(&mut contents_iterator, object_format)?);
                 }
-            }
+
+                Ok(Object::Tree(entries))
+            },
+            ObjectType::Commit => {
+                Ok(Object::Commit(CommitObject::from_string(&String::from_utf8_lossy(&contents), object_format)?))
+            },
+            ObjectType::Tag => {
+                Ok(Object::Tag(TagObject::deserialize(&String::from_utf8_lossy(&contents), object_format)?))
+            },
         }
+    }
+
+    /// Like `store`, for an object of type `tipe` whose contents are already encoded
+    pub fn store_raw(tipe: ObjectType, contents: &[u8]) -> Vec<u8> {
+        let header = format!("{} {}", tipe.to_str(), contents.len());
+        [header.as_bytes(), &[b'\0'], contents].concat()
+    }
 
+    /// Split a stored object (as produced by `store`) into its type and contents
+    pub fn split_stored(mut bytes: Vec<u8>) -> CliResult<(ObjectType, Vec<u8>)> {
         if let Some(null_byte_idx) = bytes.iter().position(|b| *b == b'\0') {
             let contents = bytes.split_off(null_byte_idx + 1);
             let header = ObjectHeader::from_bytes(&bytes[..null_byte_idx])?;
@@ -158,25 +207,48 @@ impl<'b> Object<'b> {
                     "Corrupt object (mismatched header and contents size)",
                 ))
             } else {
-                decode_contents(header.tipe, contents)
+                Ok((header.tipe, contents))
             }
         } else {
             Err(String::from("Invalid header (no null byte)"))
         }
     }
 
-    pub fn hash(&self) -> Hash {
-        Hash::digest(&self.store())
+    fn unstore(bytes: Vec<u8>, object_format: ObjectFormat) -> CliResult<Self> {
+        let (tipe, contents) = Self::split_stored(bytes)?;
+        Self::from_contents(tipe, contents, object_format)
+    }
+
+    pub fn tipe(&self) -> ObjectType {
+        match self {
+            Self::Blob(_) => ObjectType::Blob,
+            Self::Tree(_) => ObjectType::Tree,
+            Self::Commit(_) => ObjectType::Commit,
+            Self::Tag(_) => ObjectType::Tag,
+        }
+    }
+
+    /// Hash of the object (refused if its contents look like a collision attack)
+    pub fn hash(&self, object_format: ObjectFormat) -> CliResult<Hash> {
+        object_format.try_digest(&self.store())
+            .map(|bytes| Hash::from_bytes(&bytes))
     }
 
     pub fn compressed(&self) -> Vec<u8> {
         miniz_oxide::deflate::compress_to_vec_zlib(self.store().as_slice(), COMPRESSION_LEVEL)
     }
 
-    pub fn from_compressed_bytes(bytes: &[u8]) -> CliResult<Object<'b>> {
+    pub fn from_compressed_bytes(bytes: &[u8], object_format: ObjectFormat) -> CliResult<Object<'b>> {
+        miniz_oxide::inflate::decompress_to_vec_zlib(bytes)
+            .map_err(|err| err.to_string())
+            .and_then(|decompressed_bytes| Self::unstore(decompressed_bytes, object_format))
+    }
+
+    /// Like `from_compressed_bytes`, but without decoding the contents
+    pub fn split_compressed_bytes(bytes: &[u8]) -> CliResult<(ObjectType, Vec<u8>)> {
         miniz_oxide::inflate::decompress_to_vec_zlib(bytes)
             .map_err(|err| err.to_string())
-            .and_then(|decompressed_bytes| Self::unstore(decompressed_bytes))
+            .and_then(Self::split_stored)
     }
 }
 
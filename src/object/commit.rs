@@ -3,7 +3,7 @@ use std::str::FromStr;
 use chrono::TimeZone;
 use itertools::Itertools;
 
-use crate::{cli::CliResult, config::User, hash::Hash};
+use crate::{cli::CliResult, config::User, hash::{Hash, ObjectFormat}};
 
 use super::Object;
 
@@ -16,13 +16,47 @@ pub struct PersonTime {
 }
 
 impl PersonTime {
-    fn to_string(&self) -> String {
+    /// The given user, at the current time
+    pub fn now(user: User) -> Self {
+        PersonTime {
+            name: user.name,
+            email: user.email,
+            timestamp: chrono::Local::now().into(),
+        }
+    }
+
+    pub fn to_string(&self) -> String {
         //< Use tab as separator to prevent parsing issues with spaces
         //< in name causing ambiguity.
         //< This means that tabs are invalid characters in user names/emails.
         //< This isn't validated right now. Oh well.
         format!("{}\t<{}>\t{}", self.name, self.email, self.timestamp.format(DATE_FORMAT_STRING))
     }
+
+    /// Inverse of `to_string` (with the three tab-separated fields already split)
+    pub fn from_strings(name: &str, email: &str, time: &str) -> CliResult<Self> {
+        let email = email.strip_prefix('<')
+            .and_then(|email| email.strip_suffix('>'))
+            .ok_or(String::from("bad email"))?;
+
+        let [seconds, offset] = time.split(" ").collect::<Vec<_>>()[..]
+            else {
+                return Err(String::from("bad timestamp"))
+            };
+
+        let seconds = seconds.parse::<i64>()
+            .map_err(|_| String::from("bad timestamp"))?;
+
+        let offset = chrono::FixedOffset::from_str(offset)
+            .map_err(|_| String::from("bad timestamp"))?;
+
+        Ok(PersonTime {
+            name: String::from(name),
+            email: String::from(email),
+            timestamp: offset.timestamp_opt(seconds, 0).single()
+                .ok_or(String::from("bad timestamp"))?,
+        })
+    }
 }
 
 pub struct CommitObject {
@@ -46,8 +80,8 @@ impl CommitObject {
         ].join("")
     }
 
-    pub fn from_string(string: &str) -> CliResult<Self> {
-        fn from_header_and_message(header: &str, message: &str) -> CliResult<CommitObject>{
+    pub fn from_string(string: &str, object_format: ObjectFormat) -> CliResult<Self> {
+        fn from_header_and_message(header: &str, message: &str, object_format: ObjectFormat) -> CliResult<CommitObject>{
             let field_names_to_args_map = header.split("\n")
                 .map(|field| {
                     let mut tab_separated_strings = field.split("\t");
@@ -92,7 +126,7 @@ impl CommitObject {
                 .ok_or("Malformed commit object: missing tree")?
                 [0];
 
-            let tree_hash = Hash::try_from_str(tree_hash_str)
+            let tree_hash = object_format.parse_hash(tree_hash_str)
                 .ok_or(format!("Malformed commit object: bad hash: {}", tree_hash_str))?;
 
             let parent_hashes = field_names_to_args_map.get(&Some("parent"))
@@ -100,7 +134,7 @@ impl CommitObject {
                 .iter()
                 .map(|args| args[0])
                 .map(|hash_str| Ok::<_, String>(
-                    Hash::try_from_str(hash_str)
+                    object_format.parse_hash(hash_str)
                         .ok_or(format!("Malformed commit object: bad hash: {}", tree_hash_str))?
                 ))
                 .collect::<Result<Vec<_>, _>>()?;
@@ -114,25 +148,8 @@ impl CommitObject {
                     panic!("Violated arity invariant")
                 };
 
-            let email = &email[1..(email.len() - 1)]; // trim leading and trailing '<' and '>'
-
-            let [seconds, offset] = time.split(" ").collect::<Vec<_>>()[0..2]
-                else {
-                    return Err(String::from("Malformed commit object: bad timestamp"))
-                };
-
-            let seconds = seconds.parse::<i64>()
-                .map_err(|_| String::from("Malformed commit object: bad timestamp"))?;
-
-            let offset = chrono::FixedOffset::from_str(offset)
-                .map_err(|_| String::from("Malformed commit object: bad timestamp"))?;
-
-            let author = PersonTime {
-                name: String::from(name),
-                email: String::from(email),
-                timestamp: offset.timestamp_opt(seconds, 0).single()
-                    .ok_or(String::from("Malformed commit object: bad timestamp"))?,
-            };
+            let author = PersonTime::from_strings(name, email, time)
+                .map_err(|msg| format!("Malformed commit object: {msg}"))?;
 
             Ok(CommitObject {
                 tree_hash,
@@ -143,7 +160,7 @@ impl CommitObject {
         }
 
         if let [header, message] = string.splitn(2, "\n\n").collect::<Vec<_>>()[..] {
-            from_header_and_message(header, message)
+            from_header_and_message(header, message, object_format)
         } else {
             Err(String::from("Malformed commit object: no double newline found"))
         }
@@ -155,11 +172,7 @@ impl CommitObject {
         CommitObject {
             tree_hash,
             parent_hashes,
-            author: PersonTime {
-                name: user.name,
-                email: user.email,
-                timestamp: chrono::Local::now().into(),
-            },
+            author: PersonTime::now(user),
             message
         }
     }
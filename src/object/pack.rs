@@ -0,0 +1,741 @@
+// Docs for the packfile and pack index formats:
+// https://git-scm.com/docs/gitformat-pack
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex, TryLockError};
+
+use crate::cli::{with_context, CliResult};
+use crate::hash::{Hash, ObjectFormat};
+use crate::io::{open_filename, read_filename_to_bytes, FileStamp};
+
+use super::store::{ObjectStore, VerifiedReader};
+use super::{ObjectHeader, ObjectType};
+
+pub mod write;
+
+const INDEX_SIGNATURE: &[u8; 4] = b"\xfftOc";
+const INDEX_VERSION: u32 = 2;
+const PACK_SIGNATURE: &[u8; 4] = b"PACK";
+
+/// Size of the index header (signature and version) plus the fanout table
+const INDEX_FANOUT_END: u64 = 8 + 256 * 4;
+
+/// Deltas are followed at most this deep, so that a corrupt pack
+/// (e.g. with a cycle of ref deltas) can't loop forever
+const MAX_DELTA_CHAIN_LENGTH: usize = 10_000;
+
+/// Total size of the delta bases kept in memory per pack
+const DELTA_BASE_CACHE_SIZE: usize = 16 * 1024 * 1024;
+
+/// The indexes of the packs in the database, so that each object lookup doesn't
+/// list the pack directory and re-read every index
+static LOADED_INDEXES: Mutex<Option<LoadedIndexes>> = Mutex::new(None);
+
+struct LoadedIndexes {
+    directory: String,
+    object_format: ObjectFormat,
+    /// Stamp of the directory when it was listed (`None` if it didn't exist)
+    stamp: Option<FileStamp>,
+    indexes: Arc<Vec<PackIndex>>,
+}
+
+/// What a pack entry holds: a whole object, or a delta against a base object
+enum EntryKind {
+    Whole(ObjectType),
+    /// The base is the entry at this (absolute) offset in the same pack
+    OffsetDelta(u64),
+    /// The base is the object with this hash (usually, but not necessarily, in the same pack)
+    RefDelta(Hash),
+}
+
+struct EntryHeader {
+    kind: EntryKind,
+    /// Size of the inflated data (the object, or the delta)
+    size: usize,
+    /// Offset of the zlib stream holding the data
+    data_offset: u64,
+}
+
+/// A `.idx` file (version 2), mapping the hashes in a `.pack` to their offsets.
+/// Only the fanout table is held in memory; lookups binary-search the file itself.
+/// Both files are kept open once used.
+pub struct PackIndex {
+    idx_filename: String,
+    pack_filename: String,
+    /// `fanout[b]` is the number of objects whose first hash byte is <= `b`
+    fanout: [u32; 256],
+    /// That of the hashes (and checksums) in the pack and the index
+    object_format: ObjectFormat,
+    file: Mutex<std::fs::File>,
+    /// Opened on first use
+    pack: Mutex<Option<Pack>>,
+}
+
+impl PackIndex {
+    pub fn open(idx_filename: &str, object_format: ObjectFormat) -> CliResult<Self> {
+        let hash_size = object_format.hash_size() as u64;
+        let context = format!("read pack index `{idx_filename}`");
+        let mut file = with_context(&context, open_filename(idx_filename))?;
+
+        let mut header = [0; INDEX_FANOUT_END as usize];
+        file.read_exact(&mut header)
+            .map_err(|io_err| format!("Failed to {context}: {io_err}"))?;
+
+        if &header[..4] != INDEX_SIGNATURE {
+            return Err(format!("Failed to {context}: bad signature"));
+        }
+
+        let version = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        if version != INDEX_VERSION {
+            return Err(format!("Failed to {context}: unsupported version {version}"));
+        }
+
+        let fanout: [u32; 256] = std::array::from_fn(|i| u32::from_be_bytes(header[8 + 4 * i..12 + 4 * i].try_into().unwrap()));
+        if fanout.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err(format!("Failed to {context}: bad fanout table"));
+        }
+
+        // hashes, CRC32s and 4-byte offsets for every object (8-byte offsets are optional),
+        // then the pack and index checksums
+        let object_count = fanout[255] as u64;
+        let minimum_size = INDEX_FANOUT_END + object_count * (hash_size + 8) + 2 * hash_size;
+        let size = file.metadata()
+            .map_err(|io_err| format!("Failed to {context}: {io_err}"))?
+            .len();
+        if size < minimum_size {
+            return Err(format!("Failed to {context}: truncated (expected at least {minimum_size} bytes, got {size})"));
+        }
+
+        Ok(PackIndex {
+            idx_filename: String::from(idx_filename),
+            pack_filename: format!("{}.pack", idx_filename.strip_suffix(".idx").unwrap_or(idx_filename)),
+            fanout,
+            object_format,
+            file: Mutex::new(file),
+            pack: Mutex::new(None),
+        })
+    }
+
+    pub fn pack_filename(&self) -> &str {
+        &self.pack_filename
+    }
+
+    /// Number of objects in the pack
+    pub fn len(&self) -> usize {
+        self.fanout[255] as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn hash_size(&self) -> usize {
+        self.object_format.hash_size()
+    }
+
+    /// Positions (in the sorted hash table) of the hashes whose first byte is `first_byte`
+    fn fanout_range(&self, first_byte: u8) -> std::ops::Range<usize> {
+        let start = match first_byte {
+            0 => 0,
+            _ => self.fanout[first_byte as usize - 1] as usize,
+        };
+        start..self.fanout[first_byte as usize] as usize
+    }
+
+    fn read_at(&self, file: &mut std::fs::File, position: u64, buffer: &mut [u8]) -> CliResult<()> {
+        file.seek(SeekFrom::Start(position))
+            .and_then(|_| file.read_exact(buffer))
+            .map_err(|io_err| format!("Failed to read pack index `{}`: {io_err}", self.idx_filename))
+    }
+
+    /// Offset (in the pack) of the object at position `position` of the hash table
+    fn offset_at(&self, file: &mut std::fs::File, position: usize) -> CliResult<u64> {
+        // layout after the fanout table: hashes, CRC32s, 4-byte offsets, 8-byte offsets
+        let offsets_start = INDEX_FANOUT_END + (self.len() * (self.hash_size() + 4)) as u64;
+
+        let mut small_offset = [0; 4];
+        self.read_at(file, offsets_start + 4 * position as u64, &mut small_offset)?;
+        let small_offset = u32::from_be_bytes(small_offset);
+
+        if small_offset & 0x8000_0000 == 0 {
+            return Ok(small_offset as u64);
+        }
+
+        // the MSB flags an index into the table of large offsets
+        let large_offsets_start = offsets_start + 4 * self.len() as u64;
+        let mut large_offset = [0; 8];
+        self.read_at(file, large_offsets_start + 8 * (small_offset & 0x7fff_ffff) as u64, &mut large_offset)?;
+        Ok(u64::from_be_bytes(large_offset))
+    }
+
+    /// Offset of `hash` in the pack, if it is in the pack
+    pub fn find(&self, hash: &Hash) -> CliResult<Option<u64>> {
+        let hash_bytes = hash.as_bytes();
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut range = self.fanout_range(hash_bytes[0]);
+
+        let mut name = vec![0; self.hash_size()];
+        while !range.is_empty() {
+            let middle = range.start + range.len() / 2;
+            self.read_at(&mut file, INDEX_FANOUT_END + (middle * self.hash_size()) as u64, &mut name)?;
+
+            match name.as_slice().cmp(hash_bytes) {
+                std::cmp::Ordering::Equal => return self.offset_at(&mut file, middle).map(Some),
+                std::cmp::Ordering::Less => range.start = middle + 1,
+                std::cmp::Ordering::Greater => range.end = middle,
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Hashes in the pack whose (hex) name starts with `prefix` (at least 2 digits long), sorted
+    pub fn find_by_prefix(&self, prefix: &str) -> CliResult<Vec<Hash>> {
+        let Some(first_byte) = prefix.get(..2).and_then(|digits| u8::from_str_radix(digits, 16).ok())
+            else {
+                return Ok(Vec::new());
+            };
+
+        Ok(
+            self.read_hashes(self.fanout_range(first_byte))?
+                .into_iter()
+                .filter(|hash| hash.starts_with(prefix))
+                .collect()
+        )
+    }
+
+    /// All hashes in the pack, sorted
+    pub fn hashes(&self) -> CliResult<Vec<Hash>> {
+        self.read_hashes(0..self.len())
+    }
+
+    /// The hashes at positions `range` of the hash table
+    fn read_hashes(&self, range: std::ops::Range<usize>) -> CliResult<Vec<Hash>> {
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut names = vec![0; range.len() * self.hash_size()];
+        self.read_at(&mut file, INDEX_FANOUT_END + (range.start * self.hash_size()) as u64, &mut names)?;
+
+        Ok(
+            names.chunks_exact(self.hash_size())
+                .map(Hash::from_bytes)
+                .collect()
+        )
+    }
+
+    /// Type and contents of `hash`, if it is in the pack.
+    /// The bases of ref deltas that aren't in the pack are read from `store`.
+    pub fn read_object(&self, hash: &Hash, store: &dyn ObjectStore) -> CliResult<Option<(ObjectType, Vec<u8>)>> {
+        let Some(offset) = self.find(hash)?
+            else {
+                return Ok(None);
+            };
+
+        self.with_pack(|pack| pack.read_object(self, offset, store))
+            .map(Some)
+            .map_err(|msg| format!("Failed to read object `{}`: {msg}", hash.as_str()))
+    }
+
+    /// Header and a reader of the contents of `hash`, if it is in the pack.
+    /// A whole entry is inflated as it is read; a delta is applied in memory first.
+    /// Either way, the contents are checked against `hash` as they are read.
+    pub fn open_object(&self, hash: &Hash, store: &dyn ObjectStore) -> CliResult<Option<(ObjectHeader, Box<dyn Read>)>> {
+        let Some(offset) = self.find(hash)?
+            else {
+                return Ok(None);
+            };
+
+        let entry_header = self.with_pack(|pack| pack.read_entry_header(offset))
+            .map_err(|msg| format!("Failed to read object `{}`: {msg}", hash.as_str()))?;
+
+        let EntryKind::Whole(tipe) = entry_header.kind else {
+            return Ok(self.read_object(hash, store)?.map(|(tipe, contents)| {
+                let header = ObjectHeader { tipe, size: contents.len() };
+                let reader = VerifiedReader::new(std::io::Cursor::new(contents), &header, hash, self.object_format);
+                (header, Box::new(reader) as Box<dyn Read>)
+            }));
+        };
+
+        // a file of its own, as the open pack's position moves with other reads
+        let context = format!("read object `{}`", hash.as_str());
+        let mut file = with_context(&context, open_filename(&self.pack_filename))?;
+        file.seek(SeekFrom::Start(entry_header.data_offset))
+            .map_err(|io_err| format!("Failed to {context}: {io_err}"))?;
+
+        let header = ObjectHeader { tipe, size: entry_header.size };
+        let reader = VerifiedReader::new(flate2::read::ZlibDecoder::new(BufReader::new(file)), &header, hash, self.object_format);
+        Ok(Some((header, Box::new(reader))))
+    }
+
+    /// Type and size of `hash`, if it is in the pack
+    pub fn read_object_header(&self, hash: &Hash, store: &dyn ObjectStore) -> CliResult<Option<ObjectHeader>> {
+        let Some(offset) = self.find(hash)?
+            else {
+                return Ok(None);
+            };
+
+        self.with_pack(|pack| pack.read_object_header(self, offset, store))
+            .map(Some)
+            .map_err(|msg| format!("Failed to read object `{}`: {msg}", hash.as_str()))
+    }
+
+    /// Run `f` on the open pack, opening it if needed.
+    /// If it is already in use (a ref delta whose base is in another pack, whose
+    /// own base is in this one), `f` gets a pack opened just for it.
+    fn with_pack<T>(&self, f: impl FnOnce(&mut Pack) -> CliResult<T>) -> CliResult<T> {
+        let mut guard = match self.pack.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return f(&mut Pack::open(&self.pack_filename, self.object_format)?),
+        };
+
+        if guard.is_none() {
+            *guard = Some(Pack::open(&self.pack_filename, self.object_format)?);
+        }
+        f(guard.as_mut().unwrap())
+    }
+
+    /// Check the trailing checksums of the pack and the index
+    /// (and that the index was made for this pack)
+    pub fn verify_checksums(&self) -> CliResult<()> {
+        let read = |filename: &str| with_context("verify checksum", read_filename_to_bytes(filename));
+        let pack = read(&self.pack_filename)?;
+        let index = read(&self.idx_filename)?;
+
+        // the pack has a 12-byte header, and the index a header and fanout table,
+        // before their trailing checksums
+        let minimum_sizes = [12 + self.hash_size(), INDEX_FANOUT_END as usize + 2 * self.hash_size()];
+        for ((filename, contents), minimum_size) in [(&self.pack_filename, &pack), (&self.idx_filename, &index)].into_iter().zip(minimum_sizes) {
+            if contents.len() < minimum_size {
+                return Err(format!("`{filename}` is truncated ({} bytes)", contents.len()));
+            }
+
+            let (data, checksum) = contents.split_at(contents.len() - self.hash_size());
+            if self.object_format.digest(data).as_bytes() != checksum {
+                return Err(format!("Checksum mismatch for `{filename}`"));
+            }
+        }
+
+        // the index records the pack's checksum just before its own
+        let recorded_pack_checksum = &index[index.len() - 2 * self.hash_size()..index.len() - self.hash_size()];
+        if recorded_pack_checksum != &pack[pack.len() - self.hash_size()..] {
+            return Err(format!("Index `{}` doesn't match its pack", self.idx_filename));
+        }
+
+        Ok(())
+    }
+
+    /// Delete the index, then the pack
+    pub fn delete(self) -> CliResult<()> {
+        let PackIndex { idx_filename, pack_filename, .. } = self; // closes the files
+
+        forget_pack_indexes();
+        for filename in [&idx_filename, &pack_filename] {
+            std::fs::remove_file(filename)
+                .map_err(|io_err| format!("Failed to delete `{filename}`: {io_err}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// An open `.pack` file
+struct Pack {
+    filename: String,
+    object_format: ObjectFormat,
+    file: std::fs::File,
+    delta_bases: DeltaBaseCache,
+}
+
+/// Recently reconstructed delta bases, by offset, so that objects with deltas
+/// against the same base (e.g. successive versions of a file) don't rebuild it each time.
+/// The oldest entries are evicted once the total size exceeds `DELTA_BASE_CACHE_SIZE`.
+#[derive(Default)]
+struct DeltaBaseCache {
+    entries: HashMap<u64, (ObjectType, Vec<u8>)>,
+    /// Offsets, oldest first
+    order: VecDeque<u64>,
+    size: usize,
+}
+
+impl DeltaBaseCache {
+    fn get(&self, offset: u64) -> Option<(ObjectType, Vec<u8>)> {
+        self.entries.get(&offset).map(|(tipe, contents)| (*tipe, contents.clone()))
+    }
+
+    fn insert(&mut self, offset: u64, tipe: ObjectType, contents: &[u8]) {
+        if contents.len() > DELTA_BASE_CACHE_SIZE || self.entries.contains_key(&offset) {
+            return;
+        }
+
+        while self.size + contents.len() > DELTA_BASE_CACHE_SIZE {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some((_, evicted)) = self.entries.remove(&oldest) {
+                self.size -= evicted.len();
+            }
+        }
+
+        self.size += contents.len();
+        self.order.push_back(offset);
+        self.entries.insert(offset, (tipe, contents.to_vec()));
+    }
+}
+
+impl Pack {
+    fn open(filename: &str, object_format: ObjectFormat) -> CliResult<Self> {
+        let context = format!("read pack `{filename}`");
+        let mut file = with_context(&context, open_filename(filename))?;
+
+        let mut header = [0; 12];
+        file.read_exact(&mut header)
+            .map_err(|io_err| format!("Failed to {context}: {io_err}"))?;
+
+        if &header[..4] != PACK_SIGNATURE {
+            return Err(format!("Failed to {context}: bad signature"));
+        }
+
+        // version 3 only differs from version 2 in what it may be used for
+        let version = u32::from_be_bytes(header[4..8].try_into().unwrap());
+        if version != 2 && version != 3 {
+            return Err(format!("Failed to {context}: unsupported version {version}"));
+        }
+
+        Ok(Pack {
+            filename: String::from(filename),
+            object_format,
+            file,
+            delta_bases: DeltaBaseCache::default(),
+        })
+    }
+
+    fn corrupt(&self, offset: u64, msg: &str) -> String {
+        format!("Corrupt pack `{}`: entry at offset {offset}: {msg}", self.filename)
+    }
+
+    fn read_entry_header(&mut self, offset: u64) -> CliResult<EntryHeader> {
+        let filename = self.filename.clone();
+        let hash_size = self.object_format.hash_size();
+        let io_error = |io_err: std::io::Error| format!("Failed to read pack `{filename}`: {io_err}");
+
+        self.file.seek(SeekFrom::Start(offset)).map_err(io_error)?;
+        let mut reader = BufReader::new(&mut self.file);
+        let mut consumed = 0;
+        let mut next_byte = || -> CliResult<u8> {
+            let mut byte = [0];
+            reader.read_exact(&mut byte).map_err(io_error)?;
+            consumed += 1;
+            Ok(byte[0])
+        };
+
+        // type in bits 4-6 of the first byte; size in the low 4 bits, then 7 bits per byte
+        let mut byte = next_byte()?;
+        let type_number = (byte >> 4) & 0b111;
+        let mut size = (byte & 0b1111) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = next_byte()?;
+            if shift > usize::BITS - 7 {
+                return Err(self.corrupt(offset, "size too large"));
+            }
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+
+        let kind = match type_number {
+            1 => EntryKind::Whole(ObjectType::Commit),
+            2 => EntryKind::Whole(ObjectType::Tree),
+            3 => EntryKind::Whole(ObjectType::Blob),
+            4 => EntryKind::Whole(ObjectType::Tag),
+            6 => {
+                // big-endian, 7 bits per byte, with an implicit +1 on every continuation
+                // (a u64 fits in 9 bytes)
+                let mut byte = next_byte()?;
+                let mut distance = (byte & 0x7f) as u64;
+                let mut length = 1;
+                while byte & 0x80 != 0 {
+                    byte = next_byte()?;
+                    length += 1;
+                    distance = match distance.checked_add(1).and_then(|distance| distance.checked_mul(1 << 7)) {
+                        Some(distance) if length <= 9 => distance | (byte & 0x7f) as u64,
+                        _ => return Err(self.corrupt(offset, "delta base offset too large")),
+                    };
+                }
+
+                if distance == 0 || distance > offset {
+                    return Err(self.corrupt(offset, "bad delta base offset"));
+                }
+                EntryKind::OffsetDelta(offset - distance)
+            },
+            7 => {
+                let mut base_hash = vec![0; hash_size];
+                for byte in base_hash.iter_mut() {
+                    *byte = next_byte()?;
+                }
+                EntryKind::RefDelta(Hash::from_bytes(&base_hash))
+            },
+            _ => return Err(self.corrupt(offset, &format!("bad type {type_number}"))),
+        };
+
+        Ok(EntryHeader {
+            kind,
+            size,
+            data_offset: offset + consumed,
+        })
+    }
+
+    /// Inflate the zlib stream at `header.data_offset`, reading at most `limit` bytes
+    fn inflate(&mut self, header: &EntryHeader, limit: usize) -> CliResult<Vec<u8>> {
+        let filename = self.filename.clone();
+        let io_error = |io_err: std::io::Error| format!("Failed to read pack `{filename}`: {io_err}");
+
+        self.file.seek(SeekFrom::Start(header.data_offset)).map_err(io_error)?;
+
+        // `limit` comes from the pack, so it isn't trusted for the preallocation
+        let mut data = Vec::with_capacity(limit.min(DELTA_BASE_CACHE_SIZE));
+        flate2::read::ZlibDecoder::new(BufReader::new(&mut self.file))
+            .take(limit as u64)
+            .read_to_end(&mut data)
+            .map_err(io_error)?;
+
+        Ok(data)
+    }
+
+    fn inflate_entry(&mut self, offset: u64, header: &EntryHeader) -> CliResult<Vec<u8>> {
+        let data = self.inflate(header, header.size)?;
+        if data.len() != header.size {
+            return Err(self.corrupt(offset, "mismatched header and contents size"));
+        }
+        Ok(data)
+    }
+
+    /// Type and contents of the object at `offset`, with all deltas applied
+    fn read_object(&mut self, index: &PackIndex, mut offset: u64, store: &dyn ObjectStore) -> CliResult<(ObjectType, Vec<u8>)> {
+        let mut deltas = Vec::new();
+
+        // the base's offset is `None` if it's outside the pack
+        let (tipe, mut contents, base_offset) = loop {
+            if deltas.len() > MAX_DELTA_CHAIN_LENGTH {
+                return Err(self.corrupt(offset, "delta chain too long"));
+            }
+
+            if let Some((tipe, contents)) = self.delta_bases.get(offset) {
+                break (tipe, contents, Some(offset));
+            }
+
+            let header = self.read_entry_header(offset)?;
+            let data = self.inflate_entry(offset, &header)?;
+
+            match header.kind {
+                EntryKind::Whole(tipe) => break (tipe, data, Some(offset)),
+                EntryKind::OffsetDelta(base_offset) => {
+                    deltas.push((offset, data));
+                    offset = base_offset;
+                },
+                EntryKind::RefDelta(base_hash) => {
+                    deltas.push((offset, data));
+                    match index.find(&base_hash)? {
+                        Some(base_offset) => offset = base_offset,
+                        None => {
+                            let (tipe, contents) = store.read_raw(&base_hash)?;
+                            break (tipe, contents, None);
+                        },
+                    }
+                },
+            }
+        };
+
+        if let Some(base_offset) = base_offset.filter(|_| !deltas.is_empty()) {
+            self.delta_bases.insert(base_offset, tipe, &contents);
+        }
+
+        for (i, (offset, delta)) in deltas.iter().enumerate().rev() {
+            contents = apply_delta(&contents, delta)
+                .map_err(|msg| self.corrupt(*offset, &msg))?;
+
+            // every object along the chain but the requested one is a base
+            if i > 0 {
+                self.delta_bases.insert(*offset, tipe, &contents);
+            }
+        }
+
+        Ok((tipe, contents))
+    }
+
+    /// Type and size of the object at `offset`, without applying any deltas
+    fn read_object_header(&mut self, index: &PackIndex, mut offset: u64, store: &dyn ObjectStore) -> CliResult<ObjectHeader> {
+        let mut size = None;
+
+        for _ in 0..MAX_DELTA_CHAIN_LENGTH {
+            let header = self.read_entry_header(offset)?;
+
+            let base_offset = match &header.kind {
+                EntryKind::Whole(tipe) => return Ok(ObjectHeader {
+                    tipe: *tipe,
+                    size: size.unwrap_or(header.size),
+                }),
+                EntryKind::OffsetDelta(base_offset) => Some(*base_offset),
+                EntryKind::RefDelta(base_hash) => index.find(base_hash)?,
+            };
+
+            if size.is_none() {
+                // the delta starts with two sizes (base, then result): 10 bytes each, at most
+                let delta_start = self.inflate(&header, header.size.min(20))?;
+                let mut cursor = delta_start.as_slice();
+                read_delta_size(&mut cursor)
+                    .and_then(|_| read_delta_size(&mut cursor))
+                    .map(|result_size| size = Some(result_size))
+                    .map_err(|msg| self.corrupt(offset, &msg))?;
+            }
+
+            match (base_offset, &header.kind) {
+                (Some(base_offset), _) => offset = base_offset,
+                (None, EntryKind::RefDelta(base_hash)) => return Ok(ObjectHeader {
+                    tipe: store.read_header(base_hash)?.tipe,
+                    size: size.unwrap(),
+                }),
+                (None, _) => unreachable!("only ref deltas can have a base outside the pack"),
+            }
+        }
+
+        Err(self.corrupt(offset, "delta chain too long"))
+    }
+}
+
+/// Read one of the variable-length sizes at the start of a delta
+fn read_delta_size(cursor: &mut &[u8]) -> CliResult<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+
+    loop {
+        let (&byte, rest) = cursor.split_first()
+            .ok_or(String::from("truncated delta header"))?;
+        *cursor = rest;
+
+        if shift > usize::BITS - 7 {
+            return Err(String::from("delta size too large"));
+        }
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+/// Reconstruct an object from its base and a delta:
+/// a sequence of instructions to either copy a range of the base, or insert new bytes
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> CliResult<Vec<u8>> {
+    let mut cursor = delta;
+
+    let base_size = read_delta_size(&mut cursor)?;
+    if base_size != base.len() {
+        return Err(format!("delta expects a base of {base_size} bytes, but it has {}", base.len()));
+    }
+
+    let result_size = read_delta_size(&mut cursor)?;
+    let mut result = Vec::with_capacity(result_size.min(DELTA_BASE_CACHE_SIZE));
+    let too_long = || format!("delta produces more than its {result_size} bytes");
+
+    while let Some((&instruction, rest)) = cursor.split_first() {
+        cursor = rest;
+
+        if instruction & 0x80 != 0 {
+            // copy: bits 0-3 say which offset bytes follow, bits 4-6 which size bytes
+            let mut next_byte = || cursor.split_first()
+                .map(|(&byte, rest)| {
+                    cursor = rest;
+                    byte as usize
+                })
+                .ok_or(String::from("truncated copy instruction"));
+
+            let mut copy_offset = 0;
+            for i in 0..4 {
+                if instruction & (1 << i) != 0 {
+                    copy_offset |= next_byte()? << (8 * i);
+                }
+            }
+
+            let mut copy_size = 0;
+            for i in 0..3 {
+                if instruction & (1 << (4 + i)) != 0 {
+                    copy_size |= next_byte()? << (8 * i);
+                }
+            }
+            if copy_size == 0 {
+                copy_size = 0x10000;
+            }
+
+            let chunk = base.get(copy_offset..copy_offset + copy_size)
+                .ok_or(String::from("copy instruction out of the base's bounds"))?;
+            if result.len() + chunk.len() > result_size {
+                return Err(too_long());
+            }
+            result.extend_from_slice(chunk);
+        } else if instruction != 0 {
+            // insert: the instruction is the number of bytes to insert
+            let length = instruction as usize;
+            if cursor.len() < length {
+                return Err(String::from("truncated insert instruction"));
+            }
+
+            let (inserted, rest) = cursor.split_at(length);
+            if result.len() + inserted.len() > result_size {
+                return Err(too_long());
+            }
+            result.extend_from_slice(inserted);
+            cursor = rest;
+        } else {
+            return Err(String::from("reserved delta instruction"));
+        }
+    }
+
+    if result.len() != result_size {
+        return Err(format!("delta should produce {result_size} bytes, but produced {}", result.len()));
+    }
+
+    Ok(result)
+}
+
+/// Indexes of all packs in `directory` (skipping those without a `.pack`), sorted by filename
+pub fn list_pack_indexes(directory: &str, object_format: ObjectFormat) -> CliResult<Vec<PackIndex>> {
+    if !std::path::Path::new(directory).is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut idx_filenames = std::fs::read_dir(directory)
+        .map_err(|io_err| format!("Failed to list packs: error while reading directory `{directory}`: {io_err}"))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.path().to_str().map(String::from))
+        .filter(|filename| filename.ends_with(".idx"))
+        .collect::<Vec<_>>();
+    idx_filenames.sort();
+
+    idx_filenames.iter()
+        .map(|filename| PackIndex::open(filename, object_format))
+        .filter(|index| index.as_ref().map_or(true, |index| std::path::Path::new(index.pack_filename()).is_file()))
+        .collect()
+}
+
+/// Same as `list_pack_indexes`, but loaded once and kept open:
+/// the pack directory is only listed again once it has changed
+pub fn loaded_pack_indexes(directory: &str, object_format: ObjectFormat) -> CliResult<Arc<Vec<PackIndex>>> {
+    // taken before listing: if the directory changes in between, the next call lists it again
+    let stamp = with_context("list packs", FileStamp::read(directory))?;
+
+    let mut loaded = LOADED_INDEXES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let is_current = |loaded: &&LoadedIndexes| loaded.directory == directory && loaded.object_format == object_format && loaded.stamp == stamp;
+    if let Some(loaded) = loaded.as_ref().filter(is_current) {
+        return Ok(loaded.indexes.clone());
+    }
+
+    let indexes = Arc::new(list_pack_indexes(directory, object_format)?);
+    *loaded = Some(LoadedIndexes { directory: String::from(directory), object_format, stamp, indexes: indexes.clone() });
+    Ok(indexes)
+}
+
+/// Make the next lookup list the packs again (after one was written or deleted)
+pub fn forget_pack_indexes() {
+    *LOADED_INDEXES.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+}
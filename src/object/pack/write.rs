@@ -0,0 +1,450 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufWriter, Write};
+
+use crate::cli::{with_context, CliResult};
+use crate::config::read_config_option;
+use crate::hash::{Hash, Hasher, ObjectFormat};
+use crate::io::LockFile;
+use crate::object::store::ObjectStore;
+use crate::object::ObjectType;
+
+use super::{INDEX_SIGNATURE, INDEX_VERSION, PACK_SIGNATURE};
+
+/// Packs are written once and read many times,
+/// so they are worth compressing harder than loose objects
+const PACK_COMPRESSION_LEVEL: u8 = 6;
+
+/// Matches shorter than this aren't found (or worth a copy instruction)
+const DELTA_BLOCK_SIZE: usize = 16;
+/// Largest copy a single instruction can express (3 size bytes)
+const MAX_COPY_SIZE: usize = 0xff_ffff;
+/// Largest insert a single instruction can express (7 bits)
+const MAX_INSERT_SIZE: usize = 0x7f;
+
+pub struct PackOptions {
+    /// Number of preceding objects (in type/name/size order) tried as delta bases for each object
+    pub window: usize,
+    /// Longest allowed chain of deltas
+    pub depth: usize,
+    /// Objects larger than this aren't deltified, but streamed into the pack as they are read
+    pub big_file_threshold: usize,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        // same as git
+        PackOptions {
+            window: 10,
+            depth: 50,
+            big_file_threshold: 512 << 20,
+        }
+    }
+}
+
+/// `core.bigFileThreshold`: a number of bytes, optionally suffixed with `k`, `m`, or `g`
+/// (the default if it isn't set)
+pub fn read_big_file_threshold() -> CliResult<usize> {
+    let Some(value) = with_context("read core.bigFileThreshold", read_config_option("core.bigFileThreshold"))? else {
+        return Ok(PackOptions::default().big_file_threshold);
+    };
+
+    let value = value.trim();
+    let (number, shift) = match value.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&value[..i], 10),
+        Some((i, 'm' | 'M')) => (&value[..i], 20),
+        Some((i, 'g' | 'G')) => (&value[..i], 30),
+        _ => (value, 0),
+    };
+
+    number.parse::<usize>().ok()
+        .and_then(|number| number.checked_mul(1 << shift))
+        .ok_or(format!("Bad core.bigFileThreshold: `{value}`"))
+}
+
+/// Number of the type in pack entry headers
+fn type_number(tipe: ObjectType) -> u8 {
+    match tipe {
+        ObjectType::Commit => 1,
+        ObjectType::Tree => 2,
+        ObjectType::Blob => 3,
+        ObjectType::Tag => 4,
+    }
+}
+
+/// A hash of the path an object was found at, such that files with the same
+/// name (especially the same ending) sort near each other (same as git)
+fn name_hash(name: &str) -> u32 {
+    name.bytes()
+        .filter(|c| !c.is_ascii_whitespace())
+        .fold(0, |hash: u32, c| (hash >> 2).wrapping_add((c as u32) << 24))
+}
+
+fn encode_delta_size(mut size: usize, delta: &mut Vec<u8>) {
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            delta.push(byte);
+            return;
+        }
+        delta.push(byte | 0x80);
+    }
+}
+
+fn push_insert(delta: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(MAX_INSERT_SIZE) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+fn push_copy(delta: &mut Vec<u8>, mut offset: usize, mut size: usize) {
+    while size > 0 {
+        let chunk_size = size.min(MAX_COPY_SIZE);
+
+        // only the non-zero bytes of the offset and size are stored, flagged in the opcode
+        let mut opcode = 0x80;
+        let mut arguments = Vec::with_capacity(7);
+        for (i, byte) in (offset as u32).to_le_bytes().into_iter().enumerate() {
+            if byte != 0 {
+                opcode |= 1 << i;
+                arguments.push(byte);
+            }
+        }
+        for (i, byte) in (chunk_size as u32).to_le_bytes()[..3].iter().enumerate() {
+            if *byte != 0 {
+                opcode |= 1 << (4 + i);
+                arguments.push(*byte);
+            }
+        }
+
+        delta.push(opcode);
+        delta.extend(arguments);
+
+        offset += chunk_size;
+        size -= chunk_size;
+    }
+}
+
+/// A delta that turns `base` into `target` (the inverse of `apply_delta`),
+/// if one can be found that is at most `max_size` bytes long.
+/// Matches are found by indexing the base in blocks of `DELTA_BLOCK_SIZE`
+/// bytes, then extending them in both directions.
+pub fn create_delta(base: &[u8], target: &[u8], max_size: usize) -> Option<Vec<u8>> {
+    if base.len() > u32::MAX as usize || base.len() < DELTA_BLOCK_SIZE {
+        return None;
+    }
+
+    let mut blocks = HashMap::new();
+    for offset in (0..=base.len() - DELTA_BLOCK_SIZE).step_by(DELTA_BLOCK_SIZE) {
+        blocks.entry(&base[offset..offset + DELTA_BLOCK_SIZE]).or_insert(offset);
+    }
+
+    let mut delta = Vec::new();
+    encode_delta_size(base.len(), &mut delta);
+    encode_delta_size(target.len(), &mut delta);
+
+    let mut insert_start = 0;
+    let mut position = 0;
+
+    while position + DELTA_BLOCK_SIZE <= target.len() {
+        let Some(&block_offset) = blocks.get(&target[position..position + DELTA_BLOCK_SIZE])
+            else {
+                position += 1;
+                continue;
+            };
+
+        let (mut start, mut base_start) = (position, block_offset);
+        while start > insert_start && base_start > 0 && target[start - 1] == base[base_start - 1] {
+            start -= 1;
+            base_start -= 1;
+        }
+
+        let (mut end, mut base_end) = (position + DELTA_BLOCK_SIZE, block_offset + DELTA_BLOCK_SIZE);
+        while end < target.len() && base_end < base.len() && target[end] == base[base_end] {
+            end += 1;
+            base_end += 1;
+        }
+
+        push_insert(&mut delta, &target[insert_start..start]);
+        push_copy(&mut delta, base_start, end - start);
+
+        if delta.len() > max_size {
+            return None;
+        }
+
+        position = end;
+        insert_start = end;
+    }
+
+    push_insert(&mut delta, &target[insert_start..]);
+    (delta.len() <= max_size).then_some(delta)
+}
+
+/// An object to be packed (its contents are only read when it is written)
+struct PackObject {
+    hash: Hash,
+    tipe: ObjectType,
+    size: usize,
+    name_hash: u32,
+}
+
+/// The best delta for `objects[target_idx]` (with contents `target`) against the objects
+/// in `window` (`(index, contents)` of the objects written just before it), if one is worth it
+fn find_delta(
+    objects: &[PackObject],
+    depths: &[usize],
+    window: &VecDeque<(usize, Vec<u8>)>,
+    target_idx: usize,
+    target: &[u8],
+    options: &PackOptions,
+) -> Option<(usize, Vec<u8>)> {
+    // a delta that isn't at most half of the object doesn't pay for the extra lookups
+    let mut max_size = target.len() / 2;
+    let mut best = None;
+
+    for (base_idx, base) in window.iter().rev() {
+        if objects[*base_idx].tipe != objects[target_idx].tipe || depths[*base_idx] >= options.depth {
+            continue;
+        }
+
+        // every byte missing from the base must be inserted
+        if target.len().saturating_sub(base.len()) >= max_size {
+            continue;
+        }
+
+        if let Some(delta) = create_delta(base, target, max_size) {
+            max_size = delta.len().saturating_sub(1);
+            best = Some((*base_idx, delta));
+        }
+    }
+
+    best
+}
+
+fn push_entry_header(pack: &mut Vec<u8>, type_number: u8, size: usize) {
+    // type in bits 4-6 of the first byte; size in the low 4 bits, then 7 bits per byte
+    let mut byte = (type_number << 4) | (size & 0b1111) as u8;
+    let mut size = size >> 4;
+    while size != 0 {
+        pack.push(byte | 0x80);
+        byte = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+    pack.push(byte);
+}
+
+fn push_base_distance(pack: &mut Vec<u8>, mut distance: u64) {
+    // big-endian, 7 bits per byte, with an implicit +1 on every continuation
+    let mut bytes = vec![(distance & 0x7f) as u8];
+    distance >>= 7;
+    while distance != 0 {
+        distance -= 1;
+        bytes.push(0x80 | (distance & 0x7f) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+    pack.extend(bytes);
+}
+
+/// Index (version 2) of a pack whose entries are `(hash, offset, crc32)`
+fn build_index(mut entries: Vec<(Hash, u64, u32)>, pack_checksum: &Hash, object_format: ObjectFormat) -> Vec<u8> {
+    entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+    let mut index = INDEX_SIGNATURE.to_vec();
+    index.extend(INDEX_VERSION.to_be_bytes());
+
+    let mut count = 0;
+    for first_byte in 0..=255 {
+        count += entries.iter().skip(count).take_while(|(hash, _, _)| hash.as_bytes()[0] == first_byte).count();
+        index.extend((count as u32).to_be_bytes());
+    }
+
+    for (hash, _, _) in &entries {
+        index.extend_from_slice(hash.as_bytes());
+    }
+    for (_, _, crc) in &entries {
+        index.extend(crc.to_be_bytes());
+    }
+
+    // offsets that don't fit in 31 bits go in a separate table of 8-byte offsets
+    let mut large_offsets = Vec::new();
+    for (_, offset, _) in &entries {
+        if *offset < 0x8000_0000 {
+            index.extend((*offset as u32).to_be_bytes());
+        } else {
+            index.extend((0x8000_0000 | large_offsets.len() as u32).to_be_bytes());
+            large_offsets.push(*offset);
+        }
+    }
+    for offset in large_offsets {
+        index.extend(offset.to_be_bytes());
+    }
+
+    index.extend_from_slice(pack_checksum.as_bytes());
+    let index_checksum = object_format.digest(&index);
+    index.extend_from_slice(index_checksum.as_bytes());
+
+    index
+}
+
+/// The pack file being written, with its length and checksum so far
+struct PackWriter<W: Write> {
+    output: W,
+    hasher: Hasher,
+    offset: u64,
+}
+
+impl<W: Write> PackWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.output.write_all(bytes)?;
+        self.hasher.update(bytes);
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Append the checksum of everything written, and return it
+    fn finish(mut self) -> std::io::Result<Hash> {
+        let checksum = self.hasher.finalize();
+        self.output.write_all(checksum.as_bytes())?;
+        self.output.flush()?;
+        Ok(checksum)
+    }
+}
+
+/// An entry being written to `pack`, with the CRC32 of its bytes so far (for the index)
+struct EntryWriter<'a, W: Write> {
+    pack: &'a mut PackWriter<W>,
+    crc: flate2::Crc,
+}
+
+impl<W: Write> Write for EntryWriter<'_, W> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.pack.write(bytes)?;
+        self.crc.update(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write `objects` of `store` (hashes, with the path they were found at, or "") into
+/// `<base_name>-<checksum>.pack` and its `.idx`, and return the checksum.
+/// Duplicates are only packed once.
+///
+/// Objects are sorted by type, name, and size (largest first), and each one is compared
+/// to the `options.window` objects before it, so only those are held in memory:
+/// entries are written as they are made (bases always come before their deltas).
+/// Objects larger than `options.big_file_threshold` are never held in memory.
+pub fn write_pack(store: &impl ObjectStore, objects: &[(Hash, String)], base_name: &str, options: &PackOptions) -> CliResult<Hash> {
+    let object_format = store.object_format();
+    let mut seen = HashSet::new();
+    let mut objects = objects.iter()
+        .filter(|(hash, _)| seen.insert(hash.clone()))
+        .map(|(hash, name)| {
+            let header = store.read_header(hash)?;
+            Ok(PackObject {
+                hash: hash.clone(),
+                tipe: header.tipe,
+                size: header.size,
+                name_hash: name_hash(name),
+            })
+        })
+        .collect::<CliResult<Vec<_>>>()?;
+    objects.sort_by_key(|object| (type_number(object.tipe), object.name_hash, Reverse(object.size)));
+
+    // named after its checksum once written
+    let context = format!("write `{base_name}.pack`");
+    let mut lock = with_context(&context, LockFile::acquire(&format!("{base_name}.pack")))?;
+    let io_error = |io_err: std::io::Error| format!("Failed to {context}: {io_err}");
+
+    let mut pack = PackWriter {
+        output: BufWriter::new(&mut lock),
+        hasher: Hasher::new(object_format),
+        offset: 0,
+    };
+
+    pack.write(PACK_SIGNATURE).map_err(io_error)?;
+    pack.write(&2u32.to_be_bytes()).map_err(io_error)?;
+    pack.write(&(objects.len() as u32).to_be_bytes()).map_err(io_error)?;
+
+    let mut window = VecDeque::with_capacity(options.window + 1);
+    let mut depths = vec![0; objects.len()];
+    let mut offsets = vec![0; objects.len()];
+    let mut index_entries = Vec::with_capacity(objects.len());
+
+    for (i, object) in objects.iter().enumerate() {
+        let offset = pack.offset;
+        offsets[i] = offset;
+
+        if object.size > options.big_file_threshold {
+            let (header, mut contents) = store.open(&object.hash)?;
+            let mut entry = EntryWriter {
+                pack: &mut pack,
+                crc: flate2::Crc::new(),
+            };
+
+            let mut entry_header = Vec::new();
+            push_entry_header(&mut entry_header, type_number(header.tipe), header.size);
+            entry.write_all(&entry_header).map_err(io_error)?;
+
+            let compression = flate2::Compression::new(PACK_COMPRESSION_LEVEL as u32);
+            let mut encoder = flate2::write::ZlibEncoder::new(&mut entry, compression);
+            let size = std::io::copy(&mut contents, &mut encoder).map_err(io_error)?;
+            encoder.finish().map_err(io_error)?;
+
+            if size != header.size as u64 {
+                return Err(format!("Object {} has {size} bytes, not the {} in its header", object.hash.as_str(), header.size));
+            }
+
+            index_entries.push((object.hash.clone(), offset, entry.crc.sum()));
+            continue;
+        }
+
+        let (_, contents) = store.read_raw(&object.hash)?;
+
+        let mut entry = Vec::new();
+        match find_delta(&objects, &depths, &window, i, &contents, options) {
+            None => {
+                push_entry_header(&mut entry, type_number(object.tipe), contents.len());
+                entry.extend(miniz_oxide::deflate::compress_to_vec_zlib(&contents, PACK_COMPRESSION_LEVEL));
+            },
+            Some((base_idx, delta)) => {
+                push_entry_header(&mut entry, 6, delta.len());
+                push_base_distance(&mut entry, offset - offsets[base_idx]);
+                entry.extend(miniz_oxide::deflate::compress_to_vec_zlib(&delta, PACK_COMPRESSION_LEVEL));
+                depths[i] = depths[base_idx] + 1;
+            },
+        }
+        pack.write(&entry).map_err(io_error)?;
+
+        let mut crc = flate2::Crc::new();
+        crc.update(&entry);
+        index_entries.push((object.hash.clone(), offset, crc.sum()));
+
+        window.push_back((i, contents));
+        if window.len() > options.window {
+            window.pop_front();
+        }
+    }
+
+    let pack_checksum = pack.finish().map_err(io_error)?;
+
+    // the index goes last: packs are only looked at once they have one
+    let pack_filename = format!("{base_name}-{}.pack", pack_checksum.as_str());
+    with_context(&format!("write `{pack_filename}`"), lock.commit_as(&pack_filename))?;
+
+    let index = build_index(index_entries, &pack_checksum, object_format);
+    let idx_filename = format!("{base_name}-{}.idx", pack_checksum.as_str());
+    let context = format!("write `{idx_filename}`");
+
+    let mut lock = with_context(&context, LockFile::acquire(&idx_filename))?;
+    with_context(&context, lock.write_all(&index))?;
+    with_context(&context, lock.commit())?;
+
+    super::forget_pack_indexes();
+    Ok(pack_checksum)
+}
@@ -23,6 +23,7 @@ impl<'b> Object<'b> {
                     })
                     .collect::<CliResult<String>>(),
             Self::Commit(commit_object) => Ok(commit_object.to_string()),
+            Self::Tag(tag_object) => Ok(tag_object.serialize()),
         }
     }
 }
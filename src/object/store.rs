@@ -0,0 +1,571 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::cli::{with_context, CliResult};
+use crate::hash::{Hash, Hasher, ObjectFormat};
+use crate::io::{create_directory_all_idempotent, dot_mush_slash, file_exists, open_filename, read_filename_to_bytes};
+
+use super::pack::{self, PackIndex};
+use super::{Object, ObjectHeader, ObjectType, COMPRESSION_LEVEL};
+
+/// Blobs are streamed in chunks of this size, so that big files don't have to fit in memory
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Longest object header that is accepted (e.g. `commit 1234`)
+const MAX_HEADER_SIZE: usize = 32;
+
+/// Stream the stored form of a blob (header, then the `size` bytes read from `reader`)
+/// to `sink`, and return the blob's hash
+fn stream_blob(
+    reader: &mut dyn Read,
+    size: u64,
+    object_format: ObjectFormat,
+    mut sink: impl FnMut(&[u8]) -> CliResult<()>,
+) -> CliResult<Hash> {
+    let mut hasher = Hasher::new(object_format);
+    let header = format!("blob {size}\0");
+    hasher.update(header.as_bytes());
+    sink(header.as_bytes())?;
+
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut remaining = size;
+    while remaining > 0 {
+        let chunk_size = remaining.min(CHUNK_SIZE as u64) as usize;
+        let read = match reader.read(&mut buffer[..chunk_size]) {
+            Ok(0) => return Err(format!("Failed to read blob: expected {size} bytes, got {}", size - remaining)),
+            Ok(read) => read,
+            Err(io_err) if io_err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(io_err) => return Err(format!("Failed to read blob: {io_err}")),
+        };
+
+        hasher.update(&buffer[..read]);
+        sink(&buffer[..read])?;
+        remaining -= read as u64;
+    }
+
+    hasher.try_finalize()
+}
+
+/// Hash of the blob whose contents are the `size` bytes read from `reader` (a chunk at a time)
+pub fn hash_blob(reader: &mut dyn Read, size: u64, object_format: ObjectFormat) -> CliResult<Hash> {
+    stream_blob(reader, size, object_format, |_| Ok(()))
+}
+
+/// Reads the contents of an object (`header.size` bytes from `inner`), failing once the
+/// stream turns out to be shorter or longer than that, or not to hash to the object's hash
+pub struct VerifiedReader<R: Read> {
+    inner: R,
+    hash: Hash,
+    size: u64,
+    remaining: u64,
+    /// `None` once the end has been verified
+    hasher: Option<Hasher>,
+}
+
+impl<R: Read> VerifiedReader<R> {
+    pub fn new(inner: R, header: &ObjectHeader, hash: &Hash, object_format: ObjectFormat) -> Self {
+        let mut hasher = Hasher::new(object_format);
+        hasher.update(format!("{} {}\0", header.tipe.to_str(), header.size).as_bytes());
+
+        VerifiedReader {
+            inner,
+            hash: hash.clone(),
+            size: header.size as u64,
+            remaining: header.size as u64,
+            hasher: Some(hasher),
+        }
+    }
+
+    fn error(&self, kind: std::io::ErrorKind, msg: String) -> std::io::Error {
+        std::io::Error::new(kind, format!("Object `{}` is corrupt: {msg}", self.hash.as_str()))
+    }
+
+    /// Check that nothing follows the contents, and that they hash to the object's hash
+    fn verify_end(&mut self) -> std::io::Result<()> {
+        let mut byte = [0];
+        let extra = loop {
+            match self.inner.read(&mut byte) {
+                Err(io_err) if io_err.kind() == std::io::ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        if extra != 0 {
+            return Err(self.error(std::io::ErrorKind::InvalidData, format!("more than the {} bytes of its header", self.size)));
+        }
+
+        let hash = self.hasher.take()
+            .expect("VerifiedReader invariant violated: end verified twice")
+            .finalize();
+        if hash != self.hash {
+            return Err(self.error(std::io::ErrorKind::InvalidData, format!("its contents hash to {}", hash.as_str())));
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for VerifiedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.hasher.is_none() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            self.verify_end()?;
+            return Ok(0);
+        }
+
+        let max_read = buf.len().min(self.remaining.try_into().unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..max_read])?;
+        if read == 0 {
+            let msg = format!("expected {} bytes, got {}", self.size, self.size - self.remaining);
+            return Err(self.error(std::io::ErrorKind::UnexpectedEof, msg));
+        }
+
+        self.hasher.as_mut().unwrap().update(&buf[..read]);
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// A place to keep objects, by hash
+pub trait ObjectStore {
+    /// The hash algorithm naming the objects of the store
+    fn object_format(&self) -> ObjectFormat;
+
+    /// Whether `hash` is in the store
+    fn has(&self, hash: &Hash) -> CliResult<bool>;
+
+    /// Type and contents of an object, without decoding the contents
+    fn read_raw(&self, hash: &Hash) -> CliResult<(ObjectType, Vec<u8>)>;
+
+    fn read(&self, hash: &Hash) -> CliResult<Object<'static>> {
+        let (tipe, contents) = self.read_raw(hash)?;
+        Object::from_contents(tipe, contents, self.object_format())
+            .map_err(|msg| format!("Error while reading object: {msg}"))
+    }
+
+    fn read_header(&self, hash: &Hash) -> CliResult<ObjectHeader> {
+        let (tipe, contents) = self.read_raw(hash)?;
+        Ok(ObjectHeader { tipe, size: contents.len() })
+    }
+
+    /// Header of an object, and a reader of its contents
+    fn open(&self, hash: &Hash) -> CliResult<(ObjectHeader, Box<dyn Read>)> {
+        let (tipe, contents) = self.read_raw(hash)?;
+        Ok((ObjectHeader { tipe, size: contents.len() }, Box::new(std::io::Cursor::new(contents))))
+    }
+
+    /// Add the object of type `tipe` with exactly these `contents` (if it isn't already there),
+    /// and return its hash. Unlike `write`, the contents are never decoded and re-encoded.
+    fn write_raw(&self, tipe: ObjectType, contents: &[u8]) -> CliResult<Hash>;
+
+    /// Add `object` to the store (if it isn't already there), and return its hash
+    fn write(&self, object: &Object) -> CliResult<Hash> {
+        let (tipe, contents) = Object::split_stored(object.store())?;
+        self.write_raw(tipe, &contents)
+    }
+
+    /// Add the blob whose contents are the `size` bytes read from `reader`, and return its hash
+    fn write_blob(&self, reader: &mut dyn Read, size: u64) -> CliResult<Hash> {
+        let mut stored = Vec::new();
+        stream_blob(reader, size, self.object_format(), |chunk| {
+            stored.extend_from_slice(chunk);
+            Ok(())
+        })?;
+
+        let (_, contents) = Object::split_stored(stored)?;
+        self.write(&Object::Blob(Cow::Owned(contents)))
+    }
+
+    /// Hashes of all objects in the store, sorted
+    fn hashes(&self) -> CliResult<Vec<Hash>>;
+
+    /// Hashes of the objects whose (hex) name starts with `prefix` (in lowercase), sorted
+    fn find_by_prefix(&self, prefix: &str) -> CliResult<Vec<Hash>> {
+        Ok(
+            self.hashes()?
+                .into_iter()
+                .filter(|hash| hash.starts_with(prefix))
+                .collect()
+        )
+    }
+}
+
+/// Objects as zlib-compressed files, in the fanout directories (`xx/`) of an objects directory
+pub struct LooseObjectStore {
+    directory: String,
+    object_format: ObjectFormat,
+}
+
+impl LooseObjectStore {
+    /// The store in `directory` (e.g. `.mush/objects`), of a repository using `object_format`
+    pub fn new(directory: &str, object_format: ObjectFormat) -> Self {
+        LooseObjectStore { directory: String::from(directory), object_format }
+    }
+
+    /// The store of the current repository
+    pub fn current() -> CliResult<Self> {
+        let directory = with_context("resolve path", dot_mush_slash("objects"))?;
+        Ok(Self::new(&directory, ObjectFormat::load()?))
+    }
+
+    /// A new file name in the store's directory, for an object whose hash isn't known yet
+    fn temporary_filename(&self) -> String {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        format!("{}/tmp_obj_{}_{}", self.directory, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Move a complete object from `temporary_filename` to its place in the store.
+    /// The temporary file is removed if that fails.
+    fn move_into_place(&self, temporary_filename: &str, hash: &Hash) -> CliResult<()> {
+        let object_filename = self.object_filename(hash);
+        let result = std::path::Path::new(&object_filename).parent()
+            .map_or(Ok(()), |directory| with_context("write object", create_directory_all_idempotent(&directory.to_string_lossy())))
+            .and_then(|_| {
+                std::fs::rename(temporary_filename, &object_filename)
+                    .map_err(|io_err| format!("Failed to write object: error while renaming `{temporary_filename}` to `{object_filename}`: {io_err}"))
+            });
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(temporary_filename);
+        }
+        result
+    }
+
+    /// Where the object `hash` is (or would be) stored
+    pub fn object_filename(&self, hash: &Hash) -> String {
+        match hash.len() < 2 {
+            // obviously not a correct hash, but just return something plausible
+            true => format!("{}/{}", self.directory, hash.as_str()),
+            false => format!("{}/{}/{}", self.directory, &hash[..2], &hash[2..]),
+        }
+    }
+
+    /// Delete the object `hash` (and its fanout directory, if left empty)
+    pub fn delete(&self, hash: &Hash) -> CliResult<()> {
+        let object_filename = self.object_filename(hash);
+        std::fs::remove_file(&object_filename)
+            .map_err(|io_err| format!("Failed to delete object `{}`: {io_err}", hash.as_str()))?;
+
+        if let Some(directory) = std::path::Path::new(&object_filename).parent() {
+            let _ = std::fs::remove_dir(directory); // only succeeds if it's empty
+        }
+        Ok(())
+    }
+}
+
+impl ObjectStore for LooseObjectStore {
+    fn object_format(&self) -> ObjectFormat {
+        self.object_format
+    }
+
+    fn has(&self, hash: &Hash) -> CliResult<bool> {
+        Ok(file_exists(&self.object_filename(hash)))
+    }
+
+    fn read_raw(&self, hash: &Hash) -> CliResult<(ObjectType, Vec<u8>)> {
+        let object_contents = with_context("read object", read_filename_to_bytes(&self.object_filename(hash)))?;
+        Object::split_compressed_bytes(&object_contents)
+            .map_err(|msg| format!("Error while reading object: {msg}"))
+    }
+
+    /// Only decompresses the header
+    fn read_header(&self, hash: &Hash) -> CliResult<ObjectHeader> {
+        let file = with_context("get object header", open_filename(&self.object_filename(hash)))?;
+        ObjectHeader::extract_from_file(file, hash)
+    }
+
+    /// Only decompresses the contents as they are read (which checks them against `hash`)
+    fn open(&self, hash: &Hash) -> CliResult<(ObjectHeader, Box<dyn Read>)> {
+        let file = with_context("read object", open_filename(&self.object_filename(hash)))?;
+        let mut decoder = flate2::read::ZlibDecoder::new(file);
+
+        let mut header = Vec::new();
+        let mut byte = [0];
+        loop {
+            decoder.read_exact(&mut byte)
+                .map_err(|io_err| format!("Failed to read object `{}`: {io_err}", hash.as_str()))?;
+            match byte[0] {
+                b'\0' => break,
+                _ if header.len() >= MAX_HEADER_SIZE => return Err(format!("Failed to read object `{}`: Bad object header", hash.as_str())),
+                byte => header.push(byte),
+            }
+        }
+
+        let header = ObjectHeader::from_bytes(&header)
+            .map_err(|msg| format!("Failed to read object `{}`: {msg}", hash.as_str()))?;
+        let contents = VerifiedReader::new(decoder, &header, hash, self.object_format);
+        Ok((header, Box::new(contents)))
+    }
+
+    /// Writes to a temporary file, then moves it into place,
+    /// so that readers never see a partially written object
+    fn write_raw(&self, tipe: ObjectType, contents: &[u8]) -> CliResult<Hash> {
+        let stored = Object::store_raw(tipe, contents);
+        let hash = Hash::from_bytes(&self.object_format.try_digest(&stored)?);
+        if self.has(&hash)? {
+            return Ok(hash);
+        }
+
+        let temporary_filename = self.temporary_filename();
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&stored, COMPRESSION_LEVEL);
+        if let Err(io_err) = std::fs::write(&temporary_filename, compressed) {
+            let _ = std::fs::remove_file(&temporary_filename);
+            return Err(format!("Failed to write object: error while writing `{temporary_filename}`: {io_err}"));
+        }
+
+        self.move_into_place(&temporary_filename, &hash)?;
+        Ok(hash)
+    }
+
+    /// Compresses the blob into a temporary file as it is read,
+    /// then moves it into place once its hash is known
+    fn write_blob(&self, reader: &mut dyn Read, size: u64) -> CliResult<Hash> {
+        let temporary_filename = self.temporary_filename();
+        let file = std::fs::File::create_new(&temporary_filename)
+            .map_err(|io_err| format!("Failed to write object: error while creating `{temporary_filename}`: {io_err}"))?;
+
+        let write_error = |io_err: std::io::Error| format!("Failed to write object: error while writing `{temporary_filename}`: {io_err}");
+        let mut encoder = flate2::write::ZlibEncoder::new(
+            std::io::BufWriter::new(file),
+            flate2::Compression::new(COMPRESSION_LEVEL as u32),
+        );
+
+        let hash = stream_blob(reader, size, self.object_format, |chunk| encoder.write_all(chunk).map_err(write_error))
+            .and_then(|hash| {
+                encoder.finish()
+                    .and_then(|writer| writer.into_inner().map_err(|err| err.into_error()))
+                    .map_err(write_error)?;
+                Ok(hash)
+            });
+
+        let hash = match hash {
+            Ok(hash) if !self.has(&hash)? => hash,
+            result => {
+                let _ = std::fs::remove_file(&temporary_filename);
+                return result; // failed, or already stored
+            },
+        };
+
+        self.move_into_place(&temporary_filename, &hash)?;
+        Ok(hash)
+    }
+
+    fn hashes(&self) -> CliResult<Vec<Hash>> {
+        let read_directory = |directory: &std::path::Path| std::fs::read_dir(directory)
+            .map_err(|io_err| format!("Failed to list objects: error while reading directory `{}`: {io_err}", directory.display()));
+
+        let mut hashes = Vec::new();
+        for entry in read_directory(std::path::Path::new(&self.directory))?.filter_map(|entry| entry.ok()) {
+            let directory_name = entry.file_name().to_string_lossy().into_owned();
+            if directory_name.len() != 2 || !entry.path().is_dir() {
+                continue; // e.g. `pack`
+            }
+
+            hashes.extend(
+                read_directory(&entry.path())?
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| self.object_format.parse_hash(&format!("{directory_name}{}", entry.file_name().to_string_lossy())))
+            );
+        }
+
+        hashes.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        Ok(hashes)
+    }
+
+    /// Only lists the fanout directory of the prefix
+    fn find_by_prefix(&self, prefix: &str) -> CliResult<Vec<Hash>> {
+        if prefix.len() < 2 {
+            return Err(format!("Hash prefix too short: `{prefix}`"));
+        }
+
+        let (directory_name, filename_prefix) = prefix.split_at(2);
+        let directory = format!("{}/{directory_name}", self.directory);
+        if !std::path::Path::new(&directory).is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut hashes = std::fs::read_dir(&directory)
+            .map_err(|io_err| format!("Failed to find objects: error while reading directory `{directory}`: {io_err}"))?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str().map(String::from))
+            .filter(|filename| filename.starts_with(filename_prefix))
+            .filter_map(|filename| self.object_format.parse_hash(&format!("{directory_name}{filename}")))
+            .collect::<Vec<_>>();
+
+        hashes.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        Ok(hashes)
+    }
+}
+
+/// The objects of a repository: its loose objects, and those in its packs (`pack/` in the
+/// objects directory). A loose object is preferred over a packed one; new objects are written loose.
+pub struct RepositoryObjectStore {
+    loose: LooseObjectStore,
+    pack_directory: String,
+}
+
+impl RepositoryObjectStore {
+    /// The store in `directory` (e.g. `.mush/objects`), of a repository using `object_format`
+    pub fn new(directory: &str, object_format: ObjectFormat) -> Self {
+        RepositoryObjectStore {
+            loose: LooseObjectStore::new(directory, object_format),
+            pack_directory: format!("{directory}/pack"),
+        }
+    }
+
+    /// The store of the current repository
+    pub fn current() -> CliResult<Self> {
+        let directory = with_context("resolve path", dot_mush_slash("objects"))?;
+        Ok(Self::new(&directory, ObjectFormat::load()?))
+    }
+
+    /// Only the loose objects
+    pub fn loose(&self) -> &LooseObjectStore {
+        &self.loose
+    }
+
+    pub fn pack_directory(&self) -> &str {
+        &self.pack_directory
+    }
+
+    /// Indexes of all packs (skipping those without a `.pack`), sorted by filename,
+    /// freshly opened (so that they can be deleted)
+    pub fn list_pack_indexes(&self) -> CliResult<Vec<PackIndex>> {
+        pack::list_pack_indexes(&self.pack_directory, self.object_format())
+    }
+
+    /// Whether `hash` is in any pack
+    pub fn is_packed(&self, hash: &Hash) -> CliResult<bool> {
+        for index in pack::loaded_pack_indexes(&self.pack_directory, self.object_format())?.iter() {
+            if index.find(hash)?.is_some() {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// `read(index)` for the first pack that has `hash` (unless it's also loose)
+    fn find_packed<T>(&self, hash: &Hash, read: impl Fn(&PackIndex) -> CliResult<Option<T>>) -> CliResult<Option<T>> {
+        if self.loose.has(hash)? {
+            return Ok(None);
+        }
+
+        for index in pack::loaded_pack_indexes(&self.pack_directory, self.object_format())?.iter() {
+            if let Some(found) = read(index)? {
+                return Ok(Some(found));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl ObjectStore for RepositoryObjectStore {
+    fn object_format(&self) -> ObjectFormat {
+        self.loose.object_format()
+    }
+
+    fn has(&self, hash: &Hash) -> CliResult<bool> {
+        Ok(self.loose.has(hash)? || self.is_packed(hash)?)
+    }
+
+    fn read_raw(&self, hash: &Hash) -> CliResult<(ObjectType, Vec<u8>)> {
+        match self.find_packed(hash, |index| index.read_object(hash, self))? {
+            Some(raw_object) => Ok(raw_object),
+            None => self.loose.read_raw(hash),
+        }
+    }
+
+    fn read_header(&self, hash: &Hash) -> CliResult<ObjectHeader> {
+        match self.find_packed(hash, |index| index.read_object_header(hash, self))? {
+            Some(header) => Ok(header),
+            None => self.loose.read_header(hash),
+        }
+    }
+
+    /// Loose objects and whole pack entries are decompressed as they are read;
+    /// packed deltas are applied in memory first
+    fn open(&self, hash: &Hash) -> CliResult<(ObjectHeader, Box<dyn Read>)> {
+        match self.find_packed(hash, |index| index.open_object(hash, self))? {
+            Some(opened) => Ok(opened),
+            None => self.loose.open(hash),
+        }
+    }
+
+    fn write_raw(&self, tipe: ObjectType, contents: &[u8]) -> CliResult<Hash> {
+        self.loose.write_raw(tipe, contents)
+    }
+
+    fn write_blob(&self, reader: &mut dyn Read, size: u64) -> CliResult<Hash> {
+        self.loose.write_blob(reader, size)
+    }
+
+    fn hashes(&self) -> CliResult<Vec<Hash>> {
+        let mut hashes = self.loose.hashes()?;
+        for index in pack::loaded_pack_indexes(&self.pack_directory, self.object_format())?.iter() {
+            hashes.extend(index.hashes()?);
+        }
+
+        hashes.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        hashes.dedup();
+        Ok(hashes)
+    }
+
+    fn find_by_prefix(&self, prefix: &str) -> CliResult<Vec<Hash>> {
+        let mut hashes = self.loose.find_by_prefix(prefix)?;
+        for index in pack::loaded_pack_indexes(&self.pack_directory, self.object_format())?.iter() {
+            hashes.extend(index.find_by_prefix(prefix)?);
+        }
+
+        hashes.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        hashes.dedup();
+        Ok(hashes)
+    }
+}
+
+/// Objects kept in memory (e.g. to build objects without a repository on disk)
+pub struct MemoryObjectStore {
+    objects: RefCell<HashMap<Hash, (ObjectType, Vec<u8>)>>,
+    object_format: ObjectFormat,
+}
+
+impl MemoryObjectStore {
+    pub fn new(object_format: ObjectFormat) -> Self {
+        MemoryObjectStore { objects: RefCell::default(), object_format }
+    }
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn object_format(&self) -> ObjectFormat {
+        self.object_format
+    }
+
+    fn has(&self, hash: &Hash) -> CliResult<bool> {
+        Ok(self.objects.borrow().contains_key(hash))
+    }
+
+    fn read_raw(&self, hash: &Hash) -> CliResult<(ObjectType, Vec<u8>)> {
+        self.objects.borrow()
+            .get(hash)
+            .cloned()
+            .ok_or(format!("Failed to read object `{}`: not in the store", hash.as_str()))
+    }
+
+    fn write_raw(&self, tipe: ObjectType, contents: &[u8]) -> CliResult<Hash> {
+        let hash = Hash::from_bytes(&self.object_format.try_digest(&Object::store_raw(tipe, contents))?);
+        self.objects.borrow_mut().insert(hash.clone(), (tipe, contents.to_vec()));
+        Ok(hash)
+    }
+
+    fn hashes(&self) -> CliResult<Vec<Hash>> {
+        let mut hashes = self.objects.borrow().keys().cloned().collect::<Vec<_>>();
+        hashes.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        Ok(hashes)
+    }
+}
@@ -0,0 +1,103 @@
+use itertools::Itertools;
+
+use crate::{cli::CliResult, config::User, hash::{Hash, ObjectFormat}};
+
+use super::commit::PersonTime;
+use super::{Object, ObjectType};
+
+/// An annotated tag: a named, signed-off pointer to another object
+pub struct TagObject {
+    pub target_hash: Hash,
+    pub target_type: ObjectType,
+    pub tag_name: String,
+    pub tagger: PersonTime,
+    pub message: String,
+}
+
+impl TagObject {
+    pub fn serialize(&self) -> String {
+        //< Tab-separated fields, like commit objects (git uses spaces)
+        [
+            format!("object\t{}\n", self.target_hash.as_str()),
+            format!("type\t{}\n", self.target_type.to_str()),
+            format!("tag\t{}\n", self.tag_name),
+            format!("tagger\t{}\n", self.tagger.to_string()),
+            String::from("\n"),
+            self.message.clone(),
+        ].join("")
+    }
+
+    pub fn deserialize(string: &str, object_format: ObjectFormat) -> CliResult<Self> {
+        let [header, message] = string.splitn(2, "\n\n").collect::<Vec<_>>()[..]
+            else {
+                return Err(String::from("Malformed tag object: no double newline found"));
+            };
+
+        let field_names_to_args_map = header.split("\n")
+            .map(|field| {
+                let mut tab_separated_strings = field.split("\t");
+                (tab_separated_strings.next().unwrap_or(""), tab_separated_strings.collect::<Vec<_>>())
+            })
+            .into_group_map();
+
+        const FIELD_SPECS: &[(&str, usize)] = &[
+            // (field_name, num_args); all fields are required, and can't be duplicated
+            ("object", 1),
+            ("type", 1),
+            ("tag", 1),
+            ("tagger", 3),
+        ];
+
+        if let Some(field) = field_names_to_args_map.keys().find(|key| !FIELD_SPECS.iter().any(|spec| spec.0 == **key)) {
+            return Err(format!("Malformed tag object: bad field: {field}"));
+        }
+
+        let get_field = |field_name: &str| -> CliResult<&Vec<&str>> {
+            let num_args = FIELD_SPECS.iter()
+                .find(|spec| spec.0 == field_name)
+                .expect("Invariant violated: field not in FIELD_SPECS")
+                .1;
+
+            match field_names_to_args_map.get(field_name).map(|vals| vals.as_slice()).unwrap_or(&[]) {
+                [] => Err(format!("Malformed tag object: missing {field_name}")),
+                [args] if args.len() == num_args => Ok(args),
+                [_] => Err(format!("Malformed tag object: wrong arity for field: {field_name}")),
+                _ => Err(format!("Malformed tag object: duplicate field: {field_name}")),
+            }
+        };
+
+        let object = get_field("object")?[0];
+        let tipe = get_field("type")?[0];
+        let tag = get_field("tag")?[0];
+        let tagger = get_field("tagger")?;
+
+        Ok(TagObject {
+            target_hash: object_format.parse_hash(object)
+                .ok_or(format!("Malformed tag object: bad hash: {object}"))?,
+            target_type: ObjectType::from_string(tipe)
+                .map_err(|msg| format!("Malformed tag object: {msg}"))?,
+            tag_name: String::from(tag),
+            tagger: PersonTime::from_strings(tagger[0], tagger[1], tagger[2])
+                .map_err(|msg| format!("Malformed tag object: {msg}"))?,
+            message: String::from(message),
+        })
+    }
+
+    /// Assumes that the supplied hash is valid, and of type `target_type`
+    /// (it will be placed into the database without being checked)
+    pub fn new(target_hash: Hash, target_type: ObjectType, tag_name: String, user: User, message: String) -> Self {
+        TagObject {
+            target_hash,
+            target_type,
+            tag_name,
+            tagger: PersonTime::now(user),
+            message,
+        }
+    }
+}
+
+impl From<TagObject> for Object<'static> {
+    fn from(tag_object: TagObject) -> Object<'static> {
+        Object::Tag(tag_object)
+    }
+}
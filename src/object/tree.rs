@@ -1,9 +1,11 @@
 use std::ffi::OsStr;
 use std::os::unix::fs::MetadataExt;
 
-use crate::cli::CliResult;
+use crate::cli::{with_context, CliResult};
+use crate::hash::{Hash, ObjectFormat};
 use crate::index::{Index, IndexEntry, RepoRelativeFilename};
-use crate::io::write_object;
+use crate::io::{file_metadata, open_filename};
+use crate::object::store::{hash_blob, ObjectStore};
 use crate::object::TreeEntry;
 
 use itertools::Itertools;
@@ -15,28 +17,40 @@ use super::Object;
 /// Technically, in the case of the empty tree-object, a tree can also be leaf
 /// (a node with an empty vector of children).
 pub enum ObjectTree {
-    Leaf(Object<'static>),
+    /// A blob, left in its file (big files are only read a chunk at a time, when written)
+    Leaf { filename: String, hash: Hash },
     Node(Object<'static>, Vec<ObjectTree>),
 }
 
 impl ObjectTree {
-    pub fn root(&self) -> &Object<'static> {
+    /// Hash of the root object
+    pub fn hash(&self, object_format: ObjectFormat) -> CliResult<Hash> {
         match self {
-            Self::Leaf(object) => object,
-            Self::Node(object, _children) => object,
+            Self::Leaf { hash, .. } => Ok(hash.clone()),
+            Self::Node(object, _children) => object.hash(object_format),
         }
     }
 
-    pub fn write(&self) -> CliResult<()> {
+    /// Write every object of the tree to `store`
+    pub fn write(&self, store: &impl ObjectStore) -> CliResult<()> {
         match &self {
-            Self::Leaf(object) => {
-                write_object(object)
+            Self::Leaf { filename, hash } => {
+                if store.has(hash)? {
+                    return Ok(());
+                }
+
+                let mut file = with_context("write blob", open_filename(filename))?;
+                let size = with_context("write blob", file_metadata(filename))?.len();
+                match store.write_blob(&mut file, size)? == *hash {
+                    true => Ok(()),
+                    false => Err(format!("Failed to write blob: `{filename}` changed while the tree was being written")),
+                }
             },
             Self::Node(object, children) => {
                 children.iter()
-                    .map(|child| child.write())
+                    .map(|child| child.write(store))
                     .collect::<CliResult<()>>()
-                    .and_then(|_| write_object(object))
+                    .and_then(|_| store.write(object).map(|_| ()))
             },
         }
     }
@@ -112,27 +126,28 @@ impl FilenameTree {
         }
     }
 
-    pub fn into_object_tree(self) -> CliResult<ObjectTree> {
-        fn recursive_helper(nodes: Vec<FilenameTreeNode>, directory: &std::path::Path) -> CliResult<ObjectTree> {
+    /// Hash the files of the tree (with `object_format`), without storing anything yet
+    pub fn into_object_tree(self, object_format: ObjectFormat) -> CliResult<ObjectTree> {
+        fn recursive_helper(nodes: Vec<FilenameTreeNode>, directory: &std::path::Path, object_format: ObjectFormat) -> CliResult<ObjectTree> {
             let (tree_entries, object_trees) = nodes.into_iter().map(|node| {
                 match node {
                     FilenameTreeNode::Leaf(filename) => {
                         let full_filename = directory.join(&filename).to_str().unwrap().to_owned();
-                        let content = crate::cli::with_context("convert filename into object", crate::io::read_filename_to_str(&full_filename))?;
-                        let stat = crate::cli::with_context("convert filename into object", crate::io::file_metadata(&full_filename))?;
-                        let object = Object::Blob(std::borrow::Cow::Owned(content.into_bytes()));
+                        let mut file = with_context("convert filename into object", open_filename(&full_filename))?;
+                        let stat = with_context("convert filename into object", file_metadata(&full_filename))?;
+                        let hash = hash_blob(&mut file, stat.size(), object_format)?;
 
                         Ok((
-                            TreeEntry::new(filename, stat.mode(), object.hash()),
-                            ObjectTree::Leaf(object),
+                            TreeEntry::new(filename, stat.mode(), hash.clone()),
+                            ObjectTree::Leaf { filename: full_filename, hash },
                         ))
                     },
                     FilenameTreeNode::Node(dir, children) => {
-                        let subtree = recursive_helper(children, &directory.join(&dir))?;
+                        let subtree = recursive_helper(children, &directory.join(&dir), object_format)?;
                         const DEFAULT_DIRECTORY_MODE: u32 = 0o40000;
 
                         Ok((
-                            TreeEntry::new(dir, DEFAULT_DIRECTORY_MODE, subtree.root().hash()),
+                            TreeEntry::new(dir, DEFAULT_DIRECTORY_MODE, subtree.hash(object_format)?),
                             subtree,
                         ))
                     }
@@ -145,6 +160,6 @@ impl FilenameTree {
             Ok(ObjectTree::Node(Object::Tree(tree_entries), object_trees))
         }
 
-        recursive_helper(self.nodes, std::path::Path::new(""))
+        recursive_helper(self.nodes, std::path::Path::new(""), object_format)
     }
 }
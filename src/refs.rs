@@ -1,11 +1,469 @@
-use crate::hash::Hash;
+pub mod reflog;
+pub mod packed;
+pub mod transaction;
 
-struct Ref {
-    // TODO
+// Docs for git refs:
+// https://git-scm.com/book/en/v2/Git-Internals-Git-References
+
+use packed::PackedRefs;
+use reflog::ReflogEntry;
+use transaction::RefTransaction;
+
+use crate::cli::{with_context, CliResult};
+use crate::config::force_get_user;
+use crate::hash::{Hash, ObjectFormat};
+use crate::object::commit::PersonTime;
+use crate::io::{dot_mush_slash, try_read_filename_to_str, LockFile};
+
+pub const HEAD: &str = "HEAD";
+pub const DEFAULT_BRANCH: &str = "main";
+
+const HEADS_PREFIX: &str = "refs/heads/";
+const TAGS_PREFIX: &str = "refs/tags/";
+const SYMBOLIC_REF_PREFIX: &str = "ref: ";
+
+/// Same limit as git's `SYMREF_MAXDEPTH`
+const MAX_SYMBOLIC_REF_DEPTH: usize = 5;
+
+/// What a ref file holds: either an object hash, or the name of another ref
+pub enum RefContents {
+    Hash(Hash),
+    Symbolic(String),
+}
+
+impl RefContents {
+    pub fn serialize(&self) -> String {
+        match self {
+            Self::Hash(hash) => format!("{}\n", hash.as_str()),
+            Self::Symbolic(target) => format!("{SYMBOLIC_REF_PREFIX}{target}\n"),
+        }
+    }
+
+    fn deserialize(string: &str) -> CliResult<Self> {
+        let string = string.trim_end();
+
+        if let Some(target) = string.strip_prefix(SYMBOLIC_REF_PREFIX) {
+            Ref::new(target)
+                .map(|_| Self::Symbolic(String::from(target)))
+                .map_err(|msg| format!("Malformed symbolic ref: {msg}"))
+        } else {
+            ObjectFormat::load()?
+                .parse_hash(string)
+                .map(Self::Hash)
+                .ok_or(format!("Malformed ref: bad hash: `{string}`"))
+        }
+    }
+}
+
+/// The value a ref must hold for an update to go through (compare-and-swap)
+#[derive(Clone)]
+pub enum ExpectedValue {
+    /// Don't check the current value
+    Any,
+    /// The ref must not exist
+    Missing,
+    /// The ref must currently resolve to this hash
+    Hash(Hash),
+}
+
+impl ExpectedValue {
+    fn check(&self, reff: &Ref, current: Option<&Hash>) -> CliResult<()> {
+        match (self, current) {
+            (Self::Any, _) | (Self::Missing, None) => Ok(()),
+            (Self::Hash(expected), Some(current)) if expected == current => Ok(()),
+            (Self::Missing, Some(current)) => Err(format!(
+                "Cannot lock ref `{reff}`: reference already exists (at {})", current.as_str()
+            )),
+            (Self::Hash(expected), Some(current)) => Err(format!(
+                "Cannot lock ref `{reff}`: is at {} but expected {}", current.as_str(), expected.as_str()
+            )),
+            (Self::Hash(expected), None) => Err(format!(
+                "Cannot lock ref `{reff}`: unable to resolve reference (expected {})", expected.as_str()
+            )),
+        }
+    }
+}
+
+/// A (validated) full ref name, e.g. `HEAD`, `refs/heads/main`, `refs/tags/v1.0`.
+/// The ref itself might not exist.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ref {
+    name: String,
 }
 
 impl Ref {
-    fn to_hash(&self) -> Hash {
-        todo!()
+    pub fn new(name: &str) -> CliResult<Self> {
+        if is_valid_ref_name(name) {
+            Ok(Ref { name: String::from(name) })
+        } else {
+            Err(format!("Bad ref name: `{name}`"))
+        }
+    }
+
+    pub fn head() -> Self {
+        Ref { name: String::from(HEAD) }
+    }
+
+    pub fn branch(branch_name: &str) -> CliResult<Self> {
+        Self::new(&format!("{HEADS_PREFIX}{branch_name}"))
     }
+
+    pub fn tag(tag_name: &str) -> CliResult<Self> {
+        Self::new(&format!("{TAGS_PREFIX}{tag_name}"))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name without `refs/heads/` or `refs/tags/`, if present
+    pub fn short_name(&self) -> &str {
+        self.name.strip_prefix(HEADS_PREFIX)
+            .or_else(|| self.name.strip_prefix(TAGS_PREFIX))
+            .unwrap_or(&self.name)
+    }
+
+    pub fn is_branch(&self) -> bool {
+        self.name.starts_with(HEADS_PREFIX)
+    }
+
+    pub fn is_tag(&self) -> bool {
+        self.name.starts_with(TAGS_PREFIX)
+    }
+
+    /// Path of the ref file, relative to `.mush`
+    pub fn path(&self) -> &str {
+        &self.name
+    }
+
+    /// Read the ref (its loose file, falling back to `packed-refs`), without following symbolic refs.
+    /// `Ok(None)` is returned if the ref doesn't exist
+    pub fn read(&self) -> CliResult<Option<RefContents>> {
+        match self.read_loose()? {
+            Some(contents) => Ok(Some(contents)),
+            None => Ok(self.read_packed()?.map(|packed_ref| RefContents::Hash(packed_ref.hash))),
+        }
+    }
+
+    /// Read the loose ref file only (ignoring `packed-refs`)
+    fn read_loose(&self) -> CliResult<Option<RefContents>> {
+        let filename = with_context("resolve path", dot_mush_slash(self.path()))?;
+
+        if std::path::Path::new(&filename).is_dir() {
+            return Ok(None);
+        }
+
+        with_context(&format!("read ref `{}`", self.name), try_read_filename_to_str(&filename))?
+            .map(|string| {
+                RefContents::deserialize(&string)
+                    .map_err(|msg| format!("Failed to read ref `{}`: {msg}", self.name))
+            })
+            .transpose()
+    }
+
+    /// Follow symbolic refs until reaching a ref that holds a hash (or doesn't exist).
+    /// Returns the last ref in the chain, and its hash (if any).
+    pub fn resolve(&self) -> CliResult<(Ref, Option<Hash>)> {
+        let mut current = self.clone();
+
+        for _ in 0..=MAX_SYMBOLIC_REF_DEPTH {
+            match current.read()? {
+                None => return Ok((current, None)),
+                Some(RefContents::Hash(hash)) => return Ok((current, Some(hash))),
+                Some(RefContents::Symbolic(target)) => current = Ref::new(&target)?,
+            }
+        }
+
+        Err(format!("Failed to resolve ref `{}`: too many levels of symbolic refs", self.name))
+    }
+
+    /// The hash this ref ultimately points to.
+    /// `Ok(None)` is returned if the ref (or the end of its symbolic chain) doesn't exist
+    pub fn to_hash(&self) -> CliResult<Option<Hash>> {
+        self.resolve().map(|(_, hash)| hash)
+    }
+
+    pub fn exists(&self) -> CliResult<bool> {
+        self.read().map(|contents| contents.is_some())
+    }
+
+    fn lock(&self) -> CliResult<LockFile> {
+        let filename = with_context("resolve path", dot_mush_slash(self.path()))?;
+        with_context(&format!("lock ref `{}`", self.name), LockFile::acquire(&filename))
+    }
+
+    /// Write to the ref file directly (without following symbolic refs)
+    pub fn write(&self, contents: &RefContents) -> CliResult<()> {
+        let context = format!("write ref `{}`", self.name);
+        let mut lock = self.lock()?;
+        with_context(&context, lock.write_all(contents.serialize().as_bytes()))?;
+        with_context(&context, lock.commit())
+    }
+
+    /// The ref that an update would actually write to:
+    /// the end of the symbolic chain if `deref`, otherwise this ref itself
+    fn update_target(&self, deref: bool) -> CliResult<Ref> {
+        if deref {
+            self.resolve().map(|(target, _)| target)
+        } else {
+            Ok(self.clone())
+        }
+    }
+
+    /// Point this ref (or, if `deref`, the ref at the end of its symbolic chain) at `new_hash`,
+    /// provided that it currently matches `expected`.
+    /// The check and write happen while holding the ref's lock.
+    /// The update is recorded in the reflog with the given `reason`.
+    pub fn update(&self, new_hash: &Hash, expected: &ExpectedValue, deref: bool, reason: &str) -> CliResult<()> {
+        let mut transaction = RefTransaction::new();
+        transaction.update(self.clone(), new_hash.clone(), expected.clone(), deref, reason);
+        transaction.commit()
+    }
+
+    /// Make this ref a symbolic ref to `target` (which must be under `refs/`).
+    /// Switching HEAD to another branch (or from a detached hash) is always recorded in the reflog,
+    /// even if the hash stays the same, by default as `checkout: moving from <old> to <new>`
+    /// (which is what `@{-<n>}` looks for). Otherwise, the update is recorded only if it changes
+    /// the hash this ref resolves to, by default as `symbolic-ref`.
+    pub fn update_symbolic(&self, target: &Ref, reason: Option<&str>) -> CliResult<()> {
+        if !target.name.starts_with("refs/") {
+            return Err(format!("Refusing to point `{self}` outside of refs/: `{target}`"));
+        }
+
+        let context = format!("update symbolic ref `{self}`");
+        let mut lock = self.lock()?;
+
+        // what HEAD is switched from, if it is switched to another branch
+        let previous = match self.read()? {
+            _ if self.name != HEAD => None,
+            Some(RefContents::Symbolic(name)) if name != target.name => Some(String::from(Ref { name }.short_name())),
+            Some(RefContents::Hash(hash)) => Some(String::from(hash.as_str())),
+            _ => None,
+        };
+        let old_hash = self.to_hash()?;
+        let new_hash = target.to_hash()?;
+
+        with_context(&context, lock.write_all(RefContents::Symbolic(target.name.clone()).serialize().as_bytes()))?;
+
+        let zero_hash = ObjectFormat::load()?.zero_hash();
+        let old_hash = old_hash.unwrap_or_else(|| zero_hash.clone());
+        let new_hash = new_hash.unwrap_or(zero_hash);
+        match previous {
+            Some(previous) => {
+                let checkout = format!("checkout: moving from {previous} to {}", target.short_name());
+                self.log_update(old_hash, new_hash, reason.unwrap_or(&checkout))?;
+            },
+            None if old_hash != new_hash => {
+                self.log_update(old_hash, new_hash, reason.unwrap_or("symbolic-ref"))?;
+            },
+            None => (),
+        }
+
+        with_context(&context, lock.commit())
+    }
+
+    /// Append a reflog entry to this ref's log (if it should have one),
+    /// and to HEAD's log if HEAD currently points to this ref
+    fn log_update(&self, old_hash: Hash, new_hash: Hash, reason: &str) -> CliResult<()> {
+        let head = Ref::head();
+        let also_log_head = *self != head && head.resolve()?.0 == *self;
+
+        if !self.should_log()? && !also_log_head {
+            return Ok(());
+        }
+
+        let entry = ReflogEntry {
+            old_hash,
+            new_hash,
+            identity: PersonTime::now(force_get_user()?),
+            reason: String::from(reason),
+        };
+
+        if self.should_log()? {
+            self.append_reflog(&entry)?;
+        }
+
+        if also_log_head {
+            head.append_reflog(&entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete this ref (or, if `deref`, the ref at the end of its symbolic chain),
+    /// provided that it currently matches `expected`. The ref's reflog is deleted too.
+    /// The ref is removed from both its loose file and `packed-refs`.
+    pub fn delete(&self, expected: &ExpectedValue, deref: bool) -> CliResult<()> {
+        let mut transaction = RefTransaction::new();
+        transaction.delete(self.clone(), expected.clone(), deref);
+        transaction.commit()
+    }
+
+    /// An existing ref that this one can't coexist with, because the name of one
+    /// is a directory of the other (e.g. `refs/heads/a` and `refs/heads/a/b`)
+    pub fn name_conflict(&self, replaced: Option<&Ref>) -> CliResult<Option<Ref>> {
+        let is_directory_of = |a: &Ref, b: &Ref| b.name.starts_with(&format!("{}/", a.name));
+
+        Ok(
+            list_refs("refs/")?
+                .into_iter()
+                .filter(|other| Some(other) != replaced)
+                .find(|other| is_directory_of(self, other) || is_directory_of(other, self))
+        )
+    }
+
+    /// Find the ref a (possibly abbreviated) name refers to, e.g. `main` -> `refs/heads/main`.
+    /// Same lookup order as git: `<name>`, `refs/<name>`, `refs/tags/<name>`, `refs/heads/<name>`.
+    pub fn dwim(shorthand: &str) -> CliResult<Option<Self>> {
+        for reff in Self::dwim_candidates(shorthand) {
+            if reff.exists()? {
+                return Ok(Some(reff));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn dwim_candidates(shorthand: &str) -> impl Iterator<Item = Ref> {
+        [
+            String::from(shorthand),
+            format!("refs/{shorthand}"),
+            format!("{TAGS_PREFIX}{shorthand}"),
+            format!("{HEADS_PREFIX}{shorthand}"),
+        ]
+            .into_iter()
+            .filter_map(|candidate| Ref::new(&candidate).ok())
+    }
+}
+
+/// What HEAD points to
+pub enum HeadState {
+    /// HEAD is a symbolic ref (normally to a branch, which might not have any commits yet)
+    Attached(Ref),
+    /// HEAD holds a hash directly
+    Detached(Hash),
+}
+
+impl HeadState {
+    pub fn read() -> CliResult<Self> {
+        let head = Ref::head();
+
+        match head.read()? {
+            Some(RefContents::Symbolic(target)) => Ok(Self::Attached(Ref::new(&target)?)),
+            Some(RefContents::Hash(hash)) => Ok(Self::Detached(hash)),
+            None => Err(String::from("Failed to read HEAD: no such ref")),
+        }
+    }
+}
+
+impl std::fmt::Display for HeadState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Attached(reff) => write!(f, "On branch {}", reff.short_name()),
+            Self::Detached(hash) => write!(f, "HEAD detached at {}", hash.abbreviated()),
+        }
+    }
+}
+
+/// The branch that HEAD (symbolically) points to.
+/// `Ok(None)` is returned if HEAD is detached.
+pub fn current_branch() -> CliResult<Option<Ref>> {
+    let (target, _) = Ref::head().resolve()?;
+    Ok(Some(target).filter(|reff| reff.is_branch()))
+}
+
+impl std::fmt::Display for Ref {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Based on the rules of `git check-ref-format`.
+/// Additionally, names must either be under `refs/` or be all-caps (like `HEAD`)
+pub fn is_valid_ref_name(name: &str) -> bool {
+    const FORBIDDEN_CHARACTERS: &[char] = &[' ', '~', '^', ':', '?', '*', '[', '\\'];
+
+    let is_pseudo_ref = name.chars().all(|c| c.is_ascii_uppercase() || c == '_');
+
+    (is_pseudo_ref || name.starts_with("refs/")) &&
+        !name.is_empty() &&
+        name != "@" &&
+        !name.ends_with('/') &&
+        !name.ends_with('.') &&
+        !name.contains("..") &&
+        !name.contains("@{") &&
+        !name.chars().any(|c| c.is_ascii_control() || FORBIDDEN_CHARACTERS.contains(&c)) &&
+        name.split('/').all(|component|
+            !component.is_empty() &&
+            !component.starts_with('.') &&
+            !component.ends_with(".lock")
+        )
+}
+
+/// After deleting `refs/heads/a/b`, remove `refs/heads/a` if it's now empty
+/// (otherwise it would block the creation of a ref named `refs/heads/a`).
+/// `base` is the directory (relative to `.mush`) that the ref's file lives under.
+fn remove_empty_parent_directories(name: &str, base: &str) {
+    let Ok(base_directory) = dot_mush_slash(base) else {
+        return;
+    };
+
+    let mut path = std::path::Path::new(name).parent();
+    while let Some(directory) = path {
+        if directory.components().count() <= 2 { // keep `refs/heads`, `refs/tags`, etc.
+            break;
+        }
+        if std::fs::remove_dir(std::path::Path::new(&base_directory).join(directory)).is_err() {
+            break; // not empty
+        }
+        path = directory.parent();
+    }
+}
+
+/// Every ref stored as a file under `directory`, which corresponds to the ref name `root_name`
+/// (e.g. `.mush/refs` is `refs`, `.mush/logs` is the empty name)
+fn walk_ref_files(directory: &std::path::Path, root_name: &str) -> CliResult<Vec<Ref>> {
+    fn recursive_helper(directory: &std::path::Path, name: &str, refs: &mut Vec<Ref>) -> CliResult<()> {
+        let entries = std::fs::read_dir(directory)
+            .map_err(|io_err| format!("Failed to list refs: error while reading directory `{}`: {io_err}", directory.display()))?;
+
+        for entry in entries {
+            let entry = entry
+                .map_err(|io_err| format!("Failed to list refs: error while reading directory `{}`: {io_err}", directory.display()))?;
+            let Some(filename) = entry.file_name().to_str().map(String::from) else {
+                continue;
+            };
+            let child_name = if name.is_empty() { filename } else { format!("{name}/{filename}") };
+
+            if entry.path().is_dir() {
+                recursive_helper(&entry.path(), &child_name, refs)?;
+            } else if let Ok(reff) = Ref::new(&child_name) {
+                refs.push(reff);
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut refs = Vec::new();
+
+    if directory.is_dir() {
+        recursive_helper(directory, root_name, &mut refs)?;
+    }
+
+    refs.sort();
+    Ok(refs)
+}
+
+/// All refs (loose, in `.mush/refs`, or packed) whose names start with `prefix`, sorted by name
+pub fn list_refs(prefix: &str) -> CliResult<Vec<Ref>> {
+    let dot_mush = with_context("resolve path", dot_mush_slash(""))?;
+    let mut refs = walk_ref_files(&std::path::Path::new(&dot_mush).join("refs"), "refs")?;
+
+    refs.extend(PackedRefs::read()?.refs().iter().map(|packed_ref| packed_ref.reff.clone()));
+    refs.sort();
+    refs.dedup();
+
+    refs.retain(|reff| reff.name.starts_with(prefix));
+    Ok(refs)
 }
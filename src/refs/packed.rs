@@ -0,0 +1,233 @@
+// Docs for git packed-refs:
+// https://git-scm.com/docs/git-pack-refs
+
+use std::sync::{Arc, Mutex};
+
+use crate::cli::{with_context, CliResult};
+use crate::hash::{Hash, ObjectFormat};
+use crate::io::{dot_mush_slash, try_read_filename_to_str, FileStamp, LockFile};
+use crate::object::store::{ObjectStore, RepositoryObjectStore};
+use crate::object::ObjectType;
+use crate::revision::peel_tags;
+
+use super::{list_refs, remove_empty_parent_directories, RefContents, Ref, TAGS_PREFIX};
+
+const PACKED_REFS_FILENAME: &str = "packed-refs";
+const HEADER: &str = "# pack-refs with: peeled fully-peeled sorted \n";
+const PEELED_PREFIX: char = '^';
+
+/// One line of `packed-refs` (plus its peeled line, if any)
+#[derive(Clone)]
+pub struct PackedRef {
+    pub reff: Ref,
+    pub hash: Hash,
+    /// For annotated tags: the (non-tag) object the tag ultimately points to
+    pub peeled: Option<Hash>,
+}
+
+/// The contents of `.mush/packed-refs`: refs that aren't stored as individual files.
+/// Loose ref files take precedence over packed refs of the same name.
+pub struct PackedRefs {
+    /// Sorted by name
+    refs: Vec<PackedRef>,
+}
+
+/// The last parse of `packed-refs`, so that looking up each of many refs doesn't re-read the whole file
+static CACHE: Mutex<Option<CachedPackedRefs>> = Mutex::new(None);
+
+struct CachedPackedRefs {
+    filename: String,
+    stamp: Option<FileStamp>,
+    packed_refs: Arc<PackedRefs>,
+}
+
+impl PackedRefs {
+    fn filename() -> CliResult<String> {
+        with_context("resolve path", dot_mush_slash(PACKED_REFS_FILENAME))
+    }
+
+    /// Read `packed-refs` (empty if the file doesn't exist).
+    /// The file is only parsed again once it has changed.
+    pub fn read() -> CliResult<Arc<Self>> {
+        let filename = Self::filename()?;
+        // taken before reading: if the file changes in between, the next call reads it again
+        let stamp = with_context("read packed refs", FileStamp::read(&filename))?;
+
+        let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(cached) = cache.as_ref().filter(|cached| cached.filename == filename && cached.stamp == stamp) {
+            return Ok(cached.packed_refs.clone());
+        }
+
+        let packed_refs = Arc::new(Self::read_file(&filename)?);
+        *cache = Some(CachedPackedRefs { filename, stamp, packed_refs: packed_refs.clone() });
+        Ok(packed_refs)
+    }
+
+    fn read_file(filename: &str) -> CliResult<Self> {
+        let contents = with_context("read packed refs", try_read_filename_to_str(filename))?
+            .unwrap_or_default();
+
+        Self::deserialize(&contents)
+            .map_err(|msg| format!("Failed to read packed refs: {msg}"))
+    }
+
+    fn deserialize(string: &str) -> CliResult<Self> {
+        let object_format = ObjectFormat::load()?;
+        let mut refs: Vec<PackedRef> = Vec::new();
+
+        for line in string.lines() {
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+
+            if let Some(peeled) = line.strip_prefix(PEELED_PREFIX) {
+                let last = refs.last_mut()
+                    .ok_or(String::from("peeled line without a ref"))?;
+                last.peeled = Some(
+                    object_format.parse_hash(peeled).ok_or(format!("bad hash: `{peeled}`"))?
+                );
+                continue;
+            }
+
+            let (hash, name) = line.split_once(' ')
+                .ok_or(format!("malformed line: `{line}`"))?;
+
+            refs.push(PackedRef {
+                reff: Ref::new(name)?,
+                hash: object_format.parse_hash(hash).ok_or(format!("bad hash: `{hash}`"))?,
+                peeled: None,
+            });
+        }
+
+        refs.sort_by(|a, b| a.reff.cmp(&b.reff));
+        Ok(PackedRefs { refs })
+    }
+
+    fn serialize(&self) -> String {
+        //< Same format as git (hashes and names can't contain spaces)
+        let mut string = String::from(HEADER);
+
+        for packed_ref in self.refs.iter() {
+            string.push_str(&format!("{} {}\n", packed_ref.hash.as_str(), packed_ref.reff));
+            if let Some(peeled) = &packed_ref.peeled {
+                string.push_str(&format!("{PEELED_PREFIX}{}\n", peeled.as_str()));
+            }
+        }
+
+        string
+    }
+
+    pub fn refs(&self) -> &[PackedRef] {
+        &self.refs
+    }
+
+    pub fn get(&self, reff: &Ref) -> Option<&PackedRef> {
+        self.refs.binary_search_by(|packed_ref| packed_ref.reff.cmp(reff))
+            .ok()
+            .map(|index| &self.refs[index])
+    }
+
+    /// Add `packed_ref`, replacing any packed ref of the same name
+    pub fn insert(&mut self, packed_ref: PackedRef) {
+        match self.refs.binary_search_by(|existing| existing.reff.cmp(&packed_ref.reff)) {
+            Ok(index) => self.refs[index] = packed_ref,
+            Err(index) => self.refs.insert(index, packed_ref),
+        }
+    }
+
+    /// Returns whether `reff` was present
+    pub fn remove(&mut self, reff: &Ref) -> bool {
+        match self.refs.binary_search_by(|existing| existing.reff.cmp(reff)) {
+            Ok(index) => {
+                self.refs.remove(index);
+                true
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// Lock `packed-refs`, and read it (while holding the lock)
+    pub fn lock() -> CliResult<(LockFile, Self)> {
+        let filename = Self::filename()?;
+        let lock = with_context("lock packed refs", LockFile::acquire(&filename))?;
+        Ok((lock, Self::read_file(&filename)?))
+    }
+
+    /// Replace `packed-refs` with these contents (`lock` must be from `PackedRefs::lock`)
+    pub fn write(&self, mut lock: LockFile) -> CliResult<()> {
+        with_context("write packed refs", lock.write_all(self.serialize().as_bytes()))?;
+        let result = with_context("write packed refs", lock.commit());
+
+        *CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+        result
+    }
+}
+
+impl Ref {
+    /// This ref's entry in `packed-refs`, if any
+    /// (the entry is shadowed if a loose ref file also exists)
+    pub fn read_packed(&self) -> CliResult<Option<PackedRef>> {
+        Ok(PackedRefs::read()?.get(self).cloned())
+    }
+
+    /// The peeled value recorded in `packed-refs`, if this ref is packed
+    /// (and not shadowed by a loose ref file)
+    pub fn packed_peeled(&self) -> CliResult<Option<Hash>> {
+        if self.read_loose()?.is_some() {
+            return Ok(None);
+        }
+
+        Ok(self.read_packed()?.and_then(|packed_ref| packed_ref.peeled))
+    }
+}
+
+/// Move loose refs into `packed-refs`: tags only, or every ref under `refs/` if `all`.
+/// Symbolic refs are never packed. If `prune`, the loose files of packed refs are deleted.
+/// Returns the refs that were packed.
+pub fn pack_refs(all: bool, prune: bool) -> CliResult<Vec<Ref>> {
+    let (packed_lock, mut packed_refs) = PackedRefs::lock()?;
+    let store = RepositoryObjectStore::current()?;
+    let mut packed = Vec::new();
+
+    for reff in list_refs(if all { "refs/" } else { TAGS_PREFIX })? {
+        let Some(RefContents::Hash(hash)) = reff.read_loose()? else {
+            continue; // already packed, or symbolic
+        };
+
+        let peeled = match store.read_header(&hash)?.tipe {
+            ObjectType::Tag => Some(peel_tags(&store, hash.clone())?),
+            _ => None,
+        };
+
+        packed_refs.insert(PackedRef { reff: reff.clone(), hash, peeled });
+        packed.push(reff);
+    }
+
+    packed_refs.write(packed_lock)?;
+
+    if prune {
+        for reff in packed.iter() {
+            prune_loose_ref(reff, &packed_refs)?;
+        }
+    }
+
+    Ok(packed)
+}
+
+/// Delete the loose file of `reff`, if it still agrees with its packed value
+fn prune_loose_ref(reff: &Ref, packed_refs: &PackedRefs) -> CliResult<()> {
+    let lock = reff.lock()?;
+
+    let packed_hash = packed_refs.get(reff).map(|packed_ref| &packed_ref.hash);
+    let still_matches = match reff.read_loose()? {
+        Some(RefContents::Hash(hash)) => Some(&hash) == packed_hash,
+        _ => false,
+    };
+
+    if still_matches {
+        with_context(&format!("prune loose ref `{reff}`"), lock.commit_deletion())?;
+        remove_empty_parent_directories(&reff.name, "");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+// Docs for git reflogs:
+// https://git-scm.com/docs/git-reflog
+
+use crate::cli::{with_context, CliResult};
+use crate::hash::{Hash, ObjectFormat};
+use crate::io::{append_to_file_all, create_directory_all_idempotent, dot_mush_slash, try_read_filename_to_str, LockFile};
+use crate::object::commit::PersonTime;
+
+use super::{remove_empty_parent_directories, walk_ref_files, Ref, HEAD};
+
+const LOGS_DIRECTORY: &str = "logs";
+/// Where a reflog is kept in the middle of `Ref::rename_reflog`
+const RENAMED_REFLOG: &str = "logs.renamed";
+
+/// One line of a reflog: a single update of a ref
+pub struct ReflogEntry {
+    pub old_hash: Hash,
+    pub new_hash: Hash,
+    pub identity: PersonTime,
+    pub reason: String,
+}
+
+impl ReflogEntry {
+    fn serialize(&self) -> String {
+        //< git separates the hashes and identity with spaces.
+        //< Use tabs instead, for the same reason as in `PersonTime::to_string`.
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            self.old_hash.as_str(),
+            self.new_hash.as_str(),
+            self.identity.to_string(),
+            self.reason.replace('\n', " "),
+        )
+    }
+
+    fn deserialize(line: &str) -> CliResult<Self> {
+        let [old_hash, new_hash, name, email, time, reason] = line.splitn(6, '\t').collect::<Vec<_>>()[..]
+            else {
+                return Err(String::from("Malformed reflog entry: wrong number of fields"));
+            };
+
+        let object_format = ObjectFormat::load()?;
+        let parse_hash = |string: &str| object_format.parse_hash(string)
+            .ok_or(format!("Malformed reflog entry: bad hash: `{string}`"));
+
+        Ok(ReflogEntry {
+            old_hash: parse_hash(old_hash)?,
+            new_hash: parse_hash(new_hash)?,
+            identity: PersonTime::from_strings(name, email, time)
+                .map_err(|msg| format!("Malformed reflog entry: {msg}"))?,
+            reason: String::from(reason),
+        })
+    }
+}
+
+impl Ref {
+    /// Path of the reflog file, relative to `.mush`
+    fn reflog_path(&self) -> String {
+        format!("{LOGS_DIRECTORY}/{}", self.name)
+    }
+
+    pub fn has_reflog(&self) -> CliResult<bool> {
+        let filename = with_context("resolve path", dot_mush_slash(&self.reflog_path()))?;
+        Ok(std::path::Path::new(&filename).is_file())
+    }
+
+    /// Updates of HEAD and branches are always logged.
+    /// Other refs are only logged if they already have a reflog.
+    pub fn should_log(&self) -> CliResult<bool> {
+        Ok(self.name == HEAD || self.is_branch() || self.has_reflog()?)
+    }
+
+    /// All entries of this ref's reflog, oldest first.
+    /// Empty if the ref has no reflog.
+    pub fn read_reflog(&self) -> CliResult<Vec<ReflogEntry>> {
+        let filename = with_context("resolve path", dot_mush_slash(&self.reflog_path()))?;
+        let contents = with_context(&format!("read reflog of `{self}`"), try_read_filename_to_str(&filename))?
+            .unwrap_or_default();
+
+        contents.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                ReflogEntry::deserialize(line)
+                    .map_err(|msg| format!("Failed to read reflog of `{self}`: {msg}"))
+            })
+            .collect()
+    }
+
+    /// The lock on this ref's reflog (`logs/<ref>.lock`), which every writer of the log holds.
+    /// This isn't the ref's lock: HEAD's log is also appended to when the branch it points to is updated.
+    fn lock_reflog(&self) -> CliResult<LockFile> {
+        let filename = with_context("resolve path", dot_mush_slash(&self.reflog_path()))?;
+        with_context(&format!("lock reflog of `{self}`"), LockFile::acquire(&filename))
+    }
+
+    pub fn append_reflog(&self, entry: &ReflogEntry) -> CliResult<()> {
+        let _lock = self.lock_reflog()?; // never committed: only released on drop
+
+        let filename = with_context("resolve path", dot_mush_slash(&self.reflog_path()))?;
+        with_context(
+            &format!("append to reflog of `{self}`"),
+            append_to_file_all(&filename, entry.serialize().as_bytes())
+        )
+    }
+
+    /// Keep only the entries of this ref's reflog for which `keep` is true.
+    /// The log's lock is held from the read to the (atomic) rewrite,
+    /// so an entry appended in between can't be lost.
+    pub fn expire_reflog(&self, keep: impl Fn(&ReflogEntry) -> bool) -> CliResult<()> {
+        let context = format!("write reflog of `{self}`");
+        let mut lock = self.lock_reflog()?;
+
+        let contents = self.read_reflog()?
+            .into_iter()
+            .filter(keep)
+            .map(|entry| entry.serialize())
+            .collect::<String>();
+
+        with_context(&context, lock.write_all(contents.as_bytes()))?;
+        with_context(&context, lock.commit())
+    }
+
+    pub fn delete_reflog(&self) -> CliResult<()> {
+        if !self.has_reflog()? {
+            return Ok(());
+        }
+
+        let filename = with_context("resolve path", dot_mush_slash(&self.reflog_path()))?;
+        std::fs::remove_file(&filename)
+            .map_err(|io_err| format!("Failed to delete reflog of `{self}`: {io_err}"))?;
+
+        remove_empty_parent_directories(&self.name, LOGS_DIRECTORY);
+        Ok(())
+    }
+
+    /// Move this ref's reflog (if any) to be the reflog of `new_ref`
+    pub fn rename_reflog(&self, new_ref: &Ref) -> CliResult<()> {
+        if !self.has_reflog()? {
+            return Ok(());
+        }
+
+        let old_filename = with_context("resolve path", dot_mush_slash(&self.reflog_path()))?;
+        let new_filename = with_context("resolve path", dot_mush_slash(&new_ref.reflog_path()))?;
+        let new_directory = std::path::Path::new(&new_filename).parent().unwrap().to_str().unwrap();
+
+        // Go through a temporary file, as one name can be a directory of the other
+        // (e.g. `refs/heads/a` -> `refs/heads/a/b`)
+        let temporary_filename = with_context("resolve path", dot_mush_slash(RENAMED_REFLOG))?;
+        let move_error = |io_err| format!("Failed to move reflog of `{self}` to `{new_ref}`: {io_err}");
+
+        std::fs::rename(&old_filename, &temporary_filename).map_err(move_error)?;
+        remove_empty_parent_directories(&self.name, LOGS_DIRECTORY);
+        with_context("rename reflog", create_directory_all_idempotent(new_directory))?;
+        std::fs::rename(&temporary_filename, &new_filename).map_err(move_error)?;
+
+        Ok(())
+    }
+
+    /// Like `Ref::dwim`, but for refs that have a reflog
+    /// (which might not exist anymore)
+    pub fn dwim_reflog(shorthand: &str) -> CliResult<Option<Self>> {
+        for reff in Self::dwim_candidates(shorthand) {
+            if reff.has_reflog()? {
+                return Ok(Some(reff));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// All refs that have a reflog, sorted by name
+pub fn list_reflogs() -> CliResult<Vec<Ref>> {
+    let logs_directory = with_context("resolve path", dot_mush_slash(LOGS_DIRECTORY))?;
+    walk_ref_files(std::path::Path::new(&logs_directory), "")
+}
@@ -0,0 +1,217 @@
+// Based on git's ref transactions:
+// https://git-scm.com/docs/git-update-ref#_description
+
+use crate::cli::{with_context, CliResult};
+use crate::hash::{Hash, ObjectFormat};
+use crate::io::LockFile;
+
+use super::packed::{PackedRef, PackedRefs};
+use super::{remove_empty_parent_directories, ExpectedValue, Ref, RefContents};
+
+enum Change {
+    Update(Hash),
+    /// Point a symbolic ref at the second ref, provided it currently points at the first
+    Symbolic(Ref, Ref),
+    Delete,
+    /// Only check the expected value
+    Verify,
+}
+
+struct QueuedUpdate {
+    reff: Ref,
+    change: Change,
+    expected: ExpectedValue,
+    deref: bool,
+    reason: String,
+}
+
+/// A set of ref updates that are applied all together, or not at all.
+/// On commit, every affected ref is locked, and every expected value is checked,
+/// before anything is written.
+#[derive(Default)]
+pub struct RefTransaction {
+    updates: Vec<QueuedUpdate>,
+    /// Reflogs to move (from, to) once the refs are written, for renames
+    reflog_moves: Vec<(Ref, Ref)>,
+}
+
+impl RefTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue pointing `reff` (or, if `deref`, the end of its symbolic chain) at `new_hash`
+    pub fn update(&mut self, reff: Ref, new_hash: Hash, expected: ExpectedValue, deref: bool, reason: &str) {
+        self.queue(reff, Change::Update(new_hash), expected, deref, reason);
+    }
+
+    /// Queue the deletion of `reff` (or, if `deref`, the end of its symbolic chain), which must exist
+    pub fn delete(&mut self, reff: Ref, expected: ExpectedValue, deref: bool) {
+        self.queue(reff, Change::Delete, expected, deref, "");
+    }
+
+    /// Queue pointing the symbolic ref `reff` at `new_target`, provided it still points at `old_target`
+    pub fn update_symbolic(&mut self, reff: Ref, old_target: Ref, new_target: Ref) {
+        self.queue(reff, Change::Symbolic(old_target, new_target), ExpectedValue::Any, false, "");
+    }
+
+    /// Queue a check that `reff` matches `expected` (without changing it)
+    pub fn verify(&mut self, reff: Ref, expected: ExpectedValue, deref: bool) {
+        self.queue(reff, Change::Verify, expected, deref, "");
+    }
+
+    /// Queue moving `old_ref` (which must point at `hash`) to `new_ref` (which must match `expected`).
+    /// The reflog of `old_ref` replaces that of `new_ref`, but only once both refs are written.
+    pub fn rename(&mut self, old_ref: Ref, new_ref: Ref, hash: Hash, expected: ExpectedValue, reason: &str) {
+        self.queue(new_ref.clone(), Change::Update(hash.clone()), expected, false, reason);
+        self.queue(old_ref.clone(), Change::Delete, ExpectedValue::Hash(hash), false, "");
+        self.reflog_moves.push((old_ref, new_ref));
+    }
+
+    fn queue(&mut self, reff: Ref, change: Change, expected: ExpectedValue, deref: bool, reason: &str) {
+        self.updates.push(QueuedUpdate { reff, change, expected, deref, reason: String::from(reason) });
+    }
+
+    /// Lock all refs, check all expected values, then apply every update.
+    /// If anything fails before the updates are applied, nothing is changed
+    /// (all locks are released).
+    ///
+    /// A ref can be created under the name of a ref deleted in the same transaction
+    /// (e.g. `refs/heads/a` -> `refs/heads/a/b`): as the deleted ref's file is in the way
+    /// until then, it is created in `packed-refs` (whose lock is then what guards it).
+    pub fn commit(self) -> CliResult<()> {
+        // The refs that will actually be written
+        let mut targets: Vec<Ref> = Vec::with_capacity(self.updates.len());
+        for update in self.updates.iter() {
+            let target = update.reff.update_target(update.deref)?;
+
+            if targets.contains(&target) {
+                return Err(format!("Multiple updates for ref `{target}` are not allowed"));
+            }
+            targets.push(target);
+        }
+
+        // Lock in a consistent (sorted) order, so that concurrent transactions can't deadlock.
+        // An early return drops (and so releases) every lock acquired so far.
+        let mut lock_order = (0..targets.len()).collect::<Vec<_>>();
+        lock_order.sort_by(|a, b| targets[*a].cmp(&targets[*b]));
+
+        let is_packed_update = |update: &QueuedUpdate, target: &Ref| matches!(update.change, Change::Update(_)) &&
+            self.updates.iter().zip(targets.iter()).any(|(other, deleted)| {
+                matches!(other.change, Change::Delete) && target.name.starts_with(&format!("{}/", deleted.name))
+            });
+
+        let mut locks: Vec<Option<LockFile>> = targets.iter().map(|_| None).collect();
+        for index in lock_order {
+            if !is_packed_update(&self.updates[index], &targets[index]) {
+                locks[index] = Some(targets[index].lock()?);
+            }
+        }
+
+        let mut old_hashes = Vec::with_capacity(targets.len());
+        for (update, target) in self.updates.iter().zip(targets.iter()) {
+            if matches!(update.change, Change::Delete) && !target.exists()? {
+                return Err(format!("Failed to delete ref `{target}`: no such ref"));
+            }
+
+            if let Change::Symbolic(old_target, _) = &update.change {
+                if !matches!(target.read()?, Some(RefContents::Symbolic(name)) if name == old_target.name) {
+                    return Err(format!("Cannot lock ref `{target}`: it no longer points to `{old_target}`"));
+                }
+            }
+
+            let old_hash = target.to_hash()?;
+            update.expected.check(target, old_hash.as_ref())?;
+            old_hashes.push(old_hash);
+        }
+
+        for ((update, target), lock) in self.updates.iter().zip(targets.iter()).zip(locks.iter_mut()) {
+            let contents = match &update.change {
+                Change::Update(new_hash) => RefContents::Hash(new_hash.clone()),
+                Change::Symbolic(_, new_target) => RefContents::Symbolic(new_target.name.clone()),
+                Change::Delete | Change::Verify => continue,
+            };
+
+            if let Some(lock) = lock.as_mut() {
+                with_context(&format!("update ref `{target}`"), lock.write_all(contents.serialize().as_bytes()))?;
+            }
+        }
+
+        // Remove deleted refs from `packed-refs` first, so that they can't reappear
+        // once their loose files are gone
+        let mut packed_deletions = Vec::new();
+        let mut packed_updates = Vec::new();
+        for (update, target) in self.updates.iter().zip(targets.iter()) {
+            match &update.change {
+                Change::Delete if target.read_packed()?.is_some() => packed_deletions.push(target),
+                Change::Update(new_hash) if is_packed_update(update, target) => packed_updates.push(PackedRef {
+                    reff: target.clone(),
+                    hash: new_hash.clone(),
+                    peeled: None,
+                }),
+                _ => (),
+            }
+        }
+
+        if !packed_deletions.is_empty() || !packed_updates.is_empty() {
+            let (packed_lock, mut packed_refs) = PackedRefs::lock()?;
+            for target in packed_deletions {
+                packed_refs.remove(target);
+            }
+            for packed_ref in packed_updates {
+                packed_refs.insert(packed_ref);
+            }
+            packed_refs.write(packed_lock)?;
+        }
+
+        // The destinations of renames are logged once their reflogs have been moved in
+        let is_moved_from = |target: &Ref| self.reflog_moves.iter().any(|(from, _)| from == target);
+        let is_moved_to = |target: &Ref| self.reflog_moves.iter().any(|(_, to)| to == target);
+        let mut deferred_logs = Vec::new();
+        let zero_hash = ObjectFormat::load()?.zero_hash();
+
+        for ((update, target), old_hash) in self.updates.iter().zip(targets.iter()).zip(old_hashes) {
+            if let Change::Update(new_hash) = &update.change {
+                let old_hash = old_hash.unwrap_or_else(|| zero_hash.clone());
+                match is_moved_to(target) {
+                    true => deferred_logs.push((target, old_hash, new_hash.clone(), &update.reason)),
+                    false => target.log_update(old_hash, new_hash.clone(), &update.reason)?,
+                }
+            }
+        }
+
+        // Deletions first, so that the directories they leave empty (e.g. `refs/heads/a/`
+        // for `refs/heads/a/b`) are out of the way of the refs written next
+        let (deletions, others): (Vec<_>, Vec<_>) = self.updates.iter().zip(targets.iter()).zip(locks)
+            .partition(|((update, _), _)| matches!(update.change, Change::Delete));
+
+        for ((_, target), lock) in deletions {
+            let lock = lock.expect("Invariant violated: ref not locked");
+            with_context(&format!("delete ref `{target}`"), lock.commit_deletion())?;
+            if !is_moved_from(target) {
+                target.delete_reflog()?;
+            }
+            remove_empty_parent_directories(&target.name, "");
+        }
+
+        for ((update, target), lock) in others {
+            match (&update.change, lock) {
+                (Change::Update(_) | Change::Symbolic(..), Some(lock)) => with_context(&format!("update ref `{target}`"), lock.commit())?,
+                (Change::Update(_), None) => (), // written to `packed-refs`
+                (Change::Verify, _) => (), // the lock is released on drop
+                _ => panic!("Invariant violated: ref not locked"),
+            }
+        }
+
+        for (from, to) in self.reflog_moves.iter() {
+            to.delete_reflog()?;
+            from.rename_reflog(to)?;
+        }
+
+        for (target, old_hash, new_hash, reason) in deferred_logs {
+            target.log_update(old_hash, new_hash, reason)?;
+        }
+
+        Ok(())
+    }
+}
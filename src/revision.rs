@@ -1,4 +1,17 @@
-use crate::{cli::CliResult, hash::Hash};
+pub mod date;
+pub mod walk;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::cli::CliResult;
+use crate::hash::{Hash, MIN_ABBREVIATION_LENGTH};
+use crate::index::RepoRelativeFilename;
+use crate::io::read_index;
+use crate::object::commit::CommitObject;
+use crate::object::store::ObjectStore;
+use crate::object::{Object, ObjectType};
+use crate::refs::reflog::ReflogEntry;
+use crate::refs::{current_branch, list_refs, Ref, HEAD};
 
 /// A hypothetical pointer to an object (yet to be `dereference`d)
 /// (could be a hash, ref, or expression involving the two)
@@ -15,19 +28,22 @@ impl<'s> RevisionSpec<'s> {
         })
     }
 
-    /// Attempt to locate this revision in the database
+    /// Attempt to locate this revision in `store` (and the refs)
     /// `Ok(None)` is returned if the search fails gracefully
-    pub fn try_dereference(&self) -> CliResult<Option<Hash>> {
-        match &self.parse_tree {
-            RevisionSpecParseTree::HashOrRef(string) => Ok(Some(Hash::try_from_str(string).unwrap())), // TODO don't assume the input is a valid hash
-            _ => todo!(),
-        }
+    pub fn try_dereference(&self, store: &impl ObjectStore) -> CliResult<Option<Hash>> {
+        self.parse_tree.try_dereference(store)
+    }
+
+    /// Like `dereference`, but the result must be (or be peelable to) a commit
+    pub fn dereference_to_commit(&self, store: &impl ObjectStore) -> CliResult<Hash> {
+        self.dereference(store)
+            .and_then(|hash| peel(store, hash, &PeelTarget::Type(ObjectType::Commit)))
     }
 
-    /// Attempt to locate this revision in the database;
+    /// Attempt to locate this revision in `store` (and the refs);
     /// return the hash or report why it couldn't be found
-    pub fn dereference(&self) -> CliResult<Hash> {
-        self.try_dereference().and_then(|hash| match hash {
+    pub fn dereference(&self, store: &impl ObjectStore) -> CliResult<Hash> {
+        self.try_dereference(store).and_then(|hash| match hash {
             Some(x) => Ok(x),
             None => Err(format!(
                 "Not a valid object name: `{}`",
@@ -37,6 +53,291 @@ impl<'s> RevisionSpec<'s> {
     }
 }
 
+/// A revision argument that selects a set of commits (e.g. for `rev-list`):
+/// `<rev>`, `^<rev>`, `<rev1>..<rev2>`, or `<rev1>...<rev2>`
+pub enum RevisionRange<'s> {
+    /// `<rev>`: commits reachable from `<rev>`
+    Include(RevisionSpec<'s>),
+    /// `^<rev>`: exclude commits reachable from `<rev>`
+    Exclude(RevisionSpec<'s>),
+    /// `<rev1>..<rev2>`: commits reachable from `<rev2>`, but not from `<rev1>`
+    Range(RevisionSpec<'s>, RevisionSpec<'s>),
+    /// `<rev1>...<rev2>`: commits reachable from either, but not from both
+    SymmetricDifference(RevisionSpec<'s>, RevisionSpec<'s>),
+}
+
+impl<'s> RevisionRange<'s> {
+    pub fn parse(input: &'s str) -> CliResult<Self> {
+        // an omitted endpoint (e.g. `main..`) means HEAD
+        let parse_endpoint = |endpoint: &'s str| RevisionSpec::parse(if endpoint.is_empty() { HEAD } else { endpoint });
+
+        if let Some(excluded) = input.strip_prefix('^') {
+            return Ok(Self::Exclude(RevisionSpec::parse(excluded)?));
+        }
+
+        match Self::find_range_operator(input) {
+            Some((idx, "...")) => Ok(Self::SymmetricDifference(parse_endpoint(&input[..idx])?, parse_endpoint(&input[idx + 3..])?)),
+            Some((idx, _)) => Ok(Self::Range(parse_endpoint(&input[..idx])?, parse_endpoint(&input[idx + 2..])?)),
+            None => Ok(Self::Include(RevisionSpec::parse(input)?)),
+        }
+    }
+
+    /// Index of the first `..` or `...` that's not inside `{}`, and comes before any `:<path>`
+    fn find_range_operator(input: &str) -> Option<(usize, &'static str)> {
+        for (idx, c, depth) in scan_braces(input) {
+            match c {
+                ':' if depth == 0 => return None, // a path (or `:/<pattern>`) follows, which may contain `..`
+                '.' if depth == 0 && input[idx..].starts_with("...") => return Some((idx, "...")),
+                '.' if depth == 0 && input[idx..].starts_with("..") => return Some((idx, "..")),
+                _ => (),
+            }
+        }
+
+        None
+    }
+
+    /// The commits to start from, and the commits whose history is excluded: `(included, excluded)`
+    pub fn dereference(&self, store: &impl ObjectStore) -> CliResult<(Vec<Hash>, Vec<Hash>)> {
+        match self {
+            Self::Include(spec) => Ok((vec![spec.dereference_to_commit(store)?], vec![])),
+            Self::Exclude(spec) => Ok((vec![], vec![spec.dereference_to_commit(store)?])),
+            Self::Range(from, to) => Ok((vec![to.dereference_to_commit(store)?], vec![from.dereference_to_commit(store)?])),
+            Self::SymmetricDifference(a, b) => {
+                let (a, b) = (a.dereference_to_commit(store)?, b.dereference_to_commit(store)?);
+                let merge_bases = walk::merge_bases(store, &a, &b)?;
+                Ok((vec![a, b], merge_bases))
+            },
+        }
+    }
+}
+
+/// Find the unique object whose hash starts with `prefix`.
+/// `Ok(None)` is returned if `prefix` can't be an abbreviated hash, or no object matches.
+fn dereference_abbreviated_hash(store: &impl ObjectStore, prefix: &str) -> CliResult<Option<Hash>> {
+    let is_plausible_prefix = (MIN_ABBREVIATION_LENGTH..=store.object_format().hex_length()).contains(&prefix.len()) &&
+        prefix.chars().all(|c| c.is_ascii_hexdigit());
+
+    if !is_plausible_prefix {
+        return Ok(None);
+    }
+
+    let mut candidates = store.find_by_prefix(&prefix.to_ascii_lowercase())?;
+
+    match candidates.len() {
+        0 => Ok(None),
+        1 => Ok(candidates.pop()),
+        _ => {
+            let candidate_descriptions = candidates.iter()
+                .map(|hash| {
+                    let type_str = store.read_header(hash)
+                        .map(|header| String::from(header.tipe.to_str()))
+                        .unwrap_or(String::from("[unreadable]"));
+                    format!("\n    {} {type_str}", hash.as_str())
+                })
+                .collect::<String>();
+
+            Err(format!("Short object hash `{prefix}` is ambiguous. The candidates are:{candidate_descriptions}"))
+        },
+    }
+}
+
+/// Read `hash` from the database (peeling tags), failing if it isn't a commit.
+/// Returns the commit and its hash.
+fn read_commit(store: &impl ObjectStore, hash: &Hash) -> CliResult<(Hash, CommitObject)> {
+    match store.read(hash)? {
+        Object::Commit(commit_object) => Ok((hash.clone(), commit_object)),
+        Object::Tag(tag_object) => read_commit(store, &tag_object.target_hash),
+        object => Err(format!("Object {} is a {}, not a commit", hash.as_str(), object.tipe().to_str())),
+    }
+}
+
+/// The `<type>` in `<rev>^{<type>}`
+enum PeelTarget {
+    /// `^{}`: the first object that isn't a tag (following tags)
+    NonTag,
+    /// `^{object}`: any object (only asserts existence)
+    AnyObject,
+    /// `^{commit}`, `^{tree}`, ...
+    Type(ObjectType),
+}
+
+impl PeelTarget {
+    fn from_string(string: &str) -> Option<Self> {
+        match string {
+            "" => Some(Self::NonTag),
+            "object" => Some(Self::AnyObject),
+            _ => ObjectType::from_string(string).ok().map(Self::Type),
+        }
+    }
+}
+
+/// Follow `hash` (tag -> target, commit -> tree) until reaching an object of the desired type
+fn peel(store: &impl ObjectStore, hash: Hash, target: &PeelTarget) -> CliResult<Hash> {
+    let tipe = store.read_header(&hash)?.tipe;
+
+    match (target, tipe) {
+        (PeelTarget::AnyObject, _) => Ok(hash),
+        (PeelTarget::Type(target_type), tipe) if *target_type == tipe => Ok(hash),
+        (_, ObjectType::Tag) => match store.read(&hash)? {
+            Object::Tag(tag_object) => peel(store, tag_object.target_hash, target),
+            _ => Err(format!("Corrupt object {} (header and contents disagree on type)", hash.as_str())),
+        },
+        (PeelTarget::NonTag, _) => Ok(hash),
+        (PeelTarget::Type(ObjectType::Tree), ObjectType::Commit) => Ok(read_commit(store, &hash)?.1.tree_hash),
+        (PeelTarget::Type(target_type), tipe) => Err(format!(
+            "Object {} is a {}, and can't be peeled to a {}",
+            hash.as_str(),
+            tipe.to_str(),
+            target_type.to_str(),
+        )),
+    }
+}
+
+/// Follow tags until reaching an object that isn't a tag (`<rev>^{}`)
+pub fn peel_tags(store: &impl ObjectStore, hash: Hash) -> CliResult<Hash> {
+    peel(store, hash, &PeelTarget::NonTag)
+}
+
+/// The reflog that `[<ref>]@{...}` refers to (oldest entry first), and its ref.
+/// Without a ref name, that's the current branch (or HEAD, if detached).
+/// `Ok(None)` is returned if there's no such ref.
+fn read_reflog_for_revision(ref_name: Option<&str>) -> CliResult<Option<(Ref, Vec<ReflogEntry>)>> {
+    let reff = match ref_name {
+        None => current_branch()?.unwrap_or(Ref::head()),
+        Some(ref_name) => match Ref::dwim_reflog(ref_name)?.or(Ref::dwim(ref_name)?) {
+            Some(reff) => reff,
+            None => return Ok(None),
+        },
+    };
+
+    let entries = reff.read_reflog()?;
+
+    if entries.is_empty() {
+        return Err(format!("Log for `{reff}` is empty"));
+    }
+
+    Ok(Some((reff, entries)))
+}
+
+/// The name of the branch (or hash) that was checked out before the last `n` checkouts,
+/// according to HEAD's reflog (`@{-<n>}`)
+fn nth_previous_branch(n: usize) -> CliResult<String> {
+    let checkout_re = regex::Regex::new(r"^checkout: moving from (?<from>\S+) to \S+$").unwrap();
+
+    let previous_branches = Ref::head().read_reflog()?
+        .into_iter()
+        .rev()
+        .filter_map(|entry| checkout_re.captures(&entry.reason).map(|captures| String::from(&captures["from"])))
+        .collect::<Vec<_>>();
+
+    previous_branches.get(n - 1)
+        .cloned()
+        .ok_or(format!("`@{{-{n}}}`: only {} checkout(s) found in the reflog of HEAD", previous_branches.len()))
+}
+
+/// The `<pattern>` in `:/<pattern>` and `<rev>^{/<pattern>}`:
+/// a regex, negated if prefixed with `!-` (a literal leading `!` is written `!!`)
+struct MessagePattern {
+    regex: regex::Regex,
+    negated: bool,
+}
+
+impl MessagePattern {
+    fn from_string(string: &str) -> Option<Self> {
+        let (pattern, negated) = match string.strip_prefix('!') {
+            None => (string, false),
+            Some(rest) if rest.starts_with('!') => (rest, false),
+            Some(rest) => (rest.strip_prefix('-')?, true), // other `!` prefixes are reserved
+        };
+
+        regex::Regex::new(pattern).ok()
+            .map(|regex| MessagePattern { regex, negated })
+    }
+
+    fn matches(&self, message: &str) -> bool {
+        self.regex.is_match(message) != self.negated
+    }
+}
+
+/// Every commit pointed to by HEAD or a ref (peeling tags; refs to other objects are skipped)
+fn all_ref_commits(store: &impl ObjectStore) -> CliResult<Vec<Hash>> {
+    let mut refs = list_refs("refs/")?;
+    refs.push(Ref::head());
+
+    let mut commits = Vec::new();
+    for reff in refs {
+        if let Some(hash) = reff.to_hash()? {
+            if let Ok(commit_hash) = peel(store, hash, &PeelTarget::Type(ObjectType::Commit)) {
+                commits.push(commit_hash);
+            }
+        }
+    }
+
+    Ok(commits)
+}
+
+/// Search the history of `starting_points` (newest first, by author date)
+/// for the first commit whose message matches `pattern`
+fn youngest_matching_commit(store: &impl ObjectStore, starting_points: Vec<Hash>, pattern: &MessagePattern) -> CliResult<Option<Hash>> {
+    let mut seen = std::collections::HashSet::new();
+    // (author date, insertion order): newest first, ties broken first-come-first-served
+    let mut queue = std::collections::BinaryHeap::new();
+    let mut queued_commits = Vec::new();
+
+    let mut pending = starting_points;
+
+    loop {
+        for hash in pending.drain(..) {
+            if seen.insert(hash.clone()) {
+                let (hash, commit_object) = read_commit(store, &hash)?;
+                queue.push((commit_object.author.timestamp, std::cmp::Reverse(queued_commits.len())));
+                queued_commits.push(Some((hash, commit_object)));
+            }
+        }
+
+        let Some((_, std::cmp::Reverse(index))) = queue.pop() else {
+            return Ok(None);
+        };
+        let (hash, commit_object) = queued_commits[index].take()
+            .expect("Invariant violated: commit popped twice");
+
+        if pattern.matches(&commit_object.message) {
+            return Ok(Some(hash));
+        }
+
+        pending = commit_object.parent_hashes;
+    }
+}
+
+/// Walk down the tree `tree_hash` (or the tree of a commit) by the `/`-separated `path`
+fn lookup_path_in_tree(store: &impl ObjectStore, tree_hash: Hash, path: &str) -> CliResult<Hash> {
+    let mut hash = peel(store, tree_hash, &PeelTarget::Type(ObjectType::Tree))?;
+    let root_hash = hash.clone();
+
+    for component in path.split('/').filter(|component| !component.is_empty()) {
+        let Object::Tree(entries) = store.read(&hash)? else {
+            return Err(format!("Path `{path}` does not exist in tree {}", root_hash.as_str()));
+        };
+
+        hash = entries.iter()
+            .find(|entry| entry.filename() == component)
+            .map(|entry| entry.hash().clone())
+            .ok_or(format!("Path `{path}` does not exist in tree {}", root_hash.as_str()))?;
+    }
+
+    Ok(hash)
+}
+
+fn lookup_path_in_index(path: &str) -> CliResult<Hash> {
+    let mut index = crate::cli::with_context("read index", read_index())?
+        .unwrap_or(crate::index::Index::empty());
+
+    index.entries()
+        .get(&RepoRelativeFilename(String::from(path)))
+        .map(|entry| entry.hash().clone())
+        .ok_or(format!("Path `{path}` is not in the index"))
+}
+
 enum RevisionSpecParseTree {
     // Many strings are ambiguous and could be either hashes or refs;
     // we can't know until checking the database, which happens after
@@ -44,53 +345,270 @@ enum RevisionSpecParseTree {
     HashOrRef(String),
     NthParent(Box<RevisionSpecParseTree>, usize), // <rev>^[<n>]
     NthGenerationalParent(Box<RevisionSpecParseTree>, usize), // <rev>~[<n>]
+    Peel(Box<RevisionSpecParseTree>, PeelTarget), // <rev>^{[<type>]}
+    TreePath(Box<RevisionSpecParseTree>, String), // <rev>:<path>
+    IndexPath(String), // :<path>
+    NthReflogEntry(Option<String>, usize), // [<ref>]@{<n>}
+    ReflogEntryAtDate(Option<String>, DateTime<FixedOffset>), // [<ref>]@{<date>}
+    NthPreviousBranch(usize), // @{-<n>}
+    MessageSearch(Option<Box<RevisionSpecParseTree>>, MessagePattern), // :/<pattern> or <rev>^{/<pattern>}
 }
 
 impl RevisionSpecParseTree {
+    fn try_dereference(&self, store: &impl ObjectStore) -> CliResult<Option<Hash>> {
+        match self {
+            Self::HashOrRef(string) => {
+                if let Some(hash) = store.object_format().parse_hash(string) {
+                    return Ok(Some(hash));
+                }
+
+                match Ref::dwim(string)? {
+                    Some(reff) => reff.to_hash(),
+                    None => dereference_abbreviated_hash(store, string),
+                }
+            },
+            Self::NthParent(base, n) => {
+                let Some(hash) = base.try_dereference(store)? else {
+                    return Ok(None);
+                };
+                let (hash, commit_object) = read_commit(store, &hash)?;
+
+                if *n == 0 { // `<rev>^0` is the commit itself
+                    return Ok(Some(hash));
+                }
+
+                commit_object.parent_hashes.get(n - 1)
+                    .cloned()
+                    .map(Some)
+                    .ok_or(format!(
+                        "Commit {} has {} parent(s), so it has no parent #{n}",
+                        hash.as_str(),
+                        commit_object.parent_hashes.len(),
+                    ))
+            },
+            Self::NthGenerationalParent(base, n) => {
+                let Some(hash) = base.try_dereference(store)? else {
+                    return Ok(None);
+                };
+                let (mut hash, mut commit_object) = read_commit(store, &hash)?;
+
+                for generation in 1..=*n {
+                    let Some(parent_hash) = commit_object.parent_hashes.first().cloned() else {
+                        return Err(format!(
+                            "Cannot go back {n} generation(s): reached root commit {} after {}",
+                            hash.as_str(),
+                            generation - 1,
+                        ));
+                    };
+                    (hash, commit_object) = read_commit(store, &parent_hash)?;
+                }
+
+                Ok(Some(hash))
+            },
+            Self::Peel(base, target) => {
+                base.try_dereference(store)?
+                    .map(|hash| peel(store, hash, target))
+                    .transpose()
+            },
+            Self::TreePath(base, path) => {
+                base.try_dereference(store)?
+                    .map(|hash| lookup_path_in_tree(store, hash, path))
+                    .transpose()
+            },
+            Self::IndexPath(path) => lookup_path_in_index(path).map(Some),
+            Self::NthReflogEntry(ref_name, n) => {
+                let Some((reff, entries)) = read_reflog_for_revision(ref_name.as_deref())? else {
+                    return Ok(None);
+                };
+
+                // `@{0}` is the newest entry
+                match entries.len().checked_sub(n + 1) {
+                    Some(index) => Ok(Some(entries[index].new_hash.clone())),
+                    // one past the end: the value before the oldest entry
+                    None if *n == entries.len() && !entries[0].old_hash.is_zero() => Ok(Some(entries[0].old_hash.clone())),
+                    None => Err(format!("Log for `{reff}` only has {} entries", entries.len())),
+                }
+            },
+            Self::ReflogEntryAtDate(ref_name, date) => {
+                let Some((_, entries)) = read_reflog_for_revision(ref_name.as_deref())? else {
+                    return Ok(None);
+                };
+
+                match entries.iter().rev().find(|entry| entry.identity.timestamp <= *date) {
+                    Some(entry) => Ok(Some(entry.new_hash.clone())),
+                    // before the log begins: the oldest value known
+                    None if !entries[0].old_hash.is_zero() => Ok(Some(entries[0].old_hash.clone())),
+                    None => Ok(Some(entries[0].new_hash.clone())),
+                }
+            },
+            Self::NthPreviousBranch(n) => {
+                let previous_branch = nth_previous_branch(*n)?;
+                Self::HashOrRef(previous_branch).try_dereference(store)
+            },
+            Self::MessageSearch(base, pattern) => {
+                let starting_points = match base {
+                    Some(base) => match base.try_dereference(store)? {
+                        Some(hash) => vec![read_commit(store, &hash)?.0],
+                        None => return Ok(None),
+                    },
+                    None => all_ref_commits(store)?,
+                };
+
+                youngest_matching_commit(store, starting_points, pattern)
+            },
+        }
+    }
+
     fn parse(input: &str) -> CliResult<Self> {
-        let main_re = regex::Regex::new(r"^(?<base>[^^~]+)(?<modifiers>[\^~].*)?").unwrap();
+        let bad_revision_spec = || format!("Bad revision spec: `{input}`");
+
+        if let Some(pattern) = input.strip_prefix(":/") {
+            return MessagePattern::from_string(pattern)
+                .map(|pattern| Self::MessageSearch(None, pattern))
+                .ok_or_else(bad_revision_spec);
+        }
+
+        if let Some(path) = input.strip_prefix(':') {
+            return Ok(Self::IndexPath(String::from(path)));
+        }
+
+        // e.g.
+        // main~2^{tree}:src/lib.rs
+        // ^^^^^^^^^^^^^
+        // "revision"
+        //               ^^^^^^^^^^
+        //               "path"
+
+        match Self::find_path_separator(input) {
+            Some(idx) => {
+                let (revision, path) = (&input[..idx], &input[idx + 1..]);
+                Self::parse_without_path(revision)
+                    .map(|tree| Self::TreePath(Box::new(tree), String::from(path)))
+            },
+            None => Self::parse_without_path(input),
+        }
+        .ok_or_else(bad_revision_spec)
+    }
+
+    /// Index of the first `:` that's not inside `{}`
+    fn find_path_separator(input: &str) -> Option<usize> {
+        scan_braces(input)
+            .find(|&(_, c, depth)| c == ':' && depth == 0)
+            .map(|(idx, _, _)| idx)
+    }
+
+    /// The part before any `^`/`~` modifiers: a hash or ref, optionally followed by `@{...}`
+    fn parse_base(base: &str) -> Option<Self> {
+        let reflog_re = regex::Regex::new(r"^(?<ref>.*?)@\{(?<selector>[^}]*)\}$").unwrap();
+
+        if base == "@" { // shorthand for HEAD
+            return Some(Self::HashOrRef(String::from(HEAD)));
+        }
+
+        let Some(captures) = reflog_re.captures(base) else {
+            return Some(Self::HashOrRef(String::from(base)));
+        };
+
+        let ref_name = match &captures["ref"] {
+            "" => None,
+            "@" => Some(String::from(HEAD)),
+            ref_name => Some(String::from(ref_name)),
+        };
+        let selector = &captures["selector"];
+
+        if let Some(n) = selector.strip_prefix('-') {
+            return match (ref_name, n.parse::<usize>()) {
+                (None, Ok(n)) if n > 0 => Some(Self::NthPreviousBranch(n)),
+                _ => None,
+            };
+        }
+
+        if let Ok(n) = selector.parse::<usize>() {
+            return Some(Self::NthReflogEntry(ref_name, n));
+        }
+
+        date::parse_approximate_date(selector, chrono::Local::now().fixed_offset())
+            .map(|date| Self::ReflogEntryAtDate(ref_name, date))
+    }
+
+    fn parse_without_path(input: &str) -> Option<Self> {
+        let main_re = regex::Regex::new(r"^(?<base>[^^~]+)(?<modifiers>[\^~].*)?$").unwrap();
 
         // e.g.
-        // origin/master~3^2~
+        // origin/master~3^2~^{tree}
         // ^^^^^^^^^^^^^
         // "base"
-        //              ^^^^
+        //              ^^^^^^^^^^^^
         //              "modifiers"
 
-        main_re
-            .captures(input)
-            .and_then(|captures| {
-                let base_str = &captures["base"];
-                let modifiers_str = captures.name("modifiers")
-                    .map(|matcch| matcch.as_str())
-                    .unwrap_or("");
-
-                let mod_re = regex::Regex::new(r"([\^~])([0-9]*)").unwrap();
-
-                let modifiers_opt = mod_re
-                    .captures_iter(modifiers_str)
-                    .map(|captures| captures.extract())
-                    .map(|(_, [operator, arg])| {
-                        let arg = if arg == "" { "1" } else { arg };
-                        arg.parse::<usize>()
-                            // Result to Option
-                            .map(|n| Some(n))
-                            .unwrap_or(None)
-                            .map(|n| (operator, n))
-                    })
-                    .collect::<Option<Vec<_>>>(); // sequence (fail on first None)
-
-                modifiers_opt.map(|modifiers| {
-                    modifiers.iter().fold(
-                        Self::HashOrRef(String::from(base_str)),
-                        |acc, (operator, n)| match *operator {
-                            "^" => RevisionSpecParseTree::NthParent(Box::new(acc), *n),
-                            "~" => RevisionSpecParseTree::NthGenerationalParent(Box::new(acc), *n),
-                            _ => panic!("Invariant error (regex with invalid modifier modifier)"),
-                        },
-                    )
-                })
-            })
-            .ok_or(format!("Bad revision spec: `{input}`"))
+        let captures = main_re.captures(input)?;
+        let base_str = &captures["base"];
+        let modifiers_str = captures.name("modifiers")
+            .map(|matcch| matcch.as_str())
+            .unwrap_or("");
+
+        let operator_re = regex::Regex::new(r"^(?<operator>[\^~])(?<arg>[0-9]*)").unwrap();
+
+        let mut parse_tree = Self::parse_base(base_str)?;
+        let mut rest = modifiers_str;
+
+        while !rest.is_empty() {
+            if rest.starts_with("^{") {
+                // the `}` matching `^{`, so that a `/<pattern>` can contain (escaped) braces
+                let (end, _, _) = scan_braces(rest).find(|&(_, c, depth)| c == '}' && depth == 1)?;
+                let peel_str = &rest[2..end];
+                rest = &rest[end + 1..];
+
+                parse_tree = match peel_str.strip_prefix('/') {
+                    Some(pattern) => Self::MessageSearch(Some(Box::new(parse_tree)), MessagePattern::from_string(pattern)?),
+                    None => Self::Peel(Box::new(parse_tree), PeelTarget::from_string(peel_str)?),
+                };
+                continue;
+            }
+
+            let captures = operator_re.captures(rest)?; // garbage between modifiers
+            rest = &rest[captures.get(0).unwrap().end()..];
+
+            let arg = match &captures["arg"] {
+                "" => "1",
+                arg => arg,
+            };
+            let n = arg.parse::<usize>().ok()?;
+
+            parse_tree = match &captures["operator"] {
+                "^" => Self::NthParent(Box::new(parse_tree), n),
+                "~" => Self::NthGenerationalParent(Box::new(parse_tree), n),
+                _ => panic!("Invariant error (regex with invalid modifier modifier)"),
+            };
+        }
+
+        Some(parse_tree)
     }
 }
+
+/// The unescaped characters of `input`, with the `{}` nesting depth before each: `(idx, char, depth)`.
+/// Inside braces, `\` escapes the next character, so that e.g. `^{/a\}b}` is a single group.
+fn scan_braces(input: &str) -> impl Iterator<Item = (usize, char, usize)> + '_ {
+    let mut depth = 0usize;
+    let mut escaped = false;
+
+    input.char_indices().filter_map(move |(idx, c)| {
+        if escaped {
+            escaped = false;
+            return None;
+        }
+
+        let depth_before = depth;
+        match c {
+            '\\' if depth > 0 => {
+                escaped = true;
+                return None;
+            },
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+
+        Some((idx, c, depth_before))
+    })
+}
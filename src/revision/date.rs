@@ -0,0 +1,94 @@
+// Based on git's "approxidate":
+// https://git-scm.com/docs/git-rev-parse#_specifying_revisions
+
+use chrono::{DateTime, Duration, FixedOffset, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+
+const ABSOLUTE_DATE_TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Parse a date in one of the formats accepted by `<ref>@{<date>}`:
+/// - absolute: `2024-01-31`, `2024-01-31 09:30[:00]`, RFC 3339 or RFC 2822, `@<unix timestamp>`
+/// - relative to `now`: `yesterday`, `3.days.ago`, `2 weeks ago`, `yesterday.noon`, `midnight`, ...
+///
+/// `None` is returned if the string isn't understood
+pub fn parse_approximate_date(string: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let string = string.trim();
+
+    parse_absolute_date(string).or_else(|| parse_relative_date(string, now))
+}
+
+fn parse_absolute_date(string: &str) -> Option<DateTime<FixedOffset>> {
+    if let Some(seconds) = string.strip_prefix('@') {
+        return seconds.parse::<i64>().ok()
+            .and_then(|seconds| FixedOffset::east_opt(0)?.timestamp_opt(seconds, 0).single());
+    }
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(string).or_else(|_| DateTime::parse_from_rfc2822(string)) {
+        return Some(date);
+    }
+
+    let local_date_time = ABSOLUTE_DATE_TIME_FORMATS.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(string, format).ok())
+        //< git keeps the current time of day for a date without a time; use midnight instead
+        .or_else(|| NaiveDate::parse_from_str(string, "%Y-%m-%d").ok().map(|date| date.and_time(NaiveTime::MIN)))?;
+
+    Local.from_local_datetime(&local_date_time)
+        .earliest()
+        .map(|date| date.fixed_offset())
+}
+
+fn parse_relative_date(string: &str, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let tokens = string.split(|c: char| c.is_whitespace() || c == '.' || c == ',')
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+
+    if tokens.is_empty() {
+        return None;
+    }
+
+    let mut date = now;
+    let mut tokens = tokens.iter();
+
+    while let Some(token) = tokens.next() {
+        match token.as_str() {
+            "now" | "today" | "ago" => (),
+            "yesterday" => date = date.checked_sub_signed(Duration::days(1))?,
+            "noon" => date = at_time_of_day(date, NaiveTime::from_hms_opt(12, 0, 0)?, now)?,
+            "midnight" => date = at_time_of_day(date, NaiveTime::MIN, now)?,
+            number => {
+                let n = number.parse::<u32>().ok()?;
+                let unit = tokens.next()?;
+
+                date = match unit.strip_suffix('s').unwrap_or(unit) {
+                    "second" => date.checked_sub_signed(Duration::try_seconds(n.into())?)?,
+                    "minute" => date.checked_sub_signed(Duration::try_minutes(n.into())?)?,
+                    "hour" => date.checked_sub_signed(Duration::try_hours(n.into())?)?,
+                    "day" => date.checked_sub_signed(Duration::try_days(n.into())?)?,
+                    "week" => date.checked_sub_signed(Duration::try_weeks(n.into())?)?,
+                    "month" => date.checked_sub_months(Months::new(n))?,
+                    "year" => date.checked_sub_months(Months::new(n.checked_mul(12)?))?,
+                    _ => return None,
+                };
+            },
+        }
+    }
+
+    Some(date)
+}
+
+/// `date`, with its time of day replaced by `time`
+/// (going back a day if that would be in the future, like git does)
+fn at_time_of_day(date: DateTime<FixedOffset>, time: NaiveTime, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+    let result = date.with_time(time).earliest()?;
+
+    if result > now {
+        result.checked_sub_signed(Duration::days(1))
+    } else {
+        Some(result)
+    }
+}
@@ -0,0 +1,430 @@
+// Docs for git's commit ordering options:
+// https://git-scm.com/docs/git-rev-list#_commit_ordering
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::cli::CliResult;
+use crate::hash::Hash;
+use crate::object::commit::CommitObject;
+use crate::object::store::ObjectStore;
+use crate::object::{Object, ObjectType};
+
+use super::read_commit;
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum WalkOrder {
+    /// Newest first (by author date), as commits are discovered
+    #[default]
+    Chronological,
+    /// No parent before all of its children; otherwise newest first
+    Date,
+    /// No parent before all of its children, and lines of history aren't interleaved
+    Topological,
+}
+
+#[derive(Default)]
+pub struct WalkOptions {
+    pub order: WalkOrder,
+    /// Only follow the first parent of merge commits
+    pub first_parent: bool,
+    /// Stop after this many commits (applied before `reverse`)
+    pub max_count: Option<usize>,
+    /// Output the selected commits in reverse order
+    pub reverse: bool,
+}
+
+struct CommitNode {
+    hash: Hash,
+    timestamp: DateTime<FixedOffset>,
+    /// Only the first parent, with `first_parent`
+    parents: Vec<Hash>,
+}
+
+/// The commits reachable from `included`, but not from `excluded`, in the order given by `options`.
+/// Tags are peeled; any other non-commit is an error.
+pub fn walk_commits(store: &impl ObjectStore, included: &[Hash], excluded: &[Hash], options: &WalkOptions) -> CliResult<Vec<Hash>> {
+    let excluded = uninteresting_commits(store, included, excluded)?;
+
+    let mut commits = match options.order {
+        WalkOrder::Chronological => chronological_walk(store, included, &excluded, options)?,
+        WalkOrder::Date | WalkOrder::Topological => {
+            let nodes = collect_commits(store, included, &excluded, options.first_parent)?;
+            topological_sort(nodes, options.order)
+        },
+    };
+
+    if let Some(max_count) = options.max_count {
+        commits.truncate(max_count);
+    }
+
+    if options.reverse {
+        commits.reverse();
+    }
+
+    Ok(commits)
+}
+
+/// Every commit reachable from `starting_points` (following all parents)
+fn ancestors(store: &impl ObjectStore, starting_points: &[Hash]) -> CliResult<HashMap<Hash, CommitObject>> {
+    let mut ancestors = HashMap::new();
+    let mut frontier = starting_points.to_vec();
+
+    while let Some(hash) = frontier.pop() {
+        let (hash, commit_object) = read_commit(store, &hash)?;
+
+        if ancestors.contains_key(&hash) {
+            continue;
+        }
+
+        frontier.extend(commit_object.parent_hashes.iter().cloned());
+        ancestors.insert(hash, commit_object);
+    }
+
+    Ok(ancestors)
+}
+
+/// How many more commits to walk once only excluded commits are left to walk,
+/// in case clock skew put an excluded commit behind one of its included descendants
+const SLOP: usize = 5;
+
+/// The commits reachable from `excluded`, as far as needed to tell which commits reachable
+/// from `included` are excluded: newest first, until nothing but excluded commits are left.
+/// Like git, this relies on dates (commits being newer than their parents), with some slop.
+fn uninteresting_commits(store: &impl ObjectStore, included: &[Hash], excluded: &[Hash]) -> CliResult<HashSet<Hash>> {
+    let mut uninteresting = HashSet::new();
+    if excluded.is_empty() {
+        return Ok(uninteresting);
+    }
+
+    let mut queued = HashSet::new();
+    // commits in the queue that are (so far) included: the walk stops once there are none
+    let mut included_in_queue = HashSet::new();
+    let mut queue = BinaryHeap::new(); // (author date, insertion order)
+    let mut queued_commits = Vec::new();
+    let mut slop = SLOP;
+
+    // (commit, whether it's excluded)
+    let mut pending = excluded.iter().map(|hash| (hash.clone(), true))
+        .chain(included.iter().map(|hash| (hash.clone(), false)))
+        .collect::<Vec<_>>();
+
+    let already_queued = |hash: &Hash, is_excluded: bool, queued: &HashSet<Hash>, uninteresting: &HashSet<Hash>| match is_excluded {
+        true => uninteresting.contains(hash),
+        false => queued.contains(hash),
+    };
+
+    loop {
+        for (hash, is_excluded) in pending.drain(..) {
+            if already_queued(&hash, is_excluded, &queued, &uninteresting) {
+                continue;
+            }
+
+            // checked again once peeled (for tags given as starting points)
+            let (hash, commit_object) = read_commit(store, &hash)?;
+            if already_queued(&hash, is_excluded, &queued, &uninteresting) {
+                continue;
+            }
+
+            // a commit that was already queued as included is queued again once it turns
+            // out to be excluded, so that its parents are excluded too
+            queued.insert(hash.clone());
+            if is_excluded {
+                uninteresting.insert(hash.clone());
+                included_in_queue.remove(&hash);
+            } else {
+                included_in_queue.insert(hash.clone());
+            }
+
+            queue.push((commit_object.author.timestamp, Reverse(queued_commits.len())));
+            queued_commits.push((hash, commit_object.parent_hashes));
+        }
+
+        if included_in_queue.is_empty() {
+            if slop == 0 {
+                return Ok(uninteresting);
+            }
+            slop -= 1;
+        } else {
+            slop = SLOP;
+        }
+
+        let Some((_, Reverse(index))) = queue.pop() else {
+            return Ok(uninteresting);
+        };
+        let (hash, parents) = &queued_commits[index];
+        included_in_queue.remove(hash);
+        let is_excluded = uninteresting.contains(hash);
+
+        pending.extend(parents.iter().map(|parent| (parent.clone(), is_excluded)));
+    }
+}
+
+/// The best common ancestors of `a` and `b`: common ancestors that aren't
+/// ancestors of any other common ancestor (usually just one commit)
+pub fn merge_bases(store: &impl ObjectStore, a: &Hash, b: &Hash) -> CliResult<Vec<Hash>> {
+    let ancestors_of_a = ancestors(store, std::slice::from_ref(a))?;
+    let ancestors_of_b = ancestors(store, std::slice::from_ref(b))?;
+
+    let common = ancestors_of_a.iter()
+        .filter(|(hash, _)| ancestors_of_b.contains_key(hash))
+        .collect::<HashMap<_, _>>();
+
+    // All ancestors of a common ancestor are common ancestors too,
+    // so a common ancestor is redundant iff it's the parent of another one
+    let redundant = common.values()
+        .flat_map(|commit_object| commit_object.parent_hashes.iter())
+        .collect::<HashSet<_>>();
+
+    let mut bases = common.keys()
+        .filter(|hash| !redundant.contains(**hash))
+        .map(|hash| (*hash).clone())
+        .collect::<Vec<_>>();
+
+    bases.sort_by(|x, y| x.as_str().cmp(y.as_str()));
+    Ok(bases)
+}
+
+/// Whether `ancestor` is reachable from `descendant` by following parents
+/// (a commit counts as its own ancestor)
+pub fn is_ancestor(store: &impl ObjectStore, ancestor: &Hash, descendant: &Hash) -> CliResult<bool> {
+    Ok(ancestors(store, std::slice::from_ref(descendant))?.contains_key(ancestor))
+}
+
+/// Pop the newest commit, queue its parents; repeat
+/// (ties are broken first-come-first-served)
+fn chronological_walk(store: &impl ObjectStore, included: &[Hash], excluded: &HashSet<Hash>, options: &WalkOptions) -> CliResult<Vec<Hash>> {
+    let mut seen = HashSet::new();
+    let mut queue = BinaryHeap::new(); // (author date, insertion order)
+    let mut queued_commits = Vec::new();
+    let mut result = Vec::new();
+
+    let mut pending = included.to_vec();
+
+    loop {
+        for hash in pending.drain(..) {
+            let (hash, commit_object) = read_commit(store, &hash)?;
+
+            if !excluded.contains(&hash) && seen.insert(hash.clone()) {
+                queue.push((commit_object.author.timestamp, Reverse(queued_commits.len())));
+                queued_commits.push(Some((hash, commit_object.parent_hashes)));
+            }
+        }
+
+        if options.max_count.is_some_and(|max_count| result.len() >= max_count) {
+            return Ok(result);
+        }
+
+        let Some((_, Reverse(index))) = queue.pop() else {
+            return Ok(result);
+        };
+        let (hash, mut parents) = queued_commits[index].take()
+            .expect("Invariant violated: commit popped twice");
+
+        if options.first_parent {
+            parents.truncate(1);
+        }
+
+        result.push(hash);
+        pending = parents;
+    }
+}
+
+/// Every commit reachable from `included` (without passing through `excluded`),
+/// in order of discovery (depth-first)
+fn collect_commits(store: &impl ObjectStore, included: &[Hash], excluded: &HashSet<Hash>, first_parent: bool) -> CliResult<Vec<CommitNode>> {
+    let mut seen = HashSet::new();
+    let mut nodes = Vec::new();
+    let mut frontier = included.iter().rev().cloned().collect::<Vec<_>>();
+
+    while let Some(hash) = frontier.pop() {
+        let (hash, mut commit_object) = read_commit(store, &hash)?;
+
+        if excluded.contains(&hash) || !seen.insert(hash.clone()) {
+            continue;
+        }
+
+        if first_parent {
+            commit_object.parent_hashes.truncate(1);
+        }
+
+        frontier.extend(commit_object.parent_hashes.iter().rev().cloned());
+        nodes.push(CommitNode {
+            hash,
+            timestamp: commit_object.author.timestamp,
+            parents: commit_object.parent_hashes,
+        });
+    }
+
+    Ok(nodes)
+}
+
+/// Order `nodes` so that every commit comes before its parents (Kahn's algorithm).
+/// Among commits that are ready, `Date` picks the newest, while `Topological`
+/// continues down the line of history it's already on.
+fn topological_sort(nodes: Vec<CommitNode>, order: WalkOrder) -> Vec<Hash> {
+    let index_of = nodes.iter()
+        .enumerate()
+        .map(|(index, node)| (node.hash.clone(), index))
+        .collect::<HashMap<_, _>>();
+
+    let parent_indices = nodes.iter()
+        .map(|node| node.parents.iter().filter_map(|parent| index_of.get(parent).copied()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let mut remaining_children = vec![0usize; nodes.len()];
+    for parent_index in parent_indices.iter().flatten() {
+        remaining_children[*parent_index] += 1;
+    }
+
+    let mut result = Vec::with_capacity(nodes.len());
+
+    if order == WalkOrder::Date {
+        let mut ready = (0..nodes.len())
+            .filter(|index| remaining_children[*index] == 0)
+            .map(|index| (nodes[index].timestamp, Reverse(index)))
+            .collect::<BinaryHeap<_>>();
+
+        while let Some((_, Reverse(index))) = ready.pop() {
+            result.push(nodes[index].hash.clone());
+
+            for parent_index in parent_indices[index].iter() {
+                remaining_children[*parent_index] -= 1;
+                if remaining_children[*parent_index] == 0 {
+                    ready.push((nodes[*parent_index].timestamp, Reverse(*parent_index)));
+                }
+            }
+        }
+    } else {
+        // Tips: newest on top of the stack (ties: first discovered on top)
+        let mut ready = (0..nodes.len())
+            .filter(|index| remaining_children[*index] == 0)
+            .collect::<Vec<_>>();
+        ready.sort_by_key(|index| (nodes[*index].timestamp, Reverse(*index)));
+
+        while let Some(index) = ready.pop() {
+            result.push(nodes[index].hash.clone());
+
+            // push in reverse, so that the first parent is visited next
+            for parent_index in parent_indices[index].iter().rev() {
+                remaining_children[*parent_index] -= 1;
+                if remaining_children[*parent_index] == 0 {
+                    ready.push(*parent_index);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Every tree and blob reachable from the trees of `commits`, but not from the trees
+/// of the boundary commits (excluded parents of `commits`), with its path (empty for root trees).
+/// Objects are listed once, in the order they're found (commit by commit, depth-first).
+pub fn walk_objects(store: &impl ObjectStore, commits: &[Hash], excluded: &[Hash]) -> CliResult<Vec<(Hash, String)>> {
+    let mut seen = HashSet::new();
+    let mut objects = Vec::new();
+
+    let uninteresting = uninteresting_commits(store, commits, excluded)?;
+    let commit_objects = commits.iter()
+        .map(|hash| read_commit(store, hash))
+        .collect::<CliResult<Vec<_>>>()?;
+
+    let boundary = commit_objects.iter()
+        .flat_map(|(_, commit_object)| commit_object.parent_hashes.iter())
+        .filter(|parent| uninteresting.contains(*parent))
+        .collect::<HashSet<_>>();
+
+    for hash in boundary {
+        let (_, commit_object) = read_commit(store, hash)?;
+        walk_tree(store, &commit_object.tree_hash, "", &mut seen, None)?;
+    }
+
+    for (_, commit_object) in commit_objects.iter() {
+        walk_tree(store, &commit_object.tree_hash, "", &mut seen, Some(&mut objects))?;
+    }
+
+    Ok(objects)
+}
+
+/// Every object reachable from `roots` (which can be of any type), listed once, with its path
+/// (empty for commits, tags, root trees, and blobs that are roots themselves).
+/// Commits come first, newest first; then tags, trees and blobs, in the order they're found.
+pub fn reachable_objects(store: &impl ObjectStore, roots: &[Hash]) -> CliResult<Vec<(Hash, String)>> {
+    let mut seen = HashSet::new();
+    let mut commits = Vec::new();
+    let mut objects = Vec::new();
+
+    let mut pending = roots.iter().rev().cloned().collect::<Vec<_>>();
+    while let Some(hash) = pending.pop() {
+        if seen.contains(&hash) {
+            continue;
+        }
+
+        match store.read_header(&hash)?.tipe {
+            ObjectType::Commit => commits.push(hash),
+            ObjectType::Tree => walk_tree(store, &hash, "", &mut seen, Some(&mut objects))?,
+            ObjectType::Blob => {
+                seen.insert(hash.clone());
+                objects.push((hash, String::new()));
+            },
+            ObjectType::Tag => {
+                let Object::Tag(tag_object) = store.read(&hash)? else {
+                    return Err(format!("Object {} is not a tag", hash.as_str()));
+                };
+                seen.insert(hash.clone());
+                objects.push((hash, String::new()));
+                pending.push(tag_object.target_hash);
+            },
+        }
+    }
+
+    let commits = walk_commits(store, &commits, &[], &WalkOptions::default())?;
+    let mut commit_objects = Vec::new();
+    for hash in commits.iter() {
+        let (_, commit_object) = read_commit(store, hash)?;
+        walk_tree(store, &commit_object.tree_hash, "", &mut seen, Some(&mut commit_objects))?;
+    }
+
+    Ok(
+        commits.into_iter()
+            .map(|hash| (hash, String::new()))
+            .chain(objects)
+            .chain(commit_objects)
+            .collect()
+    )
+}
+
+/// Add the unseen objects of the tree `hash` (including itself) to `seen`, and to `output` if given
+fn walk_tree(store: &impl ObjectStore, hash: &Hash, path: &str, seen: &mut HashSet<Hash>, mut output: Option<&mut Vec<(Hash, String)>>) -> CliResult<()> {
+    if !seen.insert(hash.clone()) {
+        return Ok(()); // so is everything below it
+    }
+
+    if let Some(output) = output.as_mut() {
+        output.push((hash.clone(), String::from(path)));
+    }
+
+    let Object::Tree(entries) = store.read(hash)? else {
+        return Err(format!("Object {} is not a tree", hash.as_str()));
+    };
+
+    for entry in entries.iter() {
+        let entry_path = if path.is_empty() { String::from(entry.filename()) } else { format!("{path}/{}", entry.filename()) };
+
+        match entry.object_type() {
+            Some(ObjectType::Tree) => walk_tree(store, entry.hash(), &entry_path, seen, output.as_deref_mut())?,
+            Some(_) if seen.insert(entry.hash().clone()) => {
+                if let Some(output) = output.as_mut() {
+                    output.push((entry.hash().clone(), entry_path));
+                }
+            },
+            Some(_) => (), // blob already seen
+            None => (), // submodule
+        }
+    }
+
+    Ok(())
+}
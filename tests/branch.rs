@@ -0,0 +1,220 @@
+mod helpers;
+
+use helpers::*;
+
+fn branch(dir: &tempdir::TempDir, args: &[&str]) -> std::process::Output {
+    mush!(dir)
+        .arg("branch")
+        .args(args)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn create_and_list() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, .. } = create_history(&dir, HistoryOptions::default());
+
+    stdout(branch(&dir, &["topic"]));
+    stdout(branch(&dir, &["old", "HEAD~"]));
+    stdout(branch(&dir, &["nested/name", &root[..8]]));
+
+    assert_file_contents(&dir.path().join(".mush/refs/heads/topic"), &format!("{child}\n"));
+    assert_file_contents(&dir.path().join(".mush/refs/heads/old"), &format!("{root}\n"));
+    assert_file_contents(&dir.path().join(".mush/refs/heads/nested/name"), &format!("{root}\n"));
+
+    assert_eq!("* main\n  nested/name\n  old\n  topic\n", stdout(branch(&dir, &[])));
+    assert_eq!(stdout(branch(&dir, &[])), stdout(branch(&dir, &["--list"])));
+    assert_eq!("main\n", stdout(branch(&dir, &["--show-current"])));
+
+    let reflog = stdout(mush!(dir).args(["reflog", "show", "old"]).output().unwrap());
+    assert_eq!(format!("{} old@{{0}}: branch: Created from HEAD~\n", &root[..7]), reflog);
+}
+
+#[test]
+fn create_errors() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, .. } = create_history(&dir, HistoryOptions::default());
+    let tree = write_tree(&dir);
+
+    stdout(branch(&dir, &["topic", &root]));
+
+    let output = branch(&dir, &["topic"]);
+    assert!(!output.status.success());
+    assert_eq!("A branch named `topic` already exists\n", String::from_utf8(output.stderr).unwrap());
+    assert_file_contents(&dir.path().join(".mush/refs/heads/topic"), &format!("{root}\n"));
+
+    // `-f` overwrites
+    stdout(branch(&dir, &["-f", "topic"]));
+    assert_eq!(
+        stdout(mush!(dir).args(["rev-parse", "main"]).output().unwrap()),
+        stdout(mush!(dir).args(["rev-parse", "topic"]).output().unwrap())
+    );
+
+    for args in [vec!["bad..name"], vec!["HEAD"], vec!["x", &tree], vec!["x", "nope"]] {
+        assert!(!branch(&dir, &args).status.success(), "{args:?}");
+    }
+
+    assert!(!dir.path().join(".mush/refs/heads/x").exists());
+}
+
+#[test]
+fn delete() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, .. } = create_history(&dir, HistoryOptions::default());
+
+    stdout(branch(&dir, &["merged", &root]));
+
+    // `unmerged` points to a commit that isn't an ancestor of HEAD
+    let tree = write_tree(&dir);
+    let side = commit_tree(&dir, &tree, &[&root], "side\n");
+    stdout(branch(&dir, &["unmerged", &side]));
+
+    assert_eq!(
+        format!("Deleted branch merged (was {}).\n", &root[..7]),
+        stdout(branch(&dir, &["-d", "merged"]))
+    );
+    assert!(!dir.path().join(".mush/refs/heads/merged").exists());
+    assert!(!dir.path().join(".mush/logs/refs/heads/merged").exists());
+
+    let output = branch(&dir, &["-d", "unmerged"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("The branch `unmerged` is not fully merged."));
+
+    stdout(branch(&dir, &["-D", "unmerged"]));
+    assert!(!dir.path().join(".mush/refs/heads/unmerged").exists());
+
+    let output = branch(&dir, &["-D", "main"]);
+    assert!(!output.status.success());
+    assert_eq!("Cannot delete branch `main`: it's the current branch\n", String::from_utf8(output.stderr).unwrap());
+    assert_file_contents(&dir.path().join(".mush/refs/heads/main"), &format!("{child}\n"));
+
+    assert!(!branch(&dir, &["-d", "nope"]).status.success());
+}
+
+#[test]
+fn rename() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, .. } = create_history(&dir, HistoryOptions::default());
+
+    stdout(branch(&dir, &["topic", &root]));
+
+    // rename the current branch
+    stdout(branch(&dir, &["-m", "trunk"]));
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &"ref: refs/heads/trunk\n");
+    assert_file_contents(&dir.path().join(".mush/refs/heads/trunk"), &format!("{child}\n"));
+    assert!(!dir.path().join(".mush/refs/heads/main").exists());
+    assert!(!dir.path().join(".mush/logs/refs/heads/main").exists());
+
+    let reflog = stdout(mush!(dir).args(["reflog", "show", "trunk"]).output().unwrap());
+    assert_eq!(
+        format!(
+            "{} trunk@{{0}}: branch: renamed refs/heads/main to refs/heads/trunk\n{} trunk@{{1}}: update-ref\n",
+            &child[..7],
+            &child[..7],
+        ),
+        reflog
+    );
+
+    // rename another branch
+    let output = branch(&dir, &["-m", "topic", "trunk"]);
+    assert!(!output.status.success());
+    assert_eq!("A branch named `trunk` already exists\n", String::from_utf8(output.stderr).unwrap());
+
+    stdout(branch(&dir, &["-m", "topic", "feature/topic"]));
+    assert_file_contents(&dir.path().join(".mush/refs/heads/feature/topic"), &format!("{root}\n"));
+    assert_eq!("  feature/topic\n* trunk\n", stdout(branch(&dir, &[])));
+
+    // force
+    let output = branch(&dir, &["-M", "feature/topic", "trunk"]);
+    assert!(!output.status.success());
+    assert_eq!("Cannot force update the current branch `trunk`\n", String::from_utf8(output.stderr).unwrap());
+
+    stdout(branch(&dir, &["other"]));
+    stdout(branch(&dir, &["-M", "feature/topic", "other"]));
+    assert_file_contents(&dir.path().join(".mush/refs/heads/other"), &format!("{root}\n"));
+    assert_eq!("  other\n* trunk\n", stdout(branch(&dir, &[])));
+}
+
+#[test]
+fn rename_into_own_directory() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { child, .. } = create_history(&dir, HistoryOptions::default());
+    let reflog_messages = |name| {
+        stdout(mush!(dir).args(["reflog", "show", name]).output().unwrap())
+            .lines()
+            .map(|line| format!("{}\n", line.split_once(": ").unwrap().1))
+            .collect::<String>()
+    };
+    let original_reflog = reflog_messages("main");
+
+    // the current branch moves under a directory of the same name, and HEAD follows it
+    stdout(branch(&dir, &["-m", "main", "main/b"]));
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &"ref: refs/heads/main/b\n");
+    assert_eq!("* main/b\n", stdout(branch(&dir, &[])));
+    assert_eq!(format!("{child}\n"), stdout(mush!(dir).args(["rev-parse", "main/b"]).output().unwrap()));
+    assert_eq!(
+        format!("branch: renamed refs/heads/main to refs/heads/main/b\n{original_reflog}"),
+        reflog_messages("main/b")
+    );
+
+    // and back
+    stdout(branch(&dir, &["-m", "main/b", "main"]));
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &"ref: refs/heads/main\n");
+    assert_file_contents(&dir.path().join(".mush/refs/heads/main"), &format!("{child}\n"));
+    assert_eq!("* main\n", stdout(branch(&dir, &[])));
+    assert!(!dir.path().join(".mush/logs/refs/heads/main/b").exists());
+    assert_eq!(
+        format!(
+            "branch: renamed refs/heads/main/b to refs/heads/main\n\
+            branch: renamed refs/heads/main to refs/heads/main/b\n{original_reflog}"
+        ),
+        reflog_messages("main")
+    );
+}
+
+#[test]
+fn failed_rename_changes_nothing() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, .. } = create_history(&dir, HistoryOptions::default());
+
+    stdout(branch(&dir, &["a", &root]));
+    stdout(branch(&dir, &["c", &root]));
+    stdout(branch(&dir, &["x/y", &root]));
+    let reflog = || stdout(mush!(dir).args(["reflog", "show", "a"]).output().unwrap());
+    let original_reflog = reflog();
+
+    // a name that is a directory of an existing branch, or the other way around
+    for (new_name, conflict) in [("c/d", "refs/heads/c"), ("x", "refs/heads/x/y")] {
+        let output = branch(&dir, &["-M", "a", new_name]);
+        assert!(!output.status.success());
+        assert_eq!(
+            format!("Cannot rename `a` to `{new_name}`: `{conflict}` exists\n"),
+            String::from_utf8(output.stderr).unwrap()
+        );
+    }
+
+    // the new name is locked by another process
+    create_file_with_contents(&dir.path().join(".mush/refs/heads"), "new.lock", "");
+    let output = branch(&dir, &["-m", "a", "new"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("unable to create lock file"));
+    assert!(!dir.path().join(".mush/refs/heads/new").exists());
+    assert!(!dir.path().join(".mush/logs/refs/heads/new").exists());
+
+    assert_file_contents(&dir.path().join(".mush/refs/heads/a"), &format!("{root}\n"));
+    assert_eq!(original_reflog, reflog());
+}
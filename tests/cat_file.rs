@@ -171,6 +171,34 @@ fn pretty_blob() {
     });
 }
 
+#[test]
+fn pretty_corrupt_blob() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let hash = hash_object_write(&dir, "999.txt", "pedal\nstroke\nmush\n999\n");
+    let object_path = dir.path().join(".mush/objects").join(&hash[..2]).join(&hash[2..]);
+
+    [
+        ("blob 22\0pedal\nstroke\n", "expected 22 bytes, got 13"),
+        ("blob 22\0pedal\nstroke\nmush\n999\nand more", "more than the 22 bytes of its header"),
+        ("blob 22\0pedal\nstroke\nmush\n998\n", "its contents hash to"),
+    ].iter().for_each(|(stored, error)| {
+        let _ = std::fs::remove_file(&object_path);
+        std::fs::write(&object_path, miniz_oxide::deflate::compress_to_vec_zlib(stored.as_bytes(), 6)).unwrap();
+
+        let output = mush!(dir)
+                .arg("cat-file")
+                .arg("-p")
+                .arg(&hash)
+                .output()
+                .unwrap();
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().contains(error));
+    });
+}
+
 #[test]
 fn pretty_tree() {
     let dir = tempdir();
@@ -3,7 +3,7 @@ mod helpers;
 use std::{fs::File, process::Stdio};
 
 use helpers::*;
-use mush::hash::Hash;
+use mush::hash::ObjectFormat;
 
 #[test]
 fn one_commit_full_pipeline() {
@@ -105,7 +105,7 @@ fn one_commit_full_pipeline() {
 	// that. This way, the test can stay the same if the reading/writing
 	// code changes (which is possible but unlikely).
 
-	let commit_hash = Hash::try_from_str(String::from_utf8_lossy(output.stdout.as_slice()).trim())
+	let commit_hash = ObjectFormat::Sha1.parse_hash(String::from_utf8_lossy(output.stdout.as_slice()).trim())
 		.unwrap();
 
 	std::env::set_current_dir(dir.path()).unwrap();
@@ -0,0 +1,106 @@
+mod helpers;
+
+use helpers::*;
+
+fn for_each_ref(dir: &tempdir::TempDir, args: &[&str]) -> std::process::Output {
+    mush!(dir)
+        .arg("for-each-ref")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// `main` at `child`, `old` at `root`, and an annotated tag `v1` of `root`
+/// (with multi-line commit messages)
+fn create_refs(dir: &tempdir::TempDir) -> History {
+    create_history(dir, HistoryOptions {
+        messages: Some(("root\n\nroot body\n", "child subject\nsecond line\n")),
+        old_branch: true,
+        annotated_tag: true,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn default_format() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, tag, .. } = create_refs(&dir);
+    let tag = tag.unwrap();
+
+    assert_eq!(
+        format!("{child} commit\trefs/heads/main\n{root} commit\trefs/heads/old\n{tag} tag\trefs/tags/v1\n"),
+        stdout(for_each_ref(&dir, &[]))
+    );
+}
+
+#[test]
+fn format_fields() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, .. } = create_refs(&dir);
+
+    assert_eq!(
+        format!(
+            "main {} commit Bud Weiser <bud@wiser.org> [child subject second line] *\n\
+             old {} commit Bud Weiser <bud@wiser.org> [root]  \n\
+             v1 {} tag   [version one]  \n",
+            &child[..7],
+            &root[..7],
+            &stdout(mush!(dir).args(["rev-parse", "--short", "v1"]).output().unwrap()).trim_end(),
+        ),
+        stdout(for_each_ref(&dir, &["--format=%(refname:short) %(objectname:short) %(objecttype) %(authorname) %(authoremail) [%(subject)] %(HEAD)"]))
+    );
+
+    assert_eq!(
+        "v1 Bud Weiser 100%\n",
+        stdout(for_each_ref(&dir, &["--format=%(refname:short) %(taggername) 100%%", "refs/tags"]))
+    );
+    // `%xx` escapes are bytes, which can make up multi-byte characters (or not be UTF-8 at all)
+    assert_eq!(
+        "old\t\u{e9}\0\n",
+        stdout(for_each_ref(&dir, &["--format=%(refname:short)%09%c3%a9%00", "refs/heads/old"]))
+    );
+    assert_eq!(
+        b"old\xff\n".as_slice(),
+        for_each_ref(&dir, &["--format=%(refname:short)%ff", "refs/heads/old"]).stdout
+    );
+    // only two hex digits make an escape (`%+1` isn't the byte 1)
+    assert_eq!("old%+1\n", stdout(for_each_ref(&dir, &["--format=%(refname:short)%+1", "refs/heads/old"])));
+    assert_eq!("root body\n\n", stdout(for_each_ref(&dir, &["--format=%(body)", "refs/heads/old"])));
+    assert_eq!(
+        format!("{}\n", &child[..12]),
+        stdout(for_each_ref(&dir, &["--format=%(objectname:short=12)", "refs/heads/main"]))
+    );
+
+    for format in ["%(nope)", "%(refname:long)", "%(refname"] {
+        assert!(!for_each_ref(&dir, &["--format", format]).status.success(), "{format}");
+    }
+}
+
+#[test]
+fn patterns_sort_and_count() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    create_refs(&dir);
+    let format = "--format=%(refname)";
+
+    assert_eq!("refs/heads/main\nrefs/heads/old\n", stdout(for_each_ref(&dir, &[format, "refs/heads"])));
+    assert_eq!("refs/heads/main\nrefs/heads/old\n", stdout(for_each_ref(&dir, &[format, "refs/heads/"])));
+    assert_eq!("refs/heads/old\n", stdout(for_each_ref(&dir, &[format, "refs/heads/o*"])));
+    assert_eq!("refs/heads/main\nrefs/tags/v1\n", stdout(for_each_ref(&dir, &[format, "refs/*/main", "refs/tags"])));
+    assert_eq!("", stdout(for_each_ref(&dir, &[format, "refs/head"])));
+    assert_eq!("", stdout(for_each_ref(&dir, &[format, "refs/*"])));
+
+    assert_eq!("refs/tags/v1\nrefs/heads/old\nrefs/heads/main\n", stdout(for_each_ref(&dir, &[format, "--sort=-refname"])));
+    assert_eq!("refs/heads/main\nrefs/heads/old\n", stdout(for_each_ref(&dir, &[format, "--count=2"])));
+
+    // the last key is the primary key
+    assert_eq!(
+        "refs/heads/old\nrefs/heads/main\nrefs/tags/v1\n",
+        stdout(for_each_ref(&dir, &[format, "--sort=-refname", "--sort=objecttype"]))
+    );
+}
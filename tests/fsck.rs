@@ -0,0 +1,160 @@
+mod helpers;
+
+use helpers::*;
+
+/// A repository with one commit on `main`: `file` in a tree
+fn repo_with_commit(dir: &tempdir::TempDir) -> (String, String, String) {
+    mush_init_clean_repo(dir);
+    mush_config_user(dir);
+
+    let (blob, tree) = stage_file_and_write_tree(dir);
+    let commit = commit_tree(dir, &tree, &[], "First");
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &commit]).output().unwrap());
+
+    (blob, tree, commit)
+}
+
+#[test]
+fn clean_repository() {
+    let dir = tempdir();
+    repo_with_commit(&dir);
+
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+
+    // also once everything is packed
+    assert_output_success(&mush!(dir).arg("gc").output().unwrap());
+    assert!(loose_objects(&dir).is_empty());
+
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+}
+
+#[test]
+fn hash_mismatch() {
+    let dir = tempdir();
+    let (blob, _, _) = repo_with_commit(&dir);
+
+    // store another object under the blob's hash
+    let other = hash_object_write(&dir, "other", "other\n");
+    let dot_mush = dir.path().join(".mush");
+    std::fs::remove_file(dot_mush.join(object_path(&blob))).unwrap();
+    std::fs::copy(dot_mush.join(object_path(&other)), dot_mush.join(object_path(&blob))).unwrap();
+
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("hash mismatch for {blob}")), "{stderr}");
+    assert!(stderr.contains(&format!("contents hash to {other}")), "{stderr}");
+}
+
+#[test]
+fn missing_objects() {
+    let dir = tempdir();
+    let (blob, tree, _) = repo_with_commit(&dir);
+
+    std::fs::remove_file(dir.path().join(".mush").join(object_path(&blob))).unwrap();
+
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("broken link from tree {tree} to blob {blob}\nmissing blob {blob}\n"),
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("error: index entry `file`: invalid pointer {blob}")), "{stderr}");
+}
+
+#[test]
+fn invalid_refs() {
+    let dir = tempdir();
+    repo_with_commit(&dir);
+
+    let missing = "99622a960c9f3d0232df4d90149b666c11159b9e";
+    create_file_with_contents(&dir.path().join(".mush/refs/heads"), "broken", &format!("{missing}\n"));
+
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("refs/heads/broken: invalid pointer {missing}")), "{stderr}");
+}
+
+#[test]
+fn dangling_and_unreachable() {
+    let dir = tempdir();
+    let (_, tree, commit) = repo_with_commit(&dir);
+
+    let lone_blob = hash_object_write(&dir, "lone", "lone\n");
+    let orphan = commit_tree(&dir, &tree, &[&commit], "Orphan");
+
+    // the orphan commit is dangling, and the commit it points to is still reachable
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert_output_success(&output);
+    let mut expected = [format!("dangling blob {lone_blob}\n"), format!("dangling commit {orphan}\n")];
+    expected.sort_by_key(|line| line.split(' ').nth(2).unwrap().to_string());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected.concat());
+
+    let output = mush!(dir).args(["fsck", "--no-dangling"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+
+    // a commit only referenced by the dangling one is unreachable, but not dangling
+    assert_output_success(&mush!(dir).args(["update-ref", "-d", "refs/heads/main"]).output().unwrap());
+    std::fs::remove_dir_all(dir.path().join(".mush/logs")).unwrap();
+    std::fs::remove_file(dir.path().join(".mush/index")).unwrap();
+
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert_output_success(&output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(&format!("dangling commit {orphan}\n")), "{stdout}");
+    assert!(!stdout.contains(&commit), "{stdout}");
+
+    let output = mush!(dir).args(["fsck", "--unreachable"]).output().unwrap();
+    assert_output_success(&output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for line in [format!("unreachable commit {commit}\n"), format!("unreachable commit {orphan}\n"), format!("unreachable tree {tree}\n")] {
+        assert!(stdout.contains(&line), "{stdout}");
+    }
+}
+
+#[test]
+fn reflogs_keep_objects_reachable() {
+    let dir = tempdir();
+    let (_, tree, first) = repo_with_commit(&dir);
+
+    let second = commit_tree(&dir, &tree, &[], "Second");
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &second]).output().unwrap());
+
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+
+    let output = mush!(dir).args(["fsck", "--no-reflogs"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("dangling commit {first}\n"));
+}
+
+#[test]
+fn unreadable_reflogs_and_index() {
+    let dir = tempdir();
+    repo_with_commit(&dir);
+    let lone_blob = hash_object_write(&dir, "lone", "lone\n");
+
+    let reflog = dir.path().join(".mush/logs/refs/heads/main");
+    let mut contents = std::fs::read_to_string(&reflog).unwrap();
+    contents.push_str("not a reflog entry\n");
+    std::fs::write(&reflog, contents).unwrap();
+    std::fs::write(dir.path().join(".mush/index"), "not an index").unwrap();
+
+    // both are reported, and the rest of the checks still run
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("error: Failed to read reflog of `refs/heads/main`"), "{stderr}");
+    assert!(stderr.contains("error: Failed to read index"), "{stderr}");
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("dangling blob {lone_blob}\n"));
+}
@@ -0,0 +1,73 @@
+mod helpers;
+
+use helpers::*;
+
+const MONTH: std::time::Duration = std::time::Duration::from_secs(30 * 24 * 60 * 60);
+
+/// A repository with a branch and a tag on one commit, plus an old and a recent unreachable blob;
+/// returns the hashes of (commit, old, recent)
+fn setup(dir: &tempdir::TempDir) -> (String, String, String) {
+    mush_init_clean_repo(dir);
+    mush_config_user(dir);
+
+    let (_, tree) = stage_file_and_write_tree(dir);
+    let commit = commit_tree_at(dir, &tree, &[], "First", 1_000_000_000);
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &commit]).output().unwrap());
+    assert_output_success(&mush!(dir).args(["tag", "v1", &commit]).output().unwrap());
+
+    let old = hash_object_write(dir, "old", "old\n");
+    age_file(dir, &object_path(&old), MONTH);
+    let recent = hash_object_write(dir, "recent", "recent\n");
+
+    (commit, old, recent)
+}
+
+#[test]
+fn gc() {
+    let dir = tempdir();
+    let (commit, _, recent) = setup(&dir);
+
+    assert_output_success(&mush!(dir).arg("gc").output().unwrap());
+
+    // everything reachable is packed, refs too; only the recent unreachable blob is left loose
+    assert_eq!(packs(&dir).len(), 1);
+    assert_eq!(loose_objects(&dir), vec![recent.clone()]);
+    assert!(!dir.path().join(".mush/refs/heads/main").exists());
+    assert!(!dir.path().join(".mush/refs/tags/v1").exists());
+
+    let output = mush!(dir).args(["rev-list", "--objects", "main"]).output().unwrap();
+    assert_output_success(&output);
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with(&commit));
+
+    // a second run changes nothing
+    let pack = packs(&dir);
+    assert_output_success(&mush!(dir).arg("gc").output().unwrap());
+    assert_eq!(packs(&dir), pack);
+    assert_eq!(loose_objects(&dir), [recent]);
+}
+
+#[test]
+fn prune_expiry() {
+    for (args, config, left) in [
+        (vec!["--prune=now"], None, 0),
+        (vec!["--no-prune"], None, 2),
+        (vec![], Some("now"), 0),
+        (vec![], Some("never"), 2),
+        (vec!["--prune=2.weeks.ago"], Some("never"), 1),
+    ] {
+        let dir = tempdir();
+        setup(&dir);
+
+        if let Some(config) = config {
+            assert_output_success(&mush!(dir).args(["config", "gc.pruneExpire", config]).output().unwrap());
+        }
+
+        assert_output_success(&mush!(dir).arg("gc").args(&args).output().unwrap());
+        assert_eq!(loose_objects(&dir).len(), left, "{args:?} {config:?}");
+    }
+
+    let dir = tempdir();
+    setup(&dir);
+    let output = mush!(dir).args(["gc", "--prune=whenever"]).output().unwrap();
+    assert!(!output.status.success());
+}
@@ -0,0 +1,39 @@
+mod helpers;
+
+use helpers::*;
+
+use mush::hash::ObjectFormat;
+
+/// The two messages of the SHA-1 chosen-prefix collision from https://sha-mbles.github.io
+fn shambles() -> [Vec<u8>; 2] {
+    ["tests/data/sha-mbles-1.bin", "tests/data/sha-mbles-2.bin"]
+        .map(|filename| std::fs::read(filename).unwrap())
+}
+
+#[test]
+fn detect_sha1_collisions() {
+    let [first, second] = shambles();
+    assert_ne!(first, second);
+
+    for message in [first, second] {
+        let error = ObjectFormat::Sha1.try_digest(&message).unwrap_err();
+        assert_eq!(error, "SHA-1 appears to be part of a collision attack: 8ac60ba76f1999a1ab70223f225aefdc78d4ddc0");
+    }
+
+    // SHA-256 tells the two messages apart, so there is nothing to detect
+    let [first, second] = shambles().map(|message| ObjectFormat::Sha256.try_digest(&message).unwrap());
+    assert_ne!(first, second);
+}
+
+#[test]
+fn ordinary_input() {
+    assert_eq!(
+        hex::encode(ObjectFormat::Sha1.try_digest(b"hello world").unwrap()),
+        "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed",
+    );
+
+    // same object names as before collision detection
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    assert_eq!(hash_object_write(&dir, "file", "hello\n"), "ce013625030ba8dba906f756967f9e9ca394464a");
+}
@@ -185,3 +185,116 @@ fn write_stdin() {
         ]
     );
 }
+
+/// Contents spanning several of the chunks blobs are streamed in, and not valid UTF-8
+fn large_binary_contents() -> Vec<u8> {
+    (0..1_000_000u32).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+#[test]
+fn write_large_binary_file() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let contents = large_binary_contents();
+    create_file_with_byte_contents(dir.path(), "large.bin", &contents);
+
+    let stored = [format!("blob {}\0", contents.len()).as_bytes(), &contents].concat();
+    let expected_hash = hex::encode(mush::hash::ObjectFormat::Sha1.try_digest(&stored).unwrap());
+
+    let output = mush!(dir).args(["hash-object", "large.bin"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{expected_hash}\n"));
+    assert!(loose_objects(&dir).is_empty());
+
+    // writing it again is a no-op
+    for _ in 0..2 {
+        let output = mush!(dir).args(["hash-object", "-w", "large.bin"]).output().unwrap();
+        assert_output_success(&output);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{expected_hash}\n"));
+    }
+
+    // only the object is left in the objects directory (no temporary files)
+    assert_eq!(loose_objects(&dir), vec![expected_hash.clone()]);
+    let entries = std::fs::read_dir(dir.path().join(".mush/objects")).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name != "pack" && name != "info")
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec![String::from(&expected_hash[..2])]);
+
+    let output = mush!(dir).args(["cat-file", "-s", &expected_hash]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\n", contents.len()));
+
+    let output = mush!(dir).args(["cat-file", "-p", &expected_hash]).output().unwrap();
+    assert_output_success(&output);
+    assert!(output.stdout == contents);
+
+    // stdin doesn't have to be UTF-8 either
+    let mut hash_object_process = mush!(dir)
+        .args(["hash-object", "-w", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = hash_object_process.stdin.take().unwrap();
+    let stdin_contents = contents.clone();
+    std::thread::spawn(move || {
+        stdin.write_all(&stdin_contents).unwrap();
+    });
+
+    let output = hash_object_process.wait_with_output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{expected_hash}\n"));
+
+    // once packed, it is still printed whole
+    let output = mush!(dir).args(["pack-objects", ".mush/objects/pack/pack"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            child.stdin.take().unwrap().write_all(format!("{expected_hash}\n").as_bytes())?;
+            child.wait_with_output()
+        })
+        .unwrap();
+    assert_output_success(&output);
+    std::fs::remove_dir_all(dir.path().join(".mush/objects").join(&expected_hash[..2])).unwrap();
+
+    let output = mush!(dir).args(["cat-file", "-p", &expected_hash]).output().unwrap();
+    assert_output_success(&output);
+    assert!(output.stdout == contents);
+}
+
+/// The SHA-mbles messages collide as raw SHA-1 input (see `tests/hash.rs`), but as blobs
+/// the `blob 640\0` header shifts the colliding blocks off their block boundaries:
+/// they must be stored as two distinct objects, rather than refused
+#[test]
+fn write_collision_attack_files() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let mut hashes = Vec::new();
+    for filename in ["sha-mbles-1.bin", "sha-mbles-2.bin"] {
+        let contents = std::fs::read(format!("tests/data/{filename}")).unwrap();
+        create_file_with_byte_contents(dir.path(), filename, &contents);
+
+        let output = mush!(dir).args(["hash-object", "-w", filename]).output().unwrap();
+        assert_output_success(&output);
+        let hash = String::from_utf8(output.stdout).unwrap().trim().to_string();
+
+        let output = mush!(dir).args(["cat-file", "-p", &hash]).output().unwrap();
+        assert_output_success(&output);
+        assert!(output.stdout == contents);
+
+        assert_output_success(&mush!(dir).args(["update-index", "--add", &hash, filename]).output().unwrap());
+        hashes.push(hash);
+    }
+
+    assert_ne!(hashes[0], hashes[1]);
+    let tree = write_tree(&dir);
+
+    let mut expected_objects = [hashes.as_slice(), &[tree]].concat();
+    expected_objects.sort();
+    assert_eq!(loose_objects(&dir), expected_objects);
+}
@@ -78,3 +78,204 @@ pub fn create_dir(base_directory: &std::path::Path, dirname: &str) {
 pub fn assert_output_success(output: &std::process::Output) {
     assert!(output.status.success(), "stderr = ```{}```", String::from_utf8(output.stderr.clone()).unwrap());
 }
+
+/// The stdout of a successful command
+#[allow(dead_code)]
+pub fn stdout(output: std::process::Output) -> String {
+    assert_output_success(&output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Write `contents` to `filename`, store it as a blob, and return the hash
+#[allow(dead_code)]
+pub fn hash_object_write(dir: &tempdir::TempDir, filename: &str, contents: &str) -> String {
+    create_file_with_contents(dir.path(), filename, contents);
+
+    let output = mush!(dir)
+        .arg("hash-object")
+        .arg("-w")
+        .arg(filename)
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Write `contents` to `filename`, and stage it; returns the blob's hash
+#[allow(dead_code)]
+pub fn stage_file(dir: &tempdir::TempDir, filename: &str, contents: &str) -> String {
+    let hash = hash_object_write(dir, filename, contents);
+    assert_output_success(&mush!(dir).args(["update-index", "--add", &hash, filename]).output().unwrap());
+    hash
+}
+
+/// Stage `file` (containing `file\n`), and write the tree of the index;
+/// returns the (blob, tree) hashes
+#[allow(dead_code)]
+pub fn stage_file_and_write_tree(dir: &tempdir::TempDir) -> (String, String) {
+    let blob = stage_file(dir, "file", "file\n");
+    (blob, write_tree(dir))
+}
+
+/// Set `user.name` and `user.email`, which are required for anything that records an identity
+#[allow(dead_code)]
+pub fn mush_config_user(directory: &tempdir::TempDir) {
+    for (option, value) in [("user.name", "Bud Weiser"), ("user.email", "bud@wiser.org")] {
+        assert_output_success(
+            &mush!(directory)
+                .args(["config", option, value])
+                .output()
+                .unwrap()
+        );
+    }
+}
+
+/// Write a tree object for the current index, and return its hash
+#[allow(dead_code)]
+pub fn write_tree(directory: &tempdir::TempDir) -> String {
+    let output = mush!(directory)
+        .arg("write-tree")
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// The hash algorithm of the repository
+#[allow(dead_code)]
+fn object_format(directory: &tempdir::TempDir) -> src::hash::ObjectFormat {
+    std::fs::read_to_string(directory.path().join(".mush/config/extensions/objectFormat"))
+        .map(|name| src::hash::ObjectFormat::from_name(name.trim()).unwrap())
+        .unwrap_or(src::hash::ObjectFormat::Sha1)
+}
+
+/// Create a commit object (by Bud Weiser, dated now), and return its hash.
+/// `commit-tree` can't set parents (nor the date), so this goes through the library.
+#[allow(dead_code)]
+pub fn commit_tree(directory: &tempdir::TempDir, tree: &str, parents: &[&str], message: &str) -> String {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+    commit_tree_at(directory, tree, parents, message, now.as_secs() as i64)
+}
+
+/// Like `commit_tree`, but with a fixed author date (seconds since the epoch),
+/// so that tests can depend on the order of commits by date
+#[allow(dead_code)]
+pub fn commit_tree_at(directory: &tempdir::TempDir, tree: &str, parents: &[&str], message: &str, seconds: i64) -> String {
+    use src::object::store::ObjectStore;
+
+    let object_format = object_format(directory);
+    let commit_object = src::object::commit::CommitObject {
+        tree_hash: object_format.parse_hash(tree).unwrap(),
+        parent_hashes: parents.iter().map(|parent| object_format.parse_hash(parent).unwrap()).collect(),
+        author: src::object::commit::PersonTime::from_strings("Bud Weiser", "<bud@wiser.org>", &format!("{seconds} +0000")).unwrap(),
+        message: String::from(message),
+    };
+
+    let store = src::object::store::LooseObjectStore::new(directory.path().join(".mush/objects").to_str().unwrap(), object_format);
+    String::from(store.write(&commit_object.into()).unwrap().as_str())
+}
+
+/// Refs (and commit messages) for `create_history`; the default is just `main` (and HEAD)
+#[allow(dead_code)]
+#[derive(Default)]
+pub struct HistoryOptions<'a> {
+    /// Messages of the root and child commits, instead of `root\n` and `child\n`
+    pub messages: Option<(&'a str, &'a str)>,
+    /// Create the branch `old`, at `root`
+    pub old_branch: bool,
+    /// Create the lightweight tag `light`, of `child`
+    pub lightweight_tag: bool,
+    /// Create the annotated tag `v1` (message `version one`), of `root`
+    pub annotated_tag: bool,
+}
+
+#[allow(dead_code)]
+pub struct History {
+    /// The tree of both commits (that of the index)
+    pub tree: String,
+    pub root: String,
+    pub child: String,
+    /// The annotated tag object, if `annotated_tag` was requested
+    pub tag: Option<String>,
+}
+
+/// Commit `root`, and `child` on top of it, with `main` (and HEAD) at `child`.
+/// Also sets the user config.
+#[allow(dead_code)]
+pub fn create_history(directory: &tempdir::TempDir, options: HistoryOptions) -> History {
+    mush_config_user(directory);
+
+    let (root_message, child_message) = options.messages.unwrap_or(("root\n", "child\n"));
+    let tree = write_tree(directory);
+    let root = commit_tree(directory, &tree, &[], root_message);
+    let child = commit_tree(directory, &tree, &[&root], child_message);
+
+    let run = |args: &[&str]| stdout(mush!(directory).args(args).output().unwrap());
+    run(&["update-ref", "HEAD", &child]);
+
+    if options.old_branch {
+        run(&["branch", "old", &root]);
+    }
+    if options.lightweight_tag {
+        run(&["tag", "light", &child]);
+    }
+    let tag = options.annotated_tag.then(|| {
+        run(&["tag", "-m", "version one", "v1", &root]);
+        run(&["rev-parse", "v1"]).trim_end().to_string()
+    });
+
+    History { tree, root, child, tag }
+}
+
+/// Hashes of the loose objects in the repository, sorted
+#[allow(dead_code)]
+pub fn loose_objects(directory: &tempdir::TempDir) -> Vec<String> {
+    let mut hashes = std::fs::read_dir(directory.path().join(".mush/objects"))
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_name().len() == 2)
+        .flat_map(|entry| {
+            let prefix = entry.file_name().into_string().unwrap();
+            std::fs::read_dir(entry.path())
+                .unwrap()
+                .map(move |object| format!("{prefix}{}", object.unwrap().file_name().into_string().unwrap()))
+        })
+        .collect::<Vec<_>>();
+
+    hashes.sort();
+    hashes
+}
+
+/// Filenames of the `.pack` files in the repository, sorted
+#[allow(dead_code)]
+pub fn packs(directory: &tempdir::TempDir) -> Vec<String> {
+    let mut packs = std::fs::read_dir(directory.path().join(".mush/objects/pack"))
+        .map(|entries| {
+            entries.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .filter(|filename| filename.ends_with(".pack"))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    packs.sort();
+    packs
+}
+
+/// Path of the loose object `hash`, relative to `.mush`
+#[allow(dead_code)]
+pub fn object_path(hash: &str) -> String {
+    format!("objects/{}/{}", &hash[..2], &hash[2..])
+}
+
+/// Set the modification time of `.mush/<path>` to `age` ago
+#[allow(dead_code)]
+pub fn age_file(directory: &tempdir::TempDir, path: &str, age: std::time::Duration) {
+    std::fs::File::options()
+        .write(true)
+        .open(directory.path().join(".mush").join(path))
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() - age)
+        .unwrap();
+}
@@ -8,6 +8,8 @@ fn assert_dir_is_proper_clean_repo(repo_dir: &std::path::Path) {
         ".mush",
         ".mush/objects",
         ".mush/refs",
+        ".mush/refs/heads",
+        ".mush/refs/tags",
         ".mush/config",
     ];
 
@@ -27,7 +29,8 @@ fn assert_dir_is_proper_clean_repo(repo_dir: &std::path::Path) {
         );
     });
 
-    // TODO
+    assert_file_contents(&repo_dir.join(".mush/HEAD"), &"ref: refs/heads/main\n");
+    assert_file_contents(&repo_dir.join(".mush/config/extensions/objectFormat"), &"sha1");
 }
 
 #[test]
@@ -96,3 +99,77 @@ fn various_nested_dirs_init() {
         assert_dir_is_proper_clean_repo(&dir.path().join(init_dir));
     });
 }
+
+#[test]
+fn sha256_init() {
+    let dir = tempdir();
+
+    assert_output_success(&mush!(dir).args(["init", "--object-format=sha256"]).output().unwrap());
+    assert_file_contents(&dir.path().join(".mush/config/extensions/objectFormat"), &"sha256");
+    mush_config_user(&dir);
+
+    // same hash as `git hash-object` in a SHA-256 repository
+    let blob = hash_object_write(&dir, "file", "hello\n");
+    assert_eq!(blob, "2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4");
+
+    assert_output_success(&mush!(dir).args(["update-index", "--add", &blob, "file"]).output().unwrap());
+    let tree = write_tree(&dir);
+    assert_eq!(tree.len(), 64);
+    let commit = commit_tree(&dir, &tree, &[], "First");
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &commit]).output().unwrap());
+
+    let output = mush!(dir).args(["rev-parse", &commit[..10]]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{commit}\n"));
+
+    // SHA-1 hashes aren't object names here
+    let output = mush!(dir).args(["update-ref", "refs/heads/other", "99622a960c9f3d0232df4d90149b666c11159b9e"]).output().unwrap();
+    assert!(!output.status.success());
+
+    // everything still reads back once packed
+    assert_output_success(&mush!(dir).arg("gc").output().unwrap());
+    let output = mush!(dir).args(["rev-list", "--objects", "main"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{commit}\n{tree} \n{blob} file\n"));
+
+    let output = mush!(dir).arg("fsck").output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+}
+
+#[test]
+fn unknown_object_format() {
+    let dir = tempdir();
+
+    let output = mush!(dir).args(["init", "--object-format=md5"]).output().unwrap();
+    assert!(!output.status.success());
+
+    mush_init_clean_repo(&dir);
+    assert_output_success(&mush!(dir).args(["config", "extensions.objectFormat", "sha512"]).output().unwrap());
+
+    create_file_with_contents(dir.path(), "file", "contents\n");
+    let output = mush!(dir).args(["hash-object", "-w", "file"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Unknown object format"));
+
+    // `init` doesn't read the object format of the repository it's run in
+    assert_output_success(&mush!(dir).args(["init", "nested"]).output().unwrap());
+    assert_dir_is_proper_clean_repo(&dir.path().join("nested"));
+}
+
+#[test]
+fn unreadable_object_format() {
+    let dir = tempdir();
+    assert_output_success(&mush!(dir).args(["init", "--object-format=sha256"]).output().unwrap());
+
+    // a directory in place of the option can't be read (unlike a missing option, which means SHA-1)
+    let option_path = dir.path().join(".mush/config/extensions/objectFormat");
+    std::fs::remove_file(&option_path).unwrap();
+    std::fs::create_dir(&option_path).unwrap();
+
+    create_file_with_contents(dir.path(), "file", "contents\n");
+    let output = mush!(dir).args(["hash-object", "-w", "file"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Failed to read object format"));
+    assert!(loose_objects(&dir).is_empty());
+}
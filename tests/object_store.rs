@@ -0,0 +1,173 @@
+mod helpers;
+
+use std::borrow::Cow;
+use std::io::Read;
+
+use helpers::*;
+
+use mush::hash::{Hash, ObjectFormat};
+use mush::object::store::{LooseObjectStore, MemoryObjectStore, ObjectStore};
+use mush::object::{Object, ObjectType, TreeEntry};
+
+/// Write a blob, and a tree containing it, to `store`
+fn write_blob_and_tree(store: &impl ObjectStore) -> (Hash, Hash) {
+    let blob = store.write(&Object::Blob(Cow::Borrowed(b"contents\n"))).unwrap();
+    let tree = store.write(&Object::Tree(vec![TreeEntry::new(String::from("file"), 0o100644, blob.clone())])).unwrap();
+    (blob, tree)
+}
+
+#[test]
+fn memory_store() {
+    let store = MemoryObjectStore::new(ObjectFormat::Sha1);
+    assert!(store.hashes().unwrap().is_empty());
+
+    let (blob, tree) = write_blob_and_tree(&store);
+    assert_eq!(blob.as_str(), "12f00e90b6ef79117ce6e650416b8cf517099b78");
+
+    // writing an object again is a no-op
+    write_blob_and_tree(&store);
+    let mut expected = vec![blob.clone(), tree.clone()];
+    expected.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    assert!(store.hashes().unwrap() == expected);
+
+    assert!(store.has(&blob).unwrap());
+    assert!(store.read_raw(&blob).unwrap() == (ObjectType::Blob, b"contents\n".to_vec()));
+
+    let header = store.read_header(&tree).unwrap();
+    assert!(header.tipe == ObjectType::Tree);
+    assert_eq!(header.size, store.read_raw(&tree).unwrap().1.len());
+
+    match store.read(&tree).unwrap() {
+        Object::Tree(entries) => {
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].filename(), "file");
+            assert!(*entries[0].hash() == blob);
+        },
+        _ => panic!("`{}` should be a tree", tree.as_str()),
+    }
+
+    let missing = ObjectFormat::Sha1.parse_hash("99622a960c9f3d0232df4d90149b666c11159b9e").unwrap();
+    assert!(!store.has(&missing).unwrap());
+    assert!(store.read_raw(&missing).is_err_and(|msg| msg.contains("not in the store")));
+}
+
+#[test]
+fn loose_store_outside_of_cwd() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let store = LooseObjectStore::new(dir.path().join(".mush/objects").to_str().unwrap(), ObjectFormat::Sha1);
+    let (blob, tree) = write_blob_and_tree(&store);
+
+    // the same objects as in memory
+    let memory = MemoryObjectStore::new(ObjectFormat::Sha1);
+    write_blob_and_tree(&memory);
+    assert!(store.hashes().unwrap() == memory.hashes().unwrap());
+    assert!(store.read_raw(&tree).unwrap() == memory.read_raw(&tree).unwrap());
+    assert!(store.read_header(&tree).unwrap().size == memory.read_header(&tree).unwrap().size);
+
+    // objects written by the library are seen by the command, and vice versa
+    let output = mush!(dir).args(["cat-file", "-p", blob.as_str()]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "contents\n");
+
+    let other = ObjectFormat::Sha1.parse_hash(&hash_object_write(&dir, "other", "other\n")).unwrap();
+    assert!(store.has(&other).unwrap());
+    assert!(store.read_raw(&other).unwrap() == (ObjectType::Blob, b"other\n".to_vec()));
+
+    store.delete(&other).unwrap();
+    assert!(!store.has(&other).unwrap());
+    assert!(!mush!(dir).args(["cat-file", "-p", other.as_str()]).output().unwrap().status.success());
+}
+
+#[test]
+fn loose_store_writes_each_object_once() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let store = LooseObjectStore::new(dir.path().join(".mush/objects").to_str().unwrap(), ObjectFormat::Sha1);
+    let (blob, _) = write_blob_and_tree(&store);
+
+    // an object that is already there is left alone
+    let old = std::time::Duration::from_secs(24 * 60 * 60);
+    age_file(&dir, &blob.path(), old);
+    let modified = || std::fs::metadata(dir.path().join(".mush").join(blob.path())).unwrap().modified().unwrap();
+    let before = modified();
+    write_blob_and_tree(&store);
+    assert_eq!(modified(), before);
+
+    // and nothing but objects is left in the store
+    let mut expected = store.hashes().unwrap().iter().map(|hash| String::from(hash.as_str())).collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(loose_objects(&dir), expected);
+    assert!(std::fs::read_dir(dir.path().join(".mush/objects")).unwrap()
+        .all(|entry| !entry.unwrap().file_name().to_string_lossy().starts_with("tmp_obj")));
+}
+
+#[test]
+fn stores_have_their_own_object_format() {
+    // independent of the repository (if any) in the current directory
+    let memory = MemoryObjectStore::new(ObjectFormat::Sha256);
+    let blob = memory.write(&Object::Blob(Cow::Borrowed(b"hello\n"))).unwrap();
+    assert_eq!(blob.as_str(), "2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4");
+
+    let dir = tempdir();
+    assert_output_success(&mush!(dir).args(["init", "--object-format=sha256"]).output().unwrap());
+
+    let store = LooseObjectStore::new(dir.path().join(".mush/objects").to_str().unwrap(), ObjectFormat::Sha256);
+    let (blob, tree) = write_blob_and_tree(&store);
+    assert_eq!(blob.len(), 64);
+    assert!(store.hashes().unwrap().len() == 2);
+
+    // trees are parsed with the store's hash size
+    match store.read(&tree).unwrap() {
+        Object::Tree(entries) => assert!(entries.len() == 1 && *entries[0].hash() == blob),
+        _ => panic!("`{}` should be a tree", tree.as_str()),
+    }
+
+    // so are commits, whose hashes are text
+    let contents = format!("tree\t{}\nauthor\tBud Weiser\t<bud@wiser.org>\t0 +0000\n\nmessage\n", tree.as_str());
+    let commit = store.write(&Object::from_contents(ObjectType::Commit, contents.into_bytes(), ObjectFormat::Sha256).unwrap()).unwrap();
+    match store.read(&commit).unwrap() {
+        Object::Commit(commit_object) => assert!(commit_object.tree_hash == tree),
+        _ => panic!("`{}` should be a commit", commit.as_str()),
+    }
+
+    let output = mush!(dir).args(["cat-file", "-p", blob.as_str()]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "contents\n");
+}
+
+#[test]
+fn repository_store_reads_packs() {
+    use mush::object::pack::write::{write_pack, PackOptions};
+    use mush::object::store::RepositoryObjectStore;
+
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let store = RepositoryObjectStore::new(dir.path().join(".mush/objects").to_str().unwrap(), ObjectFormat::Sha1);
+    let (blob, tree) = write_blob_and_tree(&store);
+    assert!(store.loose().has(&blob).unwrap());
+
+    let objects = [(tree.clone(), String::new()), (blob.clone(), String::from("file"))];
+    let base_name = format!("{}/pack", store.pack_directory());
+    write_pack(&store, &objects, &base_name, &PackOptions::default()).unwrap();
+    store.loose().delete(&blob).unwrap();
+    store.loose().delete(&tree).unwrap();
+
+    // the objects are only in the pack now
+    assert!(store.loose().hashes().unwrap().is_empty());
+    assert!(store.has(&blob).unwrap() && store.is_packed(&blob).unwrap());
+    assert!(store.read_raw(&blob).unwrap() == (ObjectType::Blob, b"contents\n".to_vec()));
+    assert!(store.read_header(&tree).unwrap().tipe == ObjectType::Tree);
+    assert!(store.find_by_prefix(&blob[..7]).unwrap() == vec![blob.clone()]);
+
+    let mut expected = vec![blob.clone(), tree.clone()];
+    expected.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    assert!(store.hashes().unwrap() == expected);
+
+    let mut contents = Vec::new();
+    store.open(&blob).unwrap().1.read_to_end(&mut contents).unwrap();
+    assert_eq!(contents, b"contents\n");
+}
@@ -0,0 +1,178 @@
+mod helpers;
+
+use std::io::Write;
+
+use helpers::*;
+
+fn pack_objects(dir: &tempdir::TempDir, args: &[&str], input: &str) -> std::process::Output {
+    let mut child = mush!(dir)
+        .arg("pack-objects")
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Remove every loose object, so that only packed objects are left
+fn remove_loose_objects(dir: &tempdir::TempDir) {
+    for entry in std::fs::read_dir(dir.path().join(".mush/objects")).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name().len() == 2 {
+            std::fs::remove_dir_all(entry.path()).unwrap();
+        }
+    }
+}
+
+/// A long file, with line `edited_line` changed in version `version`
+fn file_version(version: usize, edited_line: usize) -> String {
+    (0..500)
+        .map(|line| match line == edited_line {
+            true => format!("line {line}, version {version}\n"),
+            false => format!("line {line}\n"),
+        })
+        .collect()
+}
+
+#[test]
+fn pack_history_with_deltas() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let mut commit = None;
+    let mut blobs = Vec::new();
+    for version in 0..20 {
+        blobs.push(stage_file(&dir, "file.txt", &file_version(version, version * 10)));
+        stage_file(&dir, "other.txt", &format!("version {version}\n"));
+
+        let tree = write_tree(&dir);
+        let parents = commit.iter().map(String::as_str).collect::<Vec<_>>();
+        commit = Some(commit_tree_at(&dir, &tree, &parents, &format!("Version {version}"), 1_000_000_000 + version as i64));
+    }
+    let commit = commit.unwrap();
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &commit]).output().unwrap());
+
+    let rev_list_before = mush!(dir).args(["rev-list", "--objects", "main"]).output().unwrap();
+    assert_output_success(&rev_list_before);
+
+    let output = pack_objects(&dir, &["--revs", ".mush/objects/pack/pack"], "main\n");
+    assert_output_success(&output);
+
+    let checksum = String::from_utf8(output.stdout).unwrap();
+    let checksum = checksum.trim();
+    assert_eq!(checksum.len(), 40);
+
+    let pack_filename = dir.path().join(format!(".mush/objects/pack/pack-{checksum}.pack"));
+    assert_file_exists(&pack_filename);
+    assert_file_exists(&dir.path().join(format!(".mush/objects/pack/pack-{checksum}.idx")));
+
+    // 20 versions of a ~4.5KB file, stored as one base and small deltas
+    let pack_size = std::fs::metadata(&pack_filename).unwrap().len();
+    assert!(pack_size < 8_000, "pack is {pack_size} bytes");
+
+    remove_loose_objects(&dir);
+
+    let rev_list_after = mush!(dir).args(["rev-list", "--objects", "main"]).output().unwrap();
+    assert_output_success(&rev_list_after);
+    assert_eq!(rev_list_before.stdout, rev_list_after.stdout);
+
+    for (version, blob) in blobs.iter().enumerate() {
+        let output = mush!(dir).args(["cat-file", "-p", blob]).output().unwrap();
+        assert_output_success(&output);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), file_version(version, version * 10));
+    }
+}
+
+#[test]
+fn window_and_depth() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let list = (0..10)
+        .map(|version| format!("{} file.txt\n", hash_object_write(&dir, "file.txt", &file_version(version, 250))))
+        .collect::<String>();
+
+    let pack_size = |args: &[&str]| {
+        let output = pack_objects(&dir, args, &list);
+        assert_output_success(&output);
+
+        let checksum = String::from_utf8(output.stdout).unwrap();
+        let pack_filename = format!("{}-{}.pack", args.last().unwrap(), checksum.trim());
+        std::fs::metadata(dir.path().join(pack_filename)).unwrap().len()
+    };
+
+    let with_deltas = pack_size(&["out/default"]);
+    let without_window = pack_size(&["--window", "0", "out/no-window"]);
+    let without_depth = pack_size(&["--depth", "0", "out/no-depth"]);
+
+    assert!(with_deltas * 2 < without_window, "{with_deltas} vs {without_window}");
+    assert_eq!(without_window, without_depth);
+}
+
+#[test]
+fn big_file_threshold() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let blobs = (0..10)
+        .map(|version| hash_object_write(&dir, "file.txt", &file_version(version, 250)))
+        .collect::<Vec<_>>();
+    let list = blobs.iter().map(|blob| format!("{blob} file.txt\n")).collect::<String>();
+
+    let pack_size = |args: &[&str]| {
+        let output = pack_objects(&dir, args, &list);
+        assert_output_success(&output);
+
+        let checksum = String::from_utf8(output.stdout).unwrap();
+        let pack_filename = format!("{}-{}.pack", args.last().unwrap(), checksum.trim());
+        std::fs::metadata(dir.path().join(pack_filename)).unwrap().len()
+    };
+
+    let without_window = pack_size(&["--window", "0", "out/no-window"]);
+
+    // every version is above the threshold, so none is deltified
+    assert_output_success(&mush!(dir).args(["config", "core.bigFileThreshold", "1k"]).output().unwrap());
+    let streamed = pack_size(&[".mush/objects/pack/pack"]);
+    assert_eq!(streamed, without_window);
+
+    remove_loose_objects(&dir);
+
+    for (version, blob) in blobs.iter().enumerate() {
+        let output = mush!(dir).args(["cat-file", "-p", blob]).output().unwrap();
+        assert_output_success(&output);
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), file_version(version, 250));
+    }
+
+    assert_output_success(&mush!(dir).args(["config", "core.bigFileThreshold", "lots"]).output().unwrap());
+    let output = pack_objects(&dir, &["out/bad"], &list);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Bad core.bigFileThreshold: `lots`"));
+}
+
+#[test]
+fn bad_input() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let hash = hash_object_write(&dir, "file", "contents\n");
+
+    for (input, message) in [
+        (format!("{hash}\nnot-a-hash\n"), "Bad object name on line 2: `not-a-hash`"),
+        (String::from("99622a960c9f3d0232df4d90149b666c11159b9e\n"), "Failed to get object header"),
+    ] {
+        let output = pack_objects(&dir, &["pack"], &input);
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().contains(message));
+    }
+
+    let output = pack_objects(&dir, &["--revs", "pack"], "no-such-branch\n");
+    assert!(!output.status.success());
+
+    // nothing was written
+    assert!(std::fs::read_dir(dir.path()).unwrap().all(|entry| !entry.unwrap().file_name().to_str().unwrap().starts_with("pack")));
+}
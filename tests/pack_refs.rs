@@ -0,0 +1,122 @@
+mod helpers;
+
+use helpers::*;
+
+fn run(dir: &tempdir::TempDir, args: &[&str]) -> String {
+    stdout(mush!(dir).args(args).output().unwrap())
+}
+
+/// `main` (and HEAD) at `child`, `old` at `root`,
+/// a lightweight tag `light` of `child`, and an annotated tag `v1` of `root`
+fn create_refs(dir: &tempdir::TempDir) -> History {
+    create_history(dir, HistoryOptions { old_branch: true, lightweight_tag: true, annotated_tag: true, ..Default::default() })
+}
+
+#[test]
+fn pack_tags() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, tag, .. } = create_refs(&dir);
+    let tag = tag.unwrap();
+    let listing = run(&dir, &["show-ref", "-d"]);
+
+    run(&dir, &["pack-refs"]);
+
+    assert_file_contents(
+        &dir.path().join(".mush/packed-refs"),
+        &format!("# pack-refs with: peeled fully-peeled sorted \n{child} refs/tags/light\n{tag} refs/tags/v1\n^{root}\n")
+    );
+    assert!(!dir.path().join(".mush/refs/tags/v1").exists());
+    assert!(!dir.path().join(".mush/refs/tags/light").exists());
+    assert!(dir.path().join(".mush/refs/heads/main").exists());
+
+    // packed refs are still visible everywhere
+    assert_eq!(listing, run(&dir, &["show-ref", "-d"]));
+    assert_eq!(format!("{root}\n"), run(&dir, &["rev-parse", "v1^{commit}"]));
+    assert_eq!("light\nv1\n", run(&dir, &["tag"]));
+    assert_eq!("refs/tags/light\nrefs/tags/v1\n", run(&dir, &["for-each-ref", "--format=%(refname)", "refs/tags"]));
+}
+
+#[test]
+fn pack_all_and_no_prune() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, tag, .. } = create_refs(&dir);
+    let tag = tag.unwrap();
+
+    run(&dir, &["pack-refs", "--all", "--no-prune"]);
+
+    assert_file_contents(
+        &dir.path().join(".mush/packed-refs"),
+        &format!(
+            "# pack-refs with: peeled fully-peeled sorted \n\
+             {child} refs/heads/main\n{root} refs/heads/old\n{child} refs/tags/light\n{tag} refs/tags/v1\n^{root}\n"
+        )
+    );
+    assert!(dir.path().join(".mush/refs/heads/old").exists());
+
+    // HEAD is symbolic, and never packed
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &"ref: refs/heads/main\n");
+
+    run(&dir, &["pack-refs", "--all"]);
+    assert!(!dir.path().join(".mush/refs/heads/old").exists());
+    assert!(!dir.path().join(".mush/refs/heads/main").exists());
+    assert_eq!(format!("{child}\n"), run(&dir, &["rev-parse", "HEAD"]));
+    assert_eq!("* main\n  old\n", stdout(mush!(dir).args(["branch"]).env("NO_COLOR", "1").output().unwrap()));
+}
+
+#[test]
+fn update_and_delete_packed() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, .. } = create_refs(&dir);
+
+    run(&dir, &["pack-refs", "--all"]);
+
+    // an update writes a loose ref, which shadows the packed one
+    run(&dir, &["update-ref", "refs/heads/old", &child, &root]);
+    assert_file_contents(&dir.path().join(".mush/refs/heads/old"), &format!("{child}\n"));
+    assert_eq!(format!("{child}\n"), run(&dir, &["rev-parse", "old"]));
+
+    // deleting a ref in both places removes it from both
+    run(&dir, &["update-ref", "-d", "refs/heads/old", &child]);
+    assert!(!dir.path().join(".mush/refs/heads/old").exists());
+    assert!(!mush!(dir).args(["rev-parse", "--verify", "-q", "old"]).output().unwrap().status.success());
+    assert!(!std::fs::read_to_string(dir.path().join(".mush/packed-refs")).unwrap().contains("refs/heads/old"));
+
+    // deleting a packed-only ref
+    run(&dir, &["tag", "-d", "light"]);
+    assert_eq!("v1\n", run(&dir, &["tag"]));
+
+    // compare-and-swap sees packed values
+    let output = mush!(dir).args(["update-ref", "-d", "refs/tags/v1", &root]).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!("v1\n", run(&dir, &["tag"]));
+
+    assert!(!mush!(dir).args(["update-ref", "refs/heads/main", &root, "0000000000000000000000000000000000000000"]).output().unwrap().status.success());
+}
+
+#[test]
+fn many_packed_refs() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { child, .. } = create_history(&dir, HistoryOptions::default());
+
+    // (written directly, since creating them one by one is slow)
+    let names = (0..2000).map(|i| format!("refs/tags/t{i:04}")).collect::<Vec<_>>();
+    for name in names.iter() {
+        create_file_with_contents(&dir.path().join(".mush/refs/tags"), name.strip_prefix("refs/tags/").unwrap(), &format!("{child}\n"));
+    }
+    run(&dir, &["pack-refs"]);
+
+    let expected = std::iter::once(String::from("refs/heads/main"))
+        .chain(names)
+        .map(|name| format!("{child} {name}\n"))
+        .collect::<String>();
+    assert_eq!(expected, run(&dir, &["show-ref"]));
+    assert_eq!(expected.lines().count(), run(&dir, &["for-each-ref"]).lines().count());
+}
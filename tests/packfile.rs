@@ -0,0 +1,387 @@
+mod helpers;
+
+use helpers::*;
+use mush::hash::{Hash, ObjectFormat};
+
+/// How an object is stored in a hand-built pack
+enum Encoding<'a> {
+    Whole,
+    /// Delta against the entry at this position in the pack
+    OffsetDelta(usize, &'a [u8]),
+    /// Delta against the object with this hash
+    RefDelta(&'a str, &'a [u8]),
+    /// Offset delta whose base offset is encoded as these (possibly bogus) bytes
+    RawOffsetDelta(&'a [u8], &'a [u8]),
+    /// Whole entry, but holding these bytes instead of the contents (which only name it)
+    Corrupt(&'a [u8]),
+    /// Whole entry, whose header claims this size instead of that of the contents
+    Oversized(usize),
+}
+
+struct PackEntry<'a> {
+    tipe: &'a str,
+    contents: &'a [u8],
+    encoding: Encoding<'a>,
+}
+
+fn object_hash(tipe: &str, contents: &[u8]) -> Hash {
+    ObjectFormat::Sha1.digest(&[format!("{tipe} {}\0", contents.len()).as_bytes(), contents].concat())
+}
+
+fn zlib(bytes: &[u8]) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec_zlib(bytes, 6)
+}
+
+/// Variable-length size used in delta headers
+fn delta_size(mut size: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// A delta made of (offset, size) copies from the base and inserted byte strings
+fn delta(base_size: usize, result_size: usize, instructions: &[Result<(usize, usize), &[u8]>]) -> Vec<u8> {
+    let mut delta = [delta_size(base_size), delta_size(result_size)].concat();
+
+    for instruction in instructions {
+        match instruction {
+            Ok((offset, size)) => {
+                let mut opcode = 0x80;
+                let mut arguments = Vec::new();
+                for (i, byte) in (*offset as u32).to_le_bytes().iter().enumerate() {
+                    if *byte != 0 {
+                        opcode |= 1 << i;
+                        arguments.push(*byte);
+                    }
+                }
+                for (i, byte) in (*size as u32).to_le_bytes()[..3].iter().enumerate() {
+                    if *byte != 0 {
+                        opcode |= 1 << (4 + i);
+                        arguments.push(*byte);
+                    }
+                }
+                delta.push(opcode);
+                delta.extend(arguments);
+            },
+            Err(inserted) => {
+                delta.push(inserted.len() as u8);
+                delta.extend_from_slice(inserted);
+            },
+        }
+    }
+
+    delta
+}
+
+/// Write `.mush/objects/pack/pack-test.{pack,idx}` holding `entries`,
+/// and return the hashes of the entries
+fn write_pack(directory: &tempdir::TempDir, entries: &[PackEntry]) -> Vec<String> {
+    let mut pack = [b"PACK".as_slice(), &2u32.to_be_bytes(), &(entries.len() as u32).to_be_bytes()].concat();
+    let mut index_entries: Vec<(Hash, usize)> = Vec::new();
+
+    for entry in entries {
+        let offset = pack.len();
+        let (type_number, data) = match entry.encoding {
+            Encoding::Whole => (
+                ["", "commit", "tree", "blob", "tag"].iter().position(|tipe| *tipe == entry.tipe).unwrap() as u8,
+                entry.contents,
+            ),
+            Encoding::OffsetDelta(_, delta) => (6, delta),
+            Encoding::RefDelta(_, delta) => (7, delta),
+            Encoding::RawOffsetDelta(_, delta) => (6, delta),
+            Encoding::Corrupt(data) => (
+                ["", "commit", "tree", "blob", "tag"].iter().position(|tipe| *tipe == entry.tipe).unwrap() as u8,
+                data,
+            ),
+            Encoding::Oversized(_) => (
+                ["", "commit", "tree", "blob", "tag"].iter().position(|tipe| *tipe == entry.tipe).unwrap() as u8,
+                entry.contents,
+            ),
+        };
+        let declared_size = match entry.encoding {
+            Encoding::Oversized(size) => size,
+            _ => data.len(),
+        };
+
+        // type and size
+        let mut size = declared_size >> 4;
+        let mut byte = (type_number << 4) | (declared_size & 0b1111) as u8;
+        while size != 0 {
+            pack.push(byte | 0x80);
+            byte = (size & 0x7f) as u8;
+            size >>= 7;
+        }
+        pack.push(byte);
+
+        match entry.encoding {
+            Encoding::Whole | Encoding::Corrupt(_) | Encoding::Oversized(_) => {},
+            Encoding::OffsetDelta(base, _) => {
+                let mut distance = offset - index_entries[base].1;
+                let mut bytes = vec![(distance & 0x7f) as u8];
+                distance >>= 7;
+                while distance != 0 {
+                    distance -= 1;
+                    bytes.push(0x80 | (distance & 0x7f) as u8);
+                    distance >>= 7;
+                }
+                bytes.reverse();
+                pack.extend(bytes);
+            },
+            Encoding::RefDelta(base, _) => pack.extend_from_slice(ObjectFormat::Sha1.parse_hash(base).unwrap().as_bytes()),
+            Encoding::RawOffsetDelta(distance, _) => pack.extend_from_slice(distance),
+        }
+
+        pack.extend(zlib(data));
+        index_entries.push((object_hash(entry.tipe, entry.contents), offset));
+    }
+
+    let pack_checksum = ObjectFormat::Sha1.digest(&pack);
+    pack.extend_from_slice(pack_checksum.as_bytes());
+
+    let hashes = index_entries.iter().map(|(hash, _)| String::from(hash.as_str())).collect();
+
+    index_entries.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+    let mut index = [b"\xfftOc".as_slice(), &2u32.to_be_bytes()].concat();
+    for first_byte in 0..=255u8 {
+        let count = index_entries.iter().filter(|(hash, _)| hash.as_bytes()[0] <= first_byte).count();
+        index.extend((count as u32).to_be_bytes());
+    }
+    for (hash, _) in &index_entries {
+        index.extend_from_slice(hash.as_bytes());
+    }
+    for _ in &index_entries {
+        index.extend(0u32.to_be_bytes()); // CRC32s aren't checked when reading
+    }
+    for (_, offset) in &index_entries {
+        index.extend((*offset as u32).to_be_bytes());
+    }
+    index.extend_from_slice(pack_checksum.as_bytes());
+    let index_checksum = ObjectFormat::Sha1.digest(&index);
+    index.extend_from_slice(index_checksum.as_bytes());
+
+    let pack_directory = directory.path().join(".mush/objects/pack");
+    std::fs::create_dir_all(&pack_directory).unwrap();
+    std::fs::write(pack_directory.join("pack-test.pack"), pack).unwrap();
+    std::fs::write(pack_directory.join("pack-test.idx"), index).unwrap();
+
+    hashes
+}
+
+fn cat_file(directory: &tempdir::TempDir, flag: &str, hash: &str) -> String {
+    let output = mush!(directory).args(["cat-file", flag, hash]).output().unwrap();
+    assert_output_success(&output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn read_packed_objects_and_deltas() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let loose = hash_object_write(&dir, "loose", "a loose object, outside of the pack\n");
+
+    let base = b"pedal\nstroke\nmush\n999\n";
+    let edited = b"pedal\nstroke\nmush harder\n999\n";
+    let edited_again = b"mush harder\n999\n1000\n";
+    let from_loose = b"a packed object, outside of the loose objects\n";
+
+    let edit_delta = delta(base.len(), edited.len(), &[Ok((0, 17)), Err(b" harder"), Ok((17, 5))]);
+    let edit_again_delta = delta(edited.len(), edited_again.len(), &[Ok((13, 16)), Err(b"1000\n")]);
+    let from_loose_delta = delta(36, from_loose.len(), &[Ok((0, 2)), Err(b"packed object, outside of the loose objects\n")]);
+
+    let hashes = write_pack(&dir, &[
+        PackEntry { tipe: "blob", contents: base, encoding: Encoding::Whole },
+        PackEntry { tipe: "blob", contents: edited, encoding: Encoding::OffsetDelta(0, &edit_delta) },
+        PackEntry {
+            tipe: "blob",
+            contents: edited_again,
+            encoding: Encoding::RefDelta(&object_hash("blob", edited), &edit_again_delta),
+        },
+        PackEntry { tipe: "blob", contents: from_loose, encoding: Encoding::RefDelta(&loose, &from_loose_delta) },
+    ]);
+
+    assert_eq!(hashes[0], "99622a960c9f3d0232df4d90149b666c11159b9e");
+
+    for (hash, contents) in hashes.iter().zip([base.as_slice(), edited, edited_again, from_loose]) {
+        assert_eq!(cat_file(&dir, "-p", hash).as_bytes(), contents);
+        assert_eq!(cat_file(&dir, "-t", hash), "blob\n");
+        assert_eq!(cat_file(&dir, "-s", hash), format!("{}\n", contents.len()));
+        assert_output_success(&mush!(dir).args(["cat-file", "-e", hash]).output().unwrap());
+    }
+
+    // loose objects are still found
+    assert_eq!(cat_file(&dir, "-p", &loose), "a loose object, outside of the pack\n");
+
+    // reading every object in one process reuses the delta bases it has already built
+    assert_output_success(&mush!(dir).arg("fsck").output().unwrap());
+
+    // abbreviated hashes see packed objects too
+    let output = mush!(dir).args(["rev-parse", &hashes[0][..7]]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\n", hashes[0]));
+}
+
+#[test]
+fn walk_packed_history() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    // build the objects loosely, then move them into a pack
+    let blob = hash_object_write(&dir, "file", "contents\n");
+    assert_output_success(&mush!(dir).args(["update-index", "--add", &blob, "file"]).output().unwrap());
+    let tree = write_tree(&dir);
+    let first = commit_tree_at(&dir, &tree, &[], "First", 1_000_000_000);
+    let second = commit_tree_at(&dir, &tree, &[&first], "Second", 1_000_000_100);
+
+    let raw_objects = [(&blob, "blob"), (&tree, "tree"), (&first, "commit"), (&second, "commit")]
+        .iter()
+        .map(|(hash, tipe)| {
+            let path = dir.path().join(".mush").join(ObjectFormat::Sha1.parse_hash(hash).unwrap().path());
+            let stored = miniz_oxide::inflate::decompress_to_vec_zlib(&std::fs::read(&path).unwrap()).unwrap();
+            std::fs::remove_file(path).unwrap();
+
+            let null_byte_idx = stored.iter().position(|b| *b == b'\0').unwrap();
+            (*tipe, stored[null_byte_idx + 1..].to_vec())
+        })
+        .collect::<Vec<_>>();
+
+    let hashes = write_pack(&dir, &raw_objects.iter()
+        .map(|(tipe, contents)| PackEntry { tipe, contents, encoding: Encoding::Whole })
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(hashes, [blob.clone(), tree.clone(), first.clone(), second.clone()]);
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &second]).output().unwrap());
+
+    let output = mush!(dir).args(["rev-list", "--objects", "main"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{second}\n{first}\n{tree} \n{blob} file\n"),
+    );
+}
+
+#[test]
+fn corrupt_delta() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let base = b"pedal\nstroke\nmush\n999\n";
+    let bad_delta = delta(base.len() + 1, 5, &[Ok((0, 5))]);
+
+    let hashes = write_pack(&dir, &[
+        PackEntry { tipe: "blob", contents: base, encoding: Encoding::Whole },
+        PackEntry { tipe: "blob", contents: b"pedal", encoding: Encoding::OffsetDelta(0, &bad_delta) },
+    ]);
+
+    let output = mush!(dir).args(["cat-file", "-p", &hashes[1]]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Corrupt pack"));
+}
+
+#[test]
+fn corrupt_whole_entry() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let hashes = write_pack(&dir, &[
+        PackEntry { tipe: "blob", contents: b"pedal\nstroke\nmush\n999\n", encoding: Encoding::Corrupt(b"pedal\nstroke\nmush\n998\n") },
+    ]);
+
+    let output = mush!(dir).args(["cat-file", "-p", &hashes[0]]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("is corrupt: its contents hash to"));
+}
+
+#[test]
+fn oversized_entries() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    // sizes far beyond what could be allocated, which must be reported rather than attempted
+    let base = b"pedal\nstroke\nmush\n999\n";
+    let huge = 1 << 60;
+    let huge_delta = delta(base.len(), huge, &[Ok((0, 5))]);
+    let overflowing_delta = delta(base.len(), 3, &[Ok((0, 5))]);
+
+    for entry in [
+        PackEntry { tipe: "blob", contents: b"pedal", encoding: Encoding::Oversized(huge) },
+        PackEntry { tipe: "blob", contents: b"pedal", encoding: Encoding::OffsetDelta(0, &huge_delta) },
+        PackEntry { tipe: "blob", contents: b"ped", encoding: Encoding::OffsetDelta(0, &overflowing_delta) },
+    ] {
+        let hashes = write_pack(&dir, &[
+            PackEntry { tipe: "blob", contents: base, encoding: Encoding::Whole },
+            entry,
+        ]);
+
+        let output = mush!(dir).args(["cat-file", "-p", &hashes[1]]).output().unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("orrupt"), "{stderr}");
+    }
+}
+
+#[test]
+fn overlong_delta_base_offset() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let base = b"pedal\nstroke\nmush\n999\n";
+    let copy = delta(base.len(), 5, &[Ok((0, 5))]);
+
+    // more continuation bytes than a u64 can hold, then a distance that overflows a u64
+    let too_long = [[0xff; 9].as_slice(), &[0x01]].concat();
+    let overflowing = [[0xff; 8].as_slice(), &[0x7f]].concat();
+    for distance in [too_long, overflowing] {
+        let hashes = write_pack(&dir, &[
+            PackEntry { tipe: "blob", contents: base, encoding: Encoding::Whole },
+            PackEntry { tipe: "blob", contents: b"pedal", encoding: Encoding::RawOffsetDelta(&distance, &copy) },
+        ]);
+
+        let output = mush!(dir).args(["cat-file", "-p", &hashes[1]]).output().unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().contains("Corrupt pack"));
+    }
+}
+
+#[test]
+fn truncated_pack() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let hashes = write_pack(&dir, &[
+        PackEntry { tipe: "blob", contents: b"pedal\nstroke\nmush\n999\n", encoding: Encoding::Whole },
+    ]);
+    let pack_directory = dir.path().join(".mush/objects/pack");
+    let fsck = || mush!(dir).arg("fsck").output().unwrap();
+
+    // the pack is cut short, even before its trailing checksum
+    std::fs::write(pack_directory.join("pack-test.pack"), b"PACK\0\0").unwrap();
+
+    let output = mush!(dir).args(["cat-file", "-p", &hashes[0]]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(!String::from_utf8(output.stderr).unwrap().contains("panicked"));
+
+    let output = fsck();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("pack-test.pack` is truncated (6 bytes)"));
+
+    // the index is cut short in its hash table
+    write_pack(&dir, &[
+        PackEntry { tipe: "blob", contents: b"pedal\nstroke\nmush\n999\n", encoding: Encoding::Whole },
+    ]);
+    let index = std::fs::read(pack_directory.join("pack-test.idx")).unwrap();
+    std::fs::write(pack_directory.join("pack-test.idx"), &index[..8 + 256 * 4 + 4]).unwrap();
+
+    let output = mush!(dir).args(["cat-file", "-p", &hashes[0]]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("truncated"));
+}
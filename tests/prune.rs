@@ -0,0 +1,85 @@
+mod helpers;
+
+use helpers::*;
+
+const MONTH: std::time::Duration = std::time::Duration::from_secs(30 * 24 * 60 * 60);
+
+#[test]
+fn prune_unreachable_objects() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let (blob, tree) = stage_file_and_write_tree(&dir);
+    let first = commit_tree_at(&dir, &tree, &[], "First", 1_000_000_000);
+    let second = commit_tree_at(&dir, &tree, &[&first], "Second", 1_000_000_100);
+    let reflog_only = commit_tree_at(&dir, &tree, &[], "Reflog only", 1_000_000_200);
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &reflog_only]).output().unwrap());
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &second]).output().unwrap());
+
+    let old = hash_object_write(&dir, "old", "old\n");
+    let recent = hash_object_write(&dir, "recent", "recent\n");
+    for hash in [&old, &first, &second, &reflog_only, &blob, &tree] {
+        age_file(&dir, &object_path(hash), MONTH);
+    }
+
+    // `--dry-run` only reports
+    let output = mush!(dir).args(["prune", "-n", "--expire", "2.weeks.ago"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{old} blob\n"));
+    assert!(loose_objects(&dir).contains(&old));
+
+    // only objects older than `--expire` are pruned
+    let output = mush!(dir).args(["prune", "-v", "--expire", "2.weeks.ago"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{old} blob\n"));
+
+    let mut expected = vec![blob.clone(), tree.clone(), first.clone(), second.clone(), reflog_only.clone(), recent.clone()];
+    expected.sort();
+    assert_eq!(loose_objects(&dir), expected);
+
+    let output = mush!(dir).args(["prune", "--expire", "never"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(loose_objects(&dir), expected);
+
+    // without `--expire`, all unreachable objects are pruned
+    let output = mush!(dir).args(["prune"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+    expected.retain(|hash| *hash != recent);
+    assert_eq!(loose_objects(&dir), expected);
+
+    // once the reflog is gone, so is what only it referenced
+    std::fs::remove_dir_all(dir.path().join(".mush/logs")).unwrap();
+    let output = mush!(dir).args(["prune", "-v"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{reflog_only} commit\n"));
+
+    let output = mush!(dir).args(["rev-list", "--objects", "main"]).output().unwrap();
+    assert_output_success(&output);
+}
+
+#[test]
+fn prune_packed_loose_objects() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let blob = stage_file(&dir, "file", "file\n");
+    assert_output_success(&mush!(dir).args(["repack"]).output().unwrap());
+    assert_eq!(loose_objects(&dir), vec![blob.clone()]);
+
+    assert_output_success(&mush!(dir).args(["prune"]).output().unwrap());
+    assert!(loose_objects(&dir).is_empty());
+    assert_output_success(&mush!(dir).args(["cat-file", "-e", &blob]).output().unwrap());
+}
+
+#[test]
+fn bad_expiry_date() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let output = mush!(dir).args(["prune", "--expire", "next tuesday"]).output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Bad expiry date: `next tuesday`\n");
+}
@@ -0,0 +1,188 @@
+mod helpers;
+
+use helpers::*;
+
+fn update_ref(dir: &tempdir::TempDir, args: &[&str]) {
+    assert_output_success(
+        &mush!(dir)
+            .arg("update-ref")
+            .args(args)
+            .output()
+            .unwrap()
+    );
+}
+
+fn reflog_lines(dir: &tempdir::TempDir, args: &[&str]) -> Vec<String> {
+    let output = mush!(dir)
+        .arg("reflog")
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+    String::from_utf8(output.stdout).unwrap()
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn branch_and_head_are_logged() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    let b = hash_object_write(&dir, "b", "b\n");
+
+    update_ref(&dir, &["-m", "first", "refs/heads/main", &a]);
+    update_ref(&dir, &["-m", "second", "HEAD", &b]);
+    update_ref(&dir, &["-m", "on topic", "refs/heads/topic", &a]);
+
+    assert_eq!(
+        vec![
+            format!("{} HEAD@{{0}}: second", &b[..7]),
+            format!("{} HEAD@{{1}}: first", &a[..7]),
+        ],
+        reflog_lines(&dir, &[])
+    );
+
+    assert_eq!(
+        vec![
+            format!("{} main@{{0}}: second", &b[..7]),
+            format!("{} main@{{1}}: first", &a[..7]),
+        ],
+        reflog_lines(&dir, &["show", "main"])
+    );
+
+    assert_eq!(
+        vec!["HEAD", "refs/heads/main", "refs/heads/topic"],
+        reflog_lines(&dir, &["list"])
+    );
+
+    let log = std::fs::read_to_string(dir.path().join(".mush/logs/refs/heads/main")).unwrap();
+    let lines = log.lines().collect::<Vec<_>>();
+    assert_eq!(2, lines.len());
+    assert!(lines[0].starts_with(&format!("0000000000000000000000000000000000000000\t{a}\tBud Weiser\t<bud@wiser.org>\t")));
+    assert!(lines[0].ends_with("\tfirst"));
+    assert!(lines[1].starts_with(&format!("{a}\t{b}\tBud Weiser\t<bud@wiser.org>\t")));
+}
+
+#[test]
+fn tags_are_not_logged() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    update_ref(&dir, &["refs/tags/v1", &a]);
+
+    assert!(!dir.path().join(".mush/logs/refs/tags/v1").exists());
+    assert!(!mush!(dir).args(["reflog", "exists", "v1"]).output().unwrap().status.success());
+}
+
+#[test]
+fn deleting_a_branch_deletes_its_log() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    update_ref(&dir, &["refs/heads/feature/x", &a]);
+
+    assert!(mush!(dir).args(["reflog", "exists", "feature/x"]).output().unwrap().status.success());
+
+    update_ref(&dir, &["-d", "refs/heads/feature/x"]);
+
+    assert!(!mush!(dir).args(["reflog", "exists", "feature/x"]).output().unwrap().status.success());
+    assert!(!dir.path().join(".mush/logs/refs/heads/feature").exists());
+}
+
+#[test]
+fn expire() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    let b = hash_object_write(&dir, "b", "b\n");
+
+    update_ref(&dir, &["refs/heads/main", &a]);
+    update_ref(&dir, &["refs/heads/main", &b]);
+
+    // make the first entry ancient
+    let log_path = dir.path().join(".mush/logs/refs/heads/main");
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let mut lines = log.lines().map(String::from).collect::<Vec<_>>();
+    let mut fields = lines[0].split('\t').map(String::from).collect::<Vec<_>>();
+    fields[4] = String::from("1000 +0000");
+    lines[0] = fields.join("\t");
+    std::fs::write(&log_path, lines.join("\n") + "\n").unwrap();
+
+    assert_output_success(&mush!(dir).args(["reflog", "expire", "main"]).output().unwrap());
+    assert_eq!(
+        vec![format!("{} main@{{0}}: update-ref", &b[..7])],
+        reflog_lines(&dir, &["show", "main"])
+    );
+
+    assert_output_success(&mush!(dir).args(["reflog", "expire", "--all", "--expire", "0"]).output().unwrap());
+    assert!(reflog_lines(&dir, &["show", "main"]).is_empty());
+    assert!(reflog_lines(&dir, &[]).is_empty());
+}
+
+/// Update `main` while expiring the reflog of `expired` (`main` itself, or HEAD, which points to it)
+fn check_expire_keeps_concurrent_updates(expired: &'static str) {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let hashes = [hash_object_write(&dir, "a", "a\n"), hash_object_write(&dir, "b", "b\n")];
+    update_ref(&dir, &["refs/heads/main", &hashes[0]]);
+
+    // either command may fail to get the lock, but every update that succeeds stays logged
+    let path = dir.path().to_path_buf();
+    let expiring = std::thread::spawn(move || {
+        for _ in 0..30 {
+            mush!(path).args(["reflog", "expire", expired]).output().unwrap();
+        }
+    });
+
+    let mut updates = 1;
+    for i in 0..30 {
+        let output = mush!(dir).args(["update-ref", "refs/heads/main", &hashes[(i + 1) % 2]]).output().unwrap();
+        if output.status.success() {
+            updates += 1;
+        }
+    }
+    expiring.join().unwrap();
+
+    assert!(updates > 1);
+    assert_eq!(updates, reflog_lines(&dir, &["show", expired]).len());
+}
+
+#[test]
+fn expire_keeps_concurrent_updates() {
+    check_expire_keeps_concurrent_updates("main");
+}
+
+#[test]
+fn expire_head_keeps_concurrent_updates_of_its_branch() {
+    check_expire_keeps_concurrent_updates("HEAD");
+}
+
+#[test]
+fn identity_is_required() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+
+    let output = mush!(dir)
+        .args(["update-ref", "refs/heads/main", &a])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("User config incomplete"));
+    assert!(!dir.path().join(".mush/refs/heads/main").exists());
+}
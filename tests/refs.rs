@@ -0,0 +1,85 @@
+mod helpers;
+
+use helpers::*;
+
+#[test]
+fn resolve_branch_tag_and_head() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let hash = hash_object_write(&dir, "999.txt", "pedal\nstroke\nmush\n999\n");
+
+    create_file_with_contents(dir.path(), ".mush/refs/heads/main", &format!("{hash}\n"));
+    create_file_with_contents(dir.path(), ".mush/refs/tags/v1", &format!("{hash}\n"));
+
+    ["HEAD", "main", "heads/main", "refs/heads/main", "v1", "tags/v1", "refs/tags/v1"]
+        .iter()
+        .for_each(|name| {
+            let output = mush!(dir)
+                .arg("cat-file")
+                .arg("-p")
+                .arg(name)
+                .output()
+                .unwrap();
+
+            assert_output_success(&output);
+            assert_eq!("pedal\nstroke\nmush\n999\n", String::from_utf8(output.stdout).unwrap());
+        });
+}
+
+#[test]
+fn chained_symbolic_refs() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let hash = hash_object_write(&dir, "x", "abcd\n");
+
+    create_dir(dir.path(), ".mush/refs/heads/nested");
+    create_file_with_contents(dir.path(), ".mush/refs/heads/nested/target", &format!("{hash}\n"));
+    create_file_with_contents(dir.path(), ".mush/refs/heads/main", "ref: refs/heads/nested/target\n");
+
+    let output = mush!(dir)
+        .arg("cat-file")
+        .arg("-t")
+        .arg("HEAD")
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+    assert_eq!("blob\n", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn symbolic_ref_loop() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    create_file_with_contents(dir.path(), ".mush/refs/heads/main", "ref: refs/heads/other\n");
+    create_file_with_contents(dir.path(), ".mush/refs/heads/other", "ref: refs/heads/main\n");
+
+    let output = mush!(dir)
+        .arg("cat-file")
+        .arg("-t")
+        .arg("HEAD")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("too many levels of symbolic refs"));
+}
+
+#[test]
+fn unborn_head() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let output = mush!(dir)
+        .arg("cat-file")
+        .arg("-t")
+        .arg("HEAD")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!("Not a valid object name: `HEAD`\n", String::from_utf8(output.stderr).unwrap());
+}
@@ -0,0 +1,137 @@
+mod helpers;
+
+use helpers::*;
+
+/// Commit `file` with `contents` on top of `parents`, and return the (commit, blob) hashes
+fn commit_file(dir: &tempdir::TempDir, contents: &str, parents: &[&str], seconds: i64) -> (String, String) {
+    let blob = stage_file(dir, "file", contents);
+    let tree = write_tree(dir);
+    (commit_tree_at(dir, &tree, parents, contents, seconds), blob)
+}
+
+fn repack(dir: &tempdir::TempDir, args: &[&str]) -> String {
+    let output = mush!(dir).arg("repack").args(args).output().unwrap();
+    assert_output_success(&output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn repack_loose_objects() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let (first, _) = commit_file(&dir, "first\n", &[], 1_000_000_000);
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &first]).output().unwrap());
+    let unreachable = hash_object_write(&dir, "unreachable", "unreachable\n");
+
+    // everything reachable is packed; loose objects are kept without `-d`
+    assert_eq!(repack(&dir, &[]), "");
+    assert_eq!(packs(&dir).len(), 1);
+    assert!(loose_objects(&dir).contains(&first));
+
+    assert_eq!(repack(&dir, &[]), "Nothing new to pack.\n");
+
+    // only the new objects go into a new pack; `-d` removes the packed loose objects
+    let (second, second_blob) = commit_file(&dir, "second\n", &[&first], 1_000_000_100);
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &second]).output().unwrap());
+
+    repack(&dir, &["-d"]);
+    assert_eq!(packs(&dir).len(), 2);
+    assert_eq!(loose_objects(&dir), vec![unreachable.clone()]);
+
+    for hash in [&first, &second, &second_blob] {
+        assert_output_success(&mush!(dir).args(["cat-file", "-e", hash]).output().unwrap());
+    }
+
+    // `-a -d` consolidates everything into a single pack
+    repack(&dir, &["-a", "-d"]);
+    assert_eq!(packs(&dir).len(), 1);
+    assert_eq!(loose_objects(&dir), vec![unreachable.clone()]);
+
+    let output = mush!(dir).args(["rev-list", "--objects", "main"]).output().unwrap();
+    assert_output_success(&output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 6);
+
+    // repacking the same objects again keeps the (identical) pack
+    repack(&dir, &["-a", "-d"]);
+    assert_eq!(packs(&dir).len(), 1);
+}
+
+#[test]
+fn unreachable_packed_objects() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let (first, _) = commit_file(&dir, "first\n", &[], 1_000_000_000);
+    let (second, _) = commit_file(&dir, "second\n", &[&first], 1_000_000_100);
+    assert_output_success(&mush!(dir).args(["update-ref", "--no-deref", "HEAD", &second]).output().unwrap());
+    repack(&dir, &["-d"]);
+    assert!(loose_objects(&dir).is_empty());
+
+    // `second` is only reachable through HEAD's reflog
+    assert_output_success(&mush!(dir).args(["update-ref", "--no-deref", "HEAD", &first]).output().unwrap());
+    std::fs::remove_dir_all(dir.path().join(".mush/logs")).unwrap();
+    let (_, staged) = commit_file(&dir, "staged\n", &[], 1_000_000_200);
+
+    // `-A` makes the unreachable objects of old packs loose, instead of deleting them
+    repack(&dir, &["-A", "-d"]);
+    assert_eq!(packs(&dir).len(), 1);
+    assert!(loose_objects(&dir).contains(&second));
+    assert!(!loose_objects(&dir).contains(&first));
+    assert!(!loose_objects(&dir).contains(&staged)); // reachable from the index
+
+    // `-a` deletes them with the old pack
+    let mut child = mush!(dir)
+        .args(["pack-objects", ".mush/objects/pack/pack"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), format!("{second}\n").as_bytes()).unwrap();
+    assert!(child.wait().unwrap().success());
+    std::fs::remove_file(dir.path().join(".mush").join(object_path(&second))).unwrap();
+
+    repack(&dir, &["-a", "-d"]);
+    assert_eq!(packs(&dir).len(), 1);
+    assert!(!loose_objects(&dir).contains(&second));
+    let output = mush!(dir).args(["cat-file", "-e", &second]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn loosened_objects_keep_their_bytes() {
+    use mush::hash::ObjectFormat;
+    use mush::object::store::{LooseObjectStore, ObjectStore};
+    use mush::object::ObjectType;
+
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    // a message that isn't UTF-8 wouldn't survive being decoded and encoded again
+    let (_, blob) = commit_file(&dir, "file\n", &[], 1_000_000_000);
+    let tree = write_tree(&dir);
+    let contents = [
+        format!("tree\t{tree}\nauthor\tBud Weiser\t<bud@wiser.org>\t1000000000 +0000\n\n").as_bytes(),
+        b"not \xff utf-8\n",
+    ].concat();
+    let store = LooseObjectStore::new(dir.path().join(".mush/objects").to_str().unwrap(), ObjectFormat::Sha1);
+    let commit = store.write_raw(ObjectType::Commit, &contents).unwrap();
+
+    let mut child = mush!(dir)
+        .args(["pack-objects", ".mush/objects/pack/pack"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), format!("{}\n", commit.as_str()).as_bytes()).unwrap();
+    assert!(child.wait().unwrap().success());
+    store.delete(&commit).unwrap();
+
+    repack(&dir, &["-A", "-d"]);
+    assert!(loose_objects(&dir).contains(&String::from(commit.as_str())));
+    assert!(!loose_objects(&dir).contains(&blob)); // reachable from the index
+    assert!(store.read_raw(&commit).unwrap() == (ObjectType::Commit, contents));
+}
@@ -0,0 +1,168 @@
+mod helpers;
+
+use helpers::*;
+
+fn rev_list(dir: &tempdir::TempDir, args: &[&str]) -> std::process::Output {
+    mush!(dir)
+        .arg("rev-list")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn lines(hashes: &[&String]) -> String {
+    hashes.iter().map(|hash| format!("{hash}\n")).collect()
+}
+
+struct History {
+    root: String,
+    a: String,
+    b: String,
+    c: String,
+    d: String,
+    merge: String,
+}
+
+/// ```text
+/// root - a - b ---- merge   (main)
+///      \         /
+///       c ---- d            (side)
+/// ```
+/// Commit dates increase in the order root, a, c, b, d, merge,
+/// unless `root_seconds` is given (to simulate clock skew)
+fn create_history(dir: &tempdir::TempDir, root_seconds: i64) -> History {
+    mush_config_user(dir);
+
+    let tree = write_tree(dir);
+
+    let root = commit_tree_at(dir, &tree, &[], "root\n", root_seconds);
+    let a = commit_tree_at(dir, &tree, &[&root], "a\n", 200);
+    let c = commit_tree_at(dir, &tree, &[&root], "c\n", 300);
+    let b = commit_tree_at(dir, &tree, &[&a], "b\n", 400);
+    let d = commit_tree_at(dir, &tree, &[&c], "d\n", 500);
+    let merge = commit_tree_at(dir, &tree, &[&b, &d], "merge\n", 600);
+
+    for (name, hash) in [("refs/heads/main", &merge), ("refs/heads/side", &d)] {
+        assert_output_success(&mush!(dir).args(["update-ref", name, hash]).output().unwrap());
+    }
+
+    History { root, a, b, c, d, merge }
+}
+
+#[test]
+fn ordering() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, a, b, c, d, merge } = create_history(&dir, 100);
+
+    assert_eq!(lines(&[&merge, &d, &b, &c, &a, &root]), stdout(rev_list(&dir, &["main"])));
+    assert_eq!(lines(&[&merge, &d, &b, &c, &a, &root]), stdout(rev_list(&dir, &["--date-order", "main"])));
+    assert_eq!(lines(&[&merge, &b, &a, &d, &c, &root]), stdout(rev_list(&dir, &["--topo-order", "main"])));
+    assert_eq!(lines(&[&root, &c, &d, &a, &b, &merge]), stdout(rev_list(&dir, &["--topo-order", "--reverse", "main"])));
+    assert_eq!(lines(&[&merge, &b, &a, &root]), stdout(rev_list(&dir, &["--first-parent", "main"])));
+
+    assert_eq!(lines(&[&merge, &d]), stdout(rev_list(&dir, &["-n", "2", "main"])));
+    assert_eq!(lines(&[&d, &merge]), stdout(rev_list(&dir, &["--max-count=2", "--reverse", "main"])));
+    assert_eq!("6\n", stdout(rev_list(&dir, &["--count", "main"])));
+    assert_eq!("2\n", stdout(rev_list(&dir, &["--count", "-n", "2", "main"])));
+
+    assert!(!rev_list(&dir, &["--topo-order", "--date-order", "main"]).status.success());
+}
+
+#[test]
+fn clock_skew() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    // root claims to be newer than all of its descendants
+    let History { root, a, b, c, d, merge } = create_history(&dir, 1000);
+
+    // the default order only looks at dates...
+    assert_eq!(lines(&[&merge, &d, &b, &c, &root, &a]), stdout(rev_list(&dir, &["main"])));
+    // ...but `--date-order` never shows a parent before its children
+    assert_eq!(lines(&[&merge, &d, &b, &c, &a, &root]), stdout(rev_list(&dir, &["--date-order", "main"])));
+}
+
+#[test]
+fn ranges_and_exclusions() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, a, b, c, d, merge } = create_history(&dir, 100);
+
+    assert_eq!(lines(&[&merge, &b, &a]), stdout(rev_list(&dir, &["side..main"])));
+    assert_eq!(lines(&[&merge, &b, &a]), stdout(rev_list(&dir, &["main", "^side"])));
+    assert_eq!(lines(&[&merge, &b, &a]), stdout(rev_list(&dir, &["^side", "main"])));
+    assert_eq!(lines(&[&d, &c]), stdout(rev_list(&dir, &[&format!("{b}..side")])));
+    assert_eq!(lines(&[&d, &b, &c, &a]), stdout(rev_list(&dir, &[&format!("{b}...side")])));
+    assert_eq!(lines(&[&d, &b, &c, &a]), stdout(rev_list(&dir, &[&format!("side...{b}")])));
+    assert_eq!(lines(&[&merge, &b, &a]), stdout(rev_list(&dir, &["main...side"])));
+    assert_eq!(lines(&[&merge]), stdout(rev_list(&dir, &["main~1..main", "side..main^2"])));
+    assert_eq!(lines(&[&merge]), stdout(rev_list(&dir, &["^main~1", "main", "^main^2", &root])));
+
+    // omitted endpoints are HEAD (which is main)
+    assert_eq!("", stdout(rev_list(&dir, &["..side"])));
+    assert_eq!(lines(&[&merge, &b, &a]), stdout(rev_list(&dir, &["side.."])));
+
+    // tags are peeled
+    assert_output_success(&mush!(dir).args(["tag", "-m", "version b", "v1", &b]).output().unwrap());
+    assert_eq!(lines(&[&b, &a, &root]), stdout(rev_list(&dir, &["v1"])));
+    assert_eq!(lines(&[&merge, &d, &c]), stdout(rev_list(&dir, &["v1..main"])));
+
+    for args in [vec![], vec!["nope"], vec!["main^{tree}"], vec!["main..nope"]] {
+        assert!(!rev_list(&dir, &args).status.success(), "{args:?}");
+    }
+
+    // a `..` after `:` is part of a path, not a range
+    let output = rev_list(&dir, &["main:../x"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("Path `../x` does not exist in tree"));
+}
+
+#[test]
+fn objects() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    create_dir(dir.path(), "y");
+    let x1 = stage_file(&dir, "x", "one\n");
+    let z = stage_file(&dir, "y/z.txt", "zed\n");
+    let tree1 = write_tree(&dir);
+    let commit1 = commit_tree_at(&dir, &tree1, &[], "first\n", 100);
+
+    let x2 = stage_file(&dir, "x", "two\n");
+    let tree2 = write_tree(&dir);
+    let commit2 = commit_tree_at(&dir, &tree2, &[&commit1], "second\n", 200);
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &commit2]).output().unwrap());
+    let y = stdout(mush!(dir).args(["rev-parse", "main:y"]).output().unwrap()).trim_end().to_string();
+
+    // root trees have an empty path (after a space, like git)
+    assert_eq!(
+        format!("{commit2}\n{commit1}\n{tree2} \n{x2} x\n{y} y\n{z} y/z.txt\n{tree1} \n{x1} x\n"),
+        stdout(rev_list(&dir, &["--objects", "main"]))
+    );
+    assert_eq!("8\n", stdout(rev_list(&dir, &["--objects", "--count", "main"])));
+
+    // objects reachable from excluded commits aren't listed
+    assert_eq!(
+        format!("{commit2}\n{tree2} \n{x2} x\n"),
+        stdout(rev_list(&dir, &["--objects", "main~1..main"]))
+    );
+    assert_eq!("", stdout(rev_list(&dir, &["--objects", "main..main~1"])));
+
+    // a tree reachable from an excluded commit is excluded, even if a listed commit points to it
+    let commit3 = commit_tree_at(&dir, &tree1, &[&commit2], "revert\n", 300);
+    assert_eq!(
+        format!("{commit3}\n{commit2}\n{tree2} \n{x2} x\n"),
+        stdout(rev_list(&dir, &["--objects", &commit3, "^main~1"]))
+    );
+
+    // only the trees of boundary commits (excluded parents of listed commits) are excluded
+    assert_eq!(
+        format!("{commit3}\n{tree1} \n{x1} x\n"),
+        stdout(rev_list(&dir, &["--objects", &format!("main..{commit3}")]))
+    );
+}
@@ -0,0 +1,126 @@
+mod helpers;
+
+use helpers::*;
+
+fn rev_parse(dir: &std::path::Path, args: &[&str]) -> std::process::Output {
+    mush!(dir)
+        .arg("rev-parse")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout_lines(output: std::process::Output) -> Vec<String> {
+    assert_output_success(&output);
+    String::from_utf8(output.stdout).unwrap()
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn full_hashes() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let tree = write_tree(&dir);
+    let root = commit_tree(&dir, &tree, &[], "root\n");
+    let child = commit_tree(&dir, &tree, &[&root], "child\n");
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &child]).output().unwrap());
+
+    assert_eq!(
+        vec![child.clone(), root.clone(), tree.clone(), root.clone()],
+        stdout_lines(rev_parse(dir.path(), &["HEAD", "main~", "HEAD^{tree}", &root[..6]]))
+    );
+
+    assert_eq!(vec![child.clone()], stdout_lines(rev_parse(dir.path(), &["--verify", "main"])));
+
+    // full hashes are given back in lowercase
+    assert_eq!(vec![root.clone()], stdout_lines(rev_parse(dir.path(), &[&root.to_uppercase()])));
+}
+
+#[test]
+fn verify() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let blob = hash_object_write(&dir, "x", "abcd\n");
+
+    let output = rev_parse(dir.path(), &["--verify", &blob, &blob]);
+    assert!(!output.status.success());
+    assert_eq!("Needed a single revision\n", String::from_utf8(output.stderr).unwrap());
+
+    // nonexistent full hashes are printed as-is, unless verifying
+    let missing = "99622a960c9f3d0232df4d90149b666c11159b9e";
+    assert_eq!(vec![missing], stdout_lines(rev_parse(dir.path(), &[missing])));
+
+    let output = rev_parse(dir.path(), &["--verify", missing]);
+    assert!(!output.status.success());
+    assert_eq!(format!("No such object: {missing}\n"), String::from_utf8(output.stderr).unwrap());
+
+    let output = rev_parse(dir.path(), &["--verify", "--quiet", "nope"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn short() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let blob = hash_object_write(&dir, "999.txt", "pedal\nstroke\nmush\n999\n");
+    assert_eq!("99622a960c9f3d0232df4d90149b666c11159b9e", blob);
+
+    assert_eq!(vec!["99622a9"], stdout_lines(rev_parse(dir.path(), &["--short", &blob])));
+    assert_eq!(vec!["99622a960c"], stdout_lines(rev_parse(dir.path(), &["--short=10", &blob])));
+    assert_eq!(vec!["9962"], stdout_lines(rev_parse(dir.path(), &["--short=1", &blob])));
+
+    // fabricate an object sharing the first 9 digits
+    let objects = dir.path().join(".mush/objects/99");
+    std::fs::copy(objects.join(&blob[2..]), objects.join("622a960fffffffffffffffffffffffffffffff")).unwrap();
+
+    assert_eq!(vec!["99622a960c"], stdout_lines(rev_parse(dir.path(), &["--short", &blob])));
+    assert_eq!(vec!["99622a960c"], stdout_lines(rev_parse(dir.path(), &["--short=4", &blob])));
+    assert_eq!(vec!["99622a960c9f"], stdout_lines(rev_parse(dir.path(), &["--short=12", &blob])));
+}
+
+#[test]
+fn abbrev_ref() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let blob = hash_object_write(&dir, "x", "abcd\n");
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/feature/x", &blob]).output().unwrap());
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/tags/v1", &blob]).output().unwrap());
+
+    // HEAD is symbolic (to `main`), even though `main` doesn't exist yet
+    assert_eq!(
+        vec!["main", "feature/x", "feature/x", "v1"],
+        stdout_lines(rev_parse(dir.path(), &["--abbrev-ref", "HEAD", "feature/x", "refs/heads/feature/x", "v1"]))
+    );
+
+    let output = rev_parse(dir.path(), &["--abbrev-ref", &blob]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn directories() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    create_dir(dir.path(), "a");
+    create_dir(dir.path(), "a/b");
+
+    let toplevel = dir.path().canonicalize().unwrap();
+    let toplevel = toplevel.to_str().unwrap();
+
+    assert_eq!(
+        vec![String::from(toplevel), format!("{toplevel}/.mush")],
+        stdout_lines(rev_parse(&dir.path().join("a/b"), &["--show-toplevel", "--mush-dir"]))
+    );
+
+    let outside = tempdir();
+    assert!(!rev_parse(outside.path(), &["--show-toplevel"]).status.success());
+}
@@ -0,0 +1,430 @@
+mod helpers;
+
+use helpers::*;
+
+fn cat_file(dir: &tempdir::TempDir, args: &[&str]) -> std::process::Output {
+    mush!(dir)
+        .arg("cat-file")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn abbreviated_hashes() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let hash = hash_object_write(&dir, "999.txt", "pedal\nstroke\nmush\n999\n");
+    assert_eq!("99622a960c9f3d0232df4d90149b666c11159b9e", hash);
+
+    ["9962", "99622a9", "99622A960C9F", "99622a960c9f3d0232df4d90149b666c11159b9", "99622A960C9F3D0232DF4D90149B666C11159B9E"]
+        .iter()
+        .for_each(|prefix| {
+            let output = cat_file(&dir, &["-p", prefix]);
+
+            assert_output_success(&output);
+            assert_eq!("pedal\nstroke\nmush\n999\n", String::from_utf8(output.stdout).unwrap());
+        });
+}
+
+#[test]
+fn unknown_names() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    hash_object_write(&dir, "999.txt", "pedal\nstroke\nmush\n999\n");
+
+    // too short, not hex, no such object, no such ref
+    ["996", "99g2", "1234abcd", "main", "refs/heads/nope"]
+        .iter()
+        .for_each(|name| {
+            let output = cat_file(&dir, &["-t", name]);
+
+            assert!(!output.status.success());
+            assert_eq!(
+                format!("Not a valid object name: `{name}`\n"),
+                String::from_utf8(output.stderr).unwrap()
+            );
+        });
+}
+
+#[test]
+fn ambiguous_abbreviated_hash() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let hash = hash_object_write(&dir, "999.txt", "pedal\nstroke\nmush\n999\n");
+
+    // fabricate a second object sharing the prefix `9962`
+    let objects = dir.path().join(".mush/objects/99");
+    std::fs::copy(objects.join(&hash[2..]), objects.join("62ffffffffffffffffffffffffffffffffffff")).unwrap();
+
+    let output = cat_file(&dir, &["-t", "9962"]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        concat!(
+            "Short object hash `9962` is ambiguous. The candidates are:\n",
+            "    99622a960c9f3d0232df4d90149b666c11159b9e blob\n",
+            "    9962ffffffffffffffffffffffffffffffffffff blob\n",
+        ),
+        String::from_utf8(output.stderr).unwrap()
+    );
+
+    // a longer prefix disambiguates
+    let output = cat_file(&dir, &["-t", "99622"]);
+    assert_output_success(&output);
+    assert_eq!("blob\n", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn refs_take_precedence_over_abbreviated_hashes() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    hash_object_write(&dir, "999.txt", "pedal\nstroke\nmush\n999\n");
+    let other = hash_object_write(&dir, "x", "abcd\n");
+
+    create_file_with_contents(dir.path(), ".mush/refs/heads/9962", &format!("{other}\n"));
+
+    let output = cat_file(&dir, &["-p", "9962"]);
+    assert_output_success(&output);
+    assert_eq!("abcd\n", String::from_utf8(output.stdout).unwrap());
+}
+
+/// Returns (root, left, right, merge), where `merge` has parents `left` and `right`,
+/// both of which have parent `root`; `main` points to `merge`.
+fn create_merge_history(dir: &tempdir::TempDir) -> (String, String, String, String) {
+    mush_config_user(dir);
+
+    let tree = write_tree(dir);
+    let root = commit_tree(dir, &tree, &[], "root\n");
+    let left = commit_tree(dir, &tree, &[&root], "left\n");
+    let right = commit_tree(dir, &tree, &[&root], "right\n");
+    let merge = commit_tree(dir, &tree, &[&left, &right], "merge\n");
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &merge]).output().unwrap());
+
+    (root, left, right, merge)
+}
+
+fn assert_same_object(dir: &tempdir::TempDir, revision: &str, hash: &str) {
+    let output = cat_file(dir, &["-p", revision]);
+    assert_output_success(&output);
+
+    let expected = cat_file(dir, &["-p", hash]);
+    assert_eq!(String::from_utf8(expected.stdout).unwrap(), String::from_utf8(output.stdout).unwrap(), "{revision}");
+}
+
+#[test]
+fn parent_modifiers() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let (root, left, right, merge) = create_merge_history(&dir);
+
+    [
+        ("HEAD^0", &merge),
+        ("HEAD~0", &merge),
+        ("HEAD^", &left),
+        ("HEAD^1", &left),
+        ("main~", &left),
+        ("HEAD^2", &right),
+        ("HEAD~2", &root),
+        ("HEAD^^", &root),
+        ("HEAD^2~1", &root),
+        ("HEAD^2^", &root),
+        (&format!("{}~1", &right[..7]), &root),
+    ].iter().for_each(|(revision, hash)| {
+        assert_same_object(&dir, revision, hash);
+    });
+}
+
+#[test]
+fn parent_modifier_errors() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let (root, left, _right, merge) = create_merge_history(&dir);
+    let blob = hash_object_write(&dir, "x", "abcd\n");
+
+    [
+        ("HEAD^3", format!("Commit {merge} has 2 parent(s), so it has no parent #3\n")),
+        ("HEAD^^2", format!("Commit {left} has 1 parent(s), so it has no parent #2\n")),
+        ("HEAD~3", format!("Cannot go back 3 generation(s): reached root commit {root} after 2\n")),
+        (&format!("{blob}^"), format!("Object {blob} is a blob, not a commit\n")),
+        (&format!("{blob}~0"), format!("Object {blob} is a blob, not a commit\n")),
+    ].iter().for_each(|(revision, message)| {
+        let output = cat_file(&dir, &["-t", revision]);
+
+        assert!(!output.status.success(), "{revision}");
+        assert_eq!(*message, String::from_utf8(output.stderr).unwrap());
+    });
+}
+
+/// Commit a tree containing `x` and `y/xyz.txt` (to `main`), and return the commit hash
+fn create_nested_commit(dir: &tempdir::TempDir) -> String {
+    mush_config_user(dir);
+
+    create_dir(dir.path(), "y");
+    create_file_with_contents(dir.path(), "y/xyz.txt", "abc\n");
+    create_file_with_contents(dir.path(), "x", "abcd\n");
+
+    for (hash, filename) in [
+        ("acbe86c7c89586e0912a0a851bacf309c595c308", "x"),
+        ("8baef1b4abc478178b004d62031cf7fe6db6f903", "y/xyz.txt"),
+    ] {
+        assert_output_success(&mush!(dir).args(["update-index", "--add", hash, filename]).output().unwrap());
+    }
+
+    let tree = write_tree(dir);
+    assert_eq!("0742454447b93be8ab983887217db204371a77bd", tree);
+
+    let commit = commit_tree(dir, &tree, &[], "nested\n");
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &commit]).output().unwrap());
+
+    commit
+}
+
+#[test]
+fn peel_and_path_syntax() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let commit = create_nested_commit(&dir);
+
+    [
+        ("HEAD^{}", commit.as_str()),
+        ("HEAD^{object}", &commit),
+        ("HEAD^{commit}", &commit),
+        ("HEAD^{tree}", "0742454447b93be8ab983887217db204371a77bd"),
+        ("HEAD^{tree}^{tree}", "0742454447b93be8ab983887217db204371a77bd"),
+        ("HEAD:", "0742454447b93be8ab983887217db204371a77bd"),
+        ("HEAD:x", "acbe86c7c89586e0912a0a851bacf309c595c308"),
+        ("HEAD:y", "892b8c36b1579b893c2eb05641d4361bd25ffde9"),
+        ("HEAD:y/", "892b8c36b1579b893c2eb05641d4361bd25ffde9"),
+        ("HEAD:y/xyz.txt", "8baef1b4abc478178b004d62031cf7fe6db6f903"),
+        ("main^{tree}:y/xyz.txt", "8baef1b4abc478178b004d62031cf7fe6db6f903"),
+        ("0742:y/xyz.txt", "8baef1b4abc478178b004d62031cf7fe6db6f903"),
+        (":x", "acbe86c7c89586e0912a0a851bacf309c595c308"),
+        (":y/xyz.txt", "8baef1b4abc478178b004d62031cf7fe6db6f903"),
+        ("acbe^{blob}", "acbe86c7c89586e0912a0a851bacf309c595c308"),
+    ].iter().for_each(|(revision, hash)| {
+        assert_same_object(&dir, revision, hash);
+    });
+}
+
+#[test]
+fn peel_and_path_errors() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let commit = create_nested_commit(&dir);
+
+    [
+        ("HEAD^{blob}", format!("Object {commit} is a commit, and can't be peeled to a blob\n")),
+        ("HEAD^{tree}^{commit}", String::from("Object 0742454447b93be8ab983887217db204371a77bd is a tree, and can't be peeled to a commit\n")),
+        ("HEAD:nope", String::from("Path `nope` does not exist in tree 0742454447b93be8ab983887217db204371a77bd\n")),
+        ("HEAD:x/nope", String::from("Path `x/nope` does not exist in tree 0742454447b93be8ab983887217db204371a77bd\n")),
+        ("HEAD:x:y", String::from("Path `x:y` does not exist in tree 0742454447b93be8ab983887217db204371a77bd\n")),
+        (":nope", String::from("Path `nope` is not in the index\n")),
+        ("acbe:x", String::from("Object acbe86c7c89586e0912a0a851bacf309c595c308 is a blob, and can't be peeled to a tree\n")),
+        ("HEAD^{nonsense}", String::from("Bad revision spec: `HEAD^{nonsense}`\n")),
+        ("HEAD^x", String::from("Bad revision spec: `HEAD^x`\n")),
+        ("HEAD~2x", String::from("Bad revision spec: `HEAD~2x`\n")),
+        ("HEAD^{tree", String::from("Bad revision spec: `HEAD^{tree`\n")),
+    ].iter().for_each(|(revision, message)| {
+        let output = cat_file(&dir, &["-t", revision]);
+
+        assert!(!output.status.success(), "{revision}");
+        assert_eq!(*message, String::from_utf8(output.stderr).unwrap());
+    });
+}
+
+#[test]
+fn reflog_entry_syntax() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let (root, left, right, merge) = create_merge_history(&dir);
+
+    for hash in [&left, &right, &root] {
+        assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", hash]).output().unwrap());
+    }
+
+    assert_same_object(&dir, "main@{0}", &root);
+    assert_same_object(&dir, "main@{1}", &right);
+    assert_same_object(&dir, "refs/heads/main@{2}", &left);
+    assert_same_object(&dir, "main@{3}", &merge);
+    assert_same_object(&dir, "main@{3}^2", &right);
+    assert_same_object(&dir, "main@{2}~1", &root);
+
+    // without a ref: the current branch
+    assert_same_object(&dir, "@{1}", &right);
+    assert_same_object(&dir, "HEAD@{3}", &merge);
+    assert_same_object(&dir, "@", &root);
+    assert_same_object(&dir, "@@{2}", &left);
+
+    [
+        ("main@{4}", String::from("Log for `refs/heads/main` only has 4 entries\n")),
+        ("nope@{1}", String::from("Not a valid object name: `nope@{1}`\n")),
+        ("main@{nonsense}", String::from("Bad revision spec: `main@{nonsense}`\n")),
+        ("main@{1", String::from("Not a valid object name: `main@{1`\n")),
+    ].iter().for_each(|(revision, message)| {
+        let output = cat_file(&dir, &["-t", revision]);
+
+        assert!(!output.status.success(), "{revision}");
+        assert_eq!(*message, String::from_utf8(output.stderr).unwrap());
+    });
+}
+
+/// Write a reflog for `ref_name`, with one line per `(old, new, unix time, reason)`
+fn write_reflog(dir: &tempdir::TempDir, ref_name: &str, entries: &[(&str, &str, i64, &str)]) {
+    let contents = entries.iter()
+        .map(|(old, new, time, reason)| format!("{old}\t{new}\tBud Weiser\t<bud@wiser.org>\t{time} +0000\t{reason}\n"))
+        .collect::<String>();
+
+    let path = dir.path().join(".mush/logs").join(ref_name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn reflog_date_syntax() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let (root, left, right, _merge) = create_merge_history(&dir);
+    let zero = "0000000000000000000000000000000000000000";
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/fixed", &right]).output().unwrap());
+    write_reflog(&dir, "refs/heads/fixed", &[
+        (zero, &root, 1700000000, "branch: Created from HEAD"),
+        (&root, &left, 1700100000, "update-ref"),
+        (&left, &right, 1700200000, "update-ref"),
+    ]);
+
+    assert_same_object(&dir, "fixed@{@1700050000}", &root);
+    assert_same_object(&dir, "fixed@{@1700100000}", &left);
+    assert_same_object(&dir, "fixed@{2023-11-16T12:00:00Z}", &left);
+    assert_same_object(&dir, "fixed@{Fri, 17 Nov 2023 12:00:00 +0000}", &right);
+    assert_same_object(&dir, "fixed@{2030-01-01}", &right);
+    // before the log begins: the oldest known value
+    assert_same_object(&dir, "fixed@{@1600000000}", &root);
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    let (hour, day) = (60 * 60, 24 * 60 * 60);
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/relative", &right]).output().unwrap());
+    write_reflog(&dir, "refs/heads/relative", &[
+        (zero, &root, now - 10 * day, "branch: Created from HEAD"),
+        (&root, &left, now - 3 * day, "update-ref"),
+        (&left, &right, now - hour, "update-ref"),
+    ]);
+
+    assert_same_object(&dir, "relative@{now}", &right);
+    assert_same_object(&dir, "relative@{5.minutes.ago}", &right);
+    assert_same_object(&dir, "relative@{yesterday}", &left);
+    assert_same_object(&dir, "relative@{2 days ago}", &left);
+    assert_same_object(&dir, "relative@{1.week.ago}", &root);
+    assert_same_object(&dir, "relative@{1.week.ago}^{tree}", &format!("{root}^{{tree}}"));
+
+    // an unknown unit, and a date too far back to represent
+    for spec in ["relative@{3.fortnights.ago}", "relative@{4000000000.days.ago}", "relative@{4000000000.weeks.ago}"] {
+        let output = cat_file(&dir, &["-t", spec]);
+        assert!(!output.status.success());
+        assert_eq!(format!("Bad revision spec: `{spec}`\n"), String::from_utf8(output.stderr).unwrap());
+    }
+}
+
+#[test]
+fn previous_branch_syntax() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let (root, left, _right, merge) = create_merge_history(&dir);
+
+    // switching branches is logged even if the hash stays the same (`same` is at `merge`, like `main`)
+    for (name, hash) in [("topic", &left), ("same", &merge)] {
+        assert_output_success(&mush!(dir).args(["update-ref", &format!("refs/heads/{name}"), hash]).output().unwrap());
+    }
+    for target in ["refs/heads/topic", "refs/heads/same", "refs/heads/main"] {
+        assert_output_success(&mush!(dir).args(["symbolic-ref", "HEAD", target]).output().unwrap());
+    }
+
+    let rev_parse = |revision: &str| stdout(mush!(dir).args(["rev-parse", revision]).output().unwrap());
+    assert_eq!(format!("{merge}\n"), rev_parse("@{-1}")); // same
+    assert_eq!(format!("{left}\n"), rev_parse("@{-2}")); // topic
+    assert_eq!(format!("{merge}\n"), rev_parse("@{-3}")); // main
+    assert_same_object(&dir, "@{-2}^1", &root);
+
+    // the branch is resolved now, not at the time of the checkout
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/same", &root]).output().unwrap());
+    assert_eq!(format!("{root}\n"), rev_parse("@{-1}"));
+
+    let output = cat_file(&dir, &["-t", "@{-4}"]);
+    assert!(!output.status.success());
+    assert_eq!("`@{-4}`: only 3 checkout(s) found in the reflog of HEAD\n", String::from_utf8(output.stderr).unwrap());
+
+    for revision in ["main@{-1}", "@{-0}"] {
+        assert!(!cat_file(&dir, &["-t", revision]).status.success(), "{revision}");
+    }
+}
+
+#[test]
+fn message_search_syntax() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let tree = write_tree(&dir);
+    let root = commit_tree(&dir, &tree, &[], "Initial commit\n");
+    let bump1 = commit_tree(&dir, &tree, &[&root], "Bump version to 1.0\n");
+    let fix = commit_tree(&dir, &tree, &[&bump1], "Fix: a bug\n\nDetails\n");
+    let bump2 = commit_tree(&dir, &tree, &[&fix], "Bump version to 2.0\n");
+    let docs = commit_tree(&dir, &tree, &[&bump2], "Write docs\n");
+    let side = commit_tree(&dir, &tree, &[&root], "!important side commit\n");
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &docs]).output().unwrap());
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/side", &side]).output().unwrap());
+
+    assert_same_object(&dir, ":/Bump version", &bump2);
+    assert_same_object(&dir, ":/^Bump version to 1", &bump1);
+    assert_same_object(&dir, ":/Details", &fix);
+    assert_same_object(&dir, ":/!!important", &side);
+    assert_same_object(&dir, ":/Fix: a", &fix);
+
+    assert_same_object(&dir, "main^{/Bump}", &bump2);
+    assert_same_object(&dir, "main~2^{/Bump}", &bump1);
+
+    assert_output_success(&mush!(dir).args(["tag", "-m", "release", "v1", &bump1]).output().unwrap());
+    assert_same_object(&dir, "v1^{/Bump}", &bump1);
+    assert_same_object(&dir, "main^{/!-Bump|Write}", &fix);
+    assert_same_object(&dir, "main^{/Fix: a}~1", &bump1);
+    assert_same_object(&dir, "side^{/Initial}", &root);
+
+    // braces in the pattern are escaped, and don't end the `^{/...}`
+    let braces = commit_tree(&dir, &tree, &[&root], "Fix {x} in braces\n");
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/braces", &braces]).output().unwrap());
+    assert_same_object(&dir, r"braces^{/Fix \{x\}}", &braces);
+    assert_same_object(&dir, r"braces^{/\{x\} in}~1", &root);
+    assert_same_object(&dir, r"braces^{/^Fix \{x\}}^{tree}", &tree);
+
+    for revision in [":/nothing matches", "side^{/Bump}", "main~3^{/docs}"] {
+        let output = cat_file(&dir, &["-t", revision]);
+
+        assert!(!output.status.success(), "{revision}");
+        assert_eq!(format!("Not a valid object name: `{revision}`\n"), String::from_utf8(output.stderr).unwrap());
+    }
+
+    for revision in [":/(unclosed", "main^{/[}", ":/!x", r"main^{/\}"] {
+        let output = cat_file(&dir, &["-t", revision]);
+
+        assert!(!output.status.success(), "{revision}");
+        assert_eq!(format!("Bad revision spec: `{revision}`\n"), String::from_utf8(output.stderr).unwrap());
+    }
+}
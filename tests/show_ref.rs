@@ -0,0 +1,69 @@
+mod helpers;
+
+use helpers::*;
+
+fn show_ref(dir: &tempdir::TempDir, args: &[&str]) -> std::process::Output {
+    mush!(dir)
+        .arg("show-ref")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// `main` (and HEAD) at `child`, `old` at `root`, and an annotated tag `v1` of `root`
+fn create_refs(dir: &tempdir::TempDir) -> History {
+    create_history(dir, HistoryOptions { old_branch: true, annotated_tag: true, ..Default::default() })
+}
+
+#[test]
+fn list() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, tag, .. } = create_refs(&dir);
+    let tag = tag.unwrap();
+
+    assert_eq!(
+        format!("{child} refs/heads/main\n{root} refs/heads/old\n{tag} refs/tags/v1\n"),
+        stdout(show_ref(&dir, &[]))
+    );
+    assert_eq!(
+        format!("{child} HEAD\n{child} refs/heads/main\n{root} refs/heads/old\n"),
+        stdout(show_ref(&dir, &["--head", "--heads"]))
+    );
+    assert_eq!(
+        format!("{tag} refs/tags/v1\n{root} refs/tags/v1^{{}}\n"),
+        stdout(show_ref(&dir, &["--tags", "-d"]))
+    );
+
+    // patterns match whole trailing components
+    assert_eq!(format!("{root} refs/heads/old\n"), stdout(show_ref(&dir, &["old"])));
+    assert_eq!(format!("{root} refs/heads/old\n"), stdout(show_ref(&dir, &["heads/old"])));
+    assert_eq!(format!("{child}\n{tag}\n"), stdout(show_ref(&dir, &["-s", "main", "v1"])));
+    assert_eq!(format!("{}\n", &child[..10]), stdout(show_ref(&dir, &["--hash=10", "main"])));
+
+    let output = show_ref(&dir, &["ld"]);
+    assert!(!output.status.success());
+    assert_eq!("", String::from_utf8(output.stdout).unwrap());
+}
+
+#[test]
+fn verify() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { child, .. } = create_refs(&dir);
+
+    assert_eq!(format!("{child} refs/heads/main\n"), stdout(show_ref(&dir, &["--verify", "refs/heads/main"])));
+    assert_eq!(format!("{child} HEAD\n"), stdout(show_ref(&dir, &["--verify", "HEAD"])));
+
+    let output = show_ref(&dir, &["--verify", "main"]);
+    assert!(!output.status.success());
+    assert_eq!("`main` - not a valid ref\n", String::from_utf8(output.stderr).unwrap());
+
+    let output = show_ref(&dir, &["--verify", "-q", "refs/heads/missing"]);
+    assert!(!output.status.success());
+    assert_eq!("", String::from_utf8(output.stderr).unwrap());
+
+    assert_eq!("", stdout(show_ref(&dir, &["--verify", "-q", "refs/heads/main"])));
+}
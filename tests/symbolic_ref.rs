@@ -0,0 +1,116 @@
+mod helpers;
+
+use helpers::*;
+
+fn symbolic_ref(dir: &tempdir::TempDir, args: &[&str]) -> std::process::Output {
+    mush!(dir)
+        .arg("symbolic-ref")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stderr_of_failure(output: std::process::Output) -> String {
+    assert!(!output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+/// `main` at `child` and `old` at `root`
+fn create_refs(dir: &tempdir::TempDir) -> History {
+    create_history(dir, HistoryOptions { old_branch: true, ..Default::default() })
+}
+
+#[test]
+fn read_and_set() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    // unborn branch
+    assert_eq!("refs/heads/main\n", stdout(symbolic_ref(&dir, &["HEAD"])));
+
+    let History { root, child, .. } = create_refs(&dir);
+
+    assert_eq!("refs/heads/main\n", stdout(symbolic_ref(&dir, &["HEAD"])));
+    assert_eq!("main\n", stdout(symbolic_ref(&dir, &["--short", "HEAD"])));
+
+    stdout(symbolic_ref(&dir, &["-m", "switch to old", "HEAD", "refs/heads/old"]));
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &"ref: refs/heads/old\n");
+    assert_eq!("old\n", stdout(symbolic_ref(&dir, &["--short", "HEAD"])));
+    assert_eq!(format!("{root}\n"), stdout(mush!(dir).args(["rev-parse", "HEAD"]).output().unwrap()));
+
+    let reflog = stdout(mush!(dir).args(["reflog", "show", "HEAD"]).output().unwrap());
+    assert!(reflog.starts_with(&format!("{} HEAD@{{0}}: switch to old\n", &root[..7])), "{reflog}");
+
+    // other symbolic refs; reading follows the chain
+    stdout(symbolic_ref(&dir, &["refs/heads/alias", "refs/heads/main"]));
+    assert_eq!(format!("{child}\n"), stdout(mush!(dir).args(["rev-parse", "alias"]).output().unwrap()));
+    stdout(symbolic_ref(&dir, &["HEAD", "refs/heads/alias"]));
+    assert_eq!("refs/heads/main\n", stdout(symbolic_ref(&dir, &["HEAD"])));
+
+    // pointing at a branch that doesn't exist yet is allowed
+    stdout(symbolic_ref(&dir, &["HEAD", "refs/heads/unborn"]));
+    assert_eq!("unborn\n", stdout(symbolic_ref(&dir, &["--short", "HEAD"])));
+
+    assert_eq!(
+        "Refusing to point `HEAD` outside of refs/: `MERGE_HEAD`\n",
+        stderr_of_failure(symbolic_ref(&dir, &["HEAD", "MERGE_HEAD"]))
+    );
+    assert!(!symbolic_ref(&dir, &["HEAD", "refs/heads/bad..name"]).status.success());
+    assert_eq!("unborn\n", stdout(symbolic_ref(&dir, &["--short", "HEAD"])));
+}
+
+#[test]
+fn delete() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    create_refs(&dir);
+
+    stdout(symbolic_ref(&dir, &["refs/heads/alias", "refs/heads/main"]));
+    stdout(symbolic_ref(&dir, &["-d", "refs/heads/alias"]));
+    assert!(!dir.path().join(".mush/refs/heads/alias").exists());
+    assert!(dir.path().join(".mush/refs/heads/main").exists());
+
+    assert_eq!("Refusing to delete HEAD\n", stderr_of_failure(symbolic_ref(&dir, &["-d", "HEAD"])));
+    assert_eq!(
+        "Cannot delete `refs/heads/main`: not a symbolic ref\n",
+        stderr_of_failure(symbolic_ref(&dir, &["-d", "refs/heads/main"]))
+    );
+    assert_eq!("No such ref: `refs/heads/alias`\n", stderr_of_failure(symbolic_ref(&dir, &["-d", "refs/heads/alias"])));
+}
+
+#[test]
+fn detached_head() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, .. } = create_refs(&dir);
+
+    assert!(stdout(mush!(dir).arg("status").env("NO_COLOR", "1").output().unwrap()).starts_with("On branch main\n"));
+
+    assert_output_success(&mush!(dir).args(["update-ref", "--no-deref", "HEAD", &root]).output().unwrap());
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &format!("{root}\n"));
+    assert_eq!(format!("{child}\n"), stdout(mush!(dir).args(["rev-parse", "main"]).output().unwrap()));
+
+    assert_eq!("Ref `HEAD` is not a symbolic ref\n", stderr_of_failure(symbolic_ref(&dir, &["HEAD"])));
+    assert_eq!("", stderr_of_failure(symbolic_ref(&dir, &["-q", "HEAD"])));
+
+    let status = stdout(mush!(dir).arg("status").env("NO_COLOR", "1").output().unwrap());
+    assert!(status.starts_with(&format!("HEAD detached at {}\n", &root[..7])), "{status}");
+
+    assert_eq!(
+        format!("* (HEAD detached at {})\n  main\n  old\n", &root[..7]),
+        stdout(mush!(dir).arg("branch").env("NO_COLOR", "1").output().unwrap())
+    );
+    assert_eq!("", stdout(mush!(dir).args(["branch", "--show-current"]).output().unwrap()));
+    assert_eq!("HEAD\n", stdout(mush!(dir).args(["rev-parse", "--abbrev-ref", "HEAD"]).output().unwrap()));
+
+    // updates of a detached HEAD don't touch any branch
+    assert_output_success(&mush!(dir).args(["update-ref", "HEAD", &child]).output().unwrap());
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &format!("{child}\n"));
+    assert_eq!(format!("{root}\n"), stdout(mush!(dir).args(["rev-parse", "old"]).output().unwrap()));
+
+    // reattach
+    stdout(symbolic_ref(&dir, &["HEAD", "refs/heads/old"]));
+    assert!(stdout(mush!(dir).arg("status").env("NO_COLOR", "1").output().unwrap()).starts_with("On branch old\n"));
+}
@@ -0,0 +1,112 @@
+mod helpers;
+
+use std::io::Write;
+
+use helpers::*;
+
+fn tag(dir: &tempdir::TempDir, args: &[&str]) -> std::process::Output {
+    mush!(dir)
+        .arg("tag")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn rev_parse(dir: &tempdir::TempDir, revision: &str) -> String {
+    stdout(mush!(dir).args(["rev-parse", revision]).output().unwrap())
+        .trim_end()
+        .to_string()
+}
+
+#[test]
+fn annotated() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { tree, root, child, .. } = create_history(&dir, HistoryOptions::default());
+
+    stdout(tag(&dir, &["-m", "release one", "v1"]));
+
+    let tag_hash = rev_parse(&dir, "v1");
+    assert_ne!(child, tag_hash);
+    assert_file_contents(&dir.path().join(".mush/refs/tags/v1"), &format!("{tag_hash}\n"));
+
+    assert_eq!("tag\n", stdout(mush!(dir).args(["cat-file", "-t", &tag_hash]).output().unwrap()));
+
+    let contents = stdout(mush!(dir).args(["cat-file", "-p", &tag_hash]).output().unwrap());
+    let expected_prefix = format!("object\t{child}\ntype\tcommit\ntag\tv1\ntagger\tBud Weiser\t<bud@wiser.org>\t");
+    assert!(contents.starts_with(&expected_prefix), "{contents}");
+    assert!(contents.ends_with("\n\nrelease one\n"), "{contents}");
+
+    // peeling
+    assert_eq!(child, rev_parse(&dir, "v1^{}"));
+    assert_eq!(child, rev_parse(&dir, "v1^{commit}"));
+    assert_eq!(child, rev_parse(&dir, "v1^0"));
+    assert_eq!(tag_hash, rev_parse(&dir, "v1^{tag}"));
+    assert_eq!(tree, rev_parse(&dir, "v1^{tree}"));
+    assert_eq!(root, rev_parse(&dir, "v1~1"));
+    assert_eq!(root, rev_parse(&dir, "v1^"));
+
+    // message from stdin
+    let mut child_process = mush!(dir)
+        .args(["tag", "-a", "v0", &root])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child_process.stdin.take().unwrap().write_all("from stdin\n".as_bytes()).unwrap();
+    assert_output_success(&child_process.wait_with_output().unwrap());
+
+    let contents = stdout(mush!(dir).args(["cat-file", "-p", "v0"]).output().unwrap());
+    assert!(contents.starts_with(&format!("object\t{root}\ntype\tcommit\ntag\tv0\n")), "{contents}");
+    assert!(contents.ends_with("\n\nfrom stdin\n"), "{contents}");
+    assert_eq!(root, rev_parse(&dir, "v0^{commit}"));
+
+    // tags of non-commits can't be peeled to commits
+    stdout(tag(&dir, &["-m", "a tree", "treetag", &tree]));
+    let contents = stdout(mush!(dir).args(["cat-file", "-p", "treetag"]).output().unwrap());
+    assert!(contents.starts_with(&format!("object\t{tree}\ntype\ttree\n")), "{contents}");
+    assert_eq!(tree, rev_parse(&dir, "treetag^{}"));
+    assert!(!mush!(dir).args(["rev-parse", "treetag^{commit}"]).output().unwrap().status.success());
+}
+
+#[test]
+fn lightweight_list_and_delete() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let History { root, child, .. } = create_history(&dir, HistoryOptions::default());
+
+    assert_eq!("", stdout(tag(&dir, &[])));
+
+    stdout(tag(&dir, &["light"]));
+    stdout(tag(&dir, &["old", "HEAD~"]));
+    stdout(tag(&dir, &["-m", "annotated", "nested/name"]));
+
+    assert_file_contents(&dir.path().join(".mush/refs/tags/light"), &format!("{child}\n"));
+    assert_file_contents(&dir.path().join(".mush/refs/tags/old"), &format!("{root}\n"));
+
+    assert_eq!("light\nnested/name\nold\n", stdout(tag(&dir, &[])));
+    assert_eq!(stdout(tag(&dir, &[])), stdout(tag(&dir, &["-l"])));
+
+    // existing tags aren't replaced without `-f`
+    let output = tag(&dir, &["old"]);
+    assert!(!output.status.success());
+    assert_eq!("Tag `old` already exists\n", String::from_utf8(output.stderr).unwrap());
+    assert_file_contents(&dir.path().join(".mush/refs/tags/old"), &format!("{root}\n"));
+
+    stdout(tag(&dir, &["-f", "old"]));
+    assert_file_contents(&dir.path().join(".mush/refs/tags/old"), &format!("{child}\n"));
+
+    assert_eq!(
+        format!("Deleted tag old (was {}).\nDeleted tag light (was {}).\n", &child[..7], &child[..7]),
+        stdout(tag(&dir, &["-d", "old", "light"]))
+    );
+    assert_eq!("nested/name\n", stdout(tag(&dir, &[])));
+    assert!(!dir.path().join(".mush/refs/tags/old").exists());
+
+    for args in [vec!["-d", "missing"], vec!["bad..name"], vec!["x", "nope"], vec!["a", "b", "c"]] {
+        assert!(!tag(&dir, &args).status.success(), "{args:?}");
+    }
+}
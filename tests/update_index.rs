@@ -51,7 +51,7 @@ fn create_new_index() {
         ].concat());
 
     // Add checksum. Unforfunately hard to sanity-check this one.
-    expected_index_contents.extend(mush::hash::Hash::digest(&expected_index_contents).as_bytes());
+    expected_index_contents.extend(mush::hash::ObjectFormat::Sha1.digest(&expected_index_contents).as_bytes());
 
     let output = mush!(dir)
             .arg("hash-object")
@@ -146,7 +146,7 @@ fn add_to_existing_index() {
         ].concat());
 
     // Add checksum. Unforfunately hard to sanity-check this one.
-    expected_index_contents.extend(mush::hash::Hash::digest(&expected_index_contents).as_bytes());
+    expected_index_contents.extend(mush::hash::ObjectFormat::Sha1.digest(&expected_index_contents).as_bytes());
 
     let output = mush!(dir)
             .arg("hash-object")
@@ -0,0 +1,278 @@
+mod helpers;
+
+use std::io::Write;
+
+use helpers::*;
+
+const ZERO_HASH: &str = "0000000000000000000000000000000000000000";
+
+#[test]
+fn create_and_update_through_head() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    let b = hash_object_write(&dir, "b", "b\n");
+
+    // HEAD -> refs/heads/main (which doesn't exist yet)
+    let output = mush!(dir)
+        .args(["update-ref", "HEAD", &a])
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+    assert_file_contents(&dir.path().join(".mush/refs/heads/main"), &format!("{a}\n"));
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &"ref: refs/heads/main\n");
+
+    let output = mush!(dir)
+        .args(["update-ref", "refs/heads/main", &b, &a])
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+    assert_file_contents(&dir.path().join(".mush/refs/heads/main"), &format!("{b}\n"));
+    assert!(!dir.path().join(".mush/refs/heads/main.lock").exists());
+}
+
+#[test]
+fn compare_and_swap_failures() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    let b = hash_object_write(&dir, "b", "b\n");
+
+    let output = mush!(dir)
+        .args(["update-ref", "refs/heads/topic", &a, ZERO_HASH])
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+
+    // already exists
+    let output = mush!(dir)
+        .args(["update-ref", "refs/heads/topic", &b, ZERO_HASH])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("reference already exists"));
+
+    // wrong old value
+    let output = mush!(dir)
+        .args(["update-ref", "refs/heads/topic", &a, &b])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains(&format!("is at {a} but expected {b}")));
+
+    assert_file_contents(&dir.path().join(".mush/refs/heads/topic"), &format!("{a}\n"));
+}
+
+#[test]
+fn no_deref() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+
+    let output = mush!(dir)
+        .args(["update-ref", "--no-deref", "HEAD", &a])
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &format!("{a}\n"));
+    assert!(!dir.path().join(".mush/refs/heads/main").exists());
+}
+
+#[test]
+fn delete() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    let b = hash_object_write(&dir, "b", "b\n");
+
+    let output = mush!(dir)
+        .args(["update-ref", "refs/heads/feature/x", &a])
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+
+    let output = mush!(dir)
+        .args(["update-ref", "-d", "refs/heads/feature/x", &b])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(dir.path().join(".mush/refs/heads/feature/x").exists());
+
+    let output = mush!(dir)
+        .args(["update-ref", "-d", "refs/heads/feature/x", &a])
+        .output()
+        .unwrap();
+
+    assert_output_success(&output);
+    assert!(!dir.path().join(".mush/refs/heads/feature").exists());
+    assert_directory_exists(&dir.path().join(".mush/refs/heads"));
+
+    let output = mush!(dir)
+        .args(["update-ref", "-d", "refs/heads/feature/x"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+}
+
+#[test]
+fn existing_lock_file() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    create_file_with_contents(dir.path(), ".mush/refs/heads/main.lock", "");
+
+    let output = mush!(dir)
+        .args(["update-ref", "refs/heads/main", &a])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("another mush process"));
+    assert!(!dir.path().join(".mush/refs/heads/main").exists());
+    assert!(dir.path().join(".mush/refs/heads/main.lock").exists());
+}
+
+#[test]
+fn bad_arguments() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+
+    [
+        vec!["update-ref", "refs/heads/main", "99622a960c9f3d0232df4d90149b666c11159b9e"], // nonexistent object
+        vec!["update-ref", "main", &a], // not a full ref name
+        vec!["update-ref", "refs/heads/a..b", &a],
+        vec!["update-ref", "refs/heads/main.lock", &a],
+    ].iter().for_each(|args| {
+        let output = mush!(dir)
+            .args(args)
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success(), "{args:?}");
+    });
+
+    assert!(!dir.path().join(".mush/refs/heads/main").exists());
+}
+
+fn update_ref_stdin(dir: &tempdir::TempDir, args: &[&str], input: &str) -> std::process::Output {
+    let mut child = mush!(dir)
+        .args(["update-ref", "--stdin"])
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn stdin_transaction() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    let b = hash_object_write(&dir, "b", "b\n");
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &a]).output().unwrap());
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/old", &a]).output().unwrap());
+
+    let output = update_ref_stdin(&dir, &["-m", "release"], &format!(
+        "update refs/heads/main {b} {a}\n\
+         create refs/tags/v1 {b}\n\
+         \n\
+         delete refs/heads/old {a}\n\
+         verify refs/heads/missing\n"
+    ));
+
+    assert_output_success(&output);
+    assert_file_contents(&dir.path().join(".mush/refs/heads/main"), &format!("{b}\n"));
+    assert_file_contents(&dir.path().join(".mush/refs/tags/v1"), &format!("{b}\n"));
+    assert!(!dir.path().join(".mush/refs/heads/old").exists());
+    assert!(!dir.path().join(".mush/refs/heads/missing").exists());
+
+    let reflog = String::from_utf8(mush!(dir).args(["reflog", "show", "main"]).output().unwrap().stdout).unwrap();
+    assert!(reflog.starts_with(&format!("{} main@{{0}}: release\n", &b[..7])), "{reflog}");
+
+    // `option no-deref` applies to the next command only
+    let output = update_ref_stdin(&dir, &[], &format!("option no-deref\nupdate HEAD {a}\nupdate refs/heads/main {a}\n"));
+    assert_output_success(&output);
+    assert_file_contents(&dir.path().join(".mush/HEAD"), &format!("{a}\n"));
+    assert_file_contents(&dir.path().join(".mush/refs/heads/main"), &format!("{a}\n"));
+}
+
+#[test]
+fn stdin_transaction_is_atomic() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+    mush_config_user(&dir);
+
+    let a = hash_object_write(&dir, "a", "a\n");
+    let b = hash_object_write(&dir, "b", "b\n");
+
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/heads/main", &a]).output().unwrap());
+    assert_output_success(&mush!(dir).args(["update-ref", "refs/tags/v1", &a]).output().unwrap());
+
+    let assert_unchanged = || {
+        assert_file_contents(&dir.path().join(".mush/refs/heads/main"), &format!("{a}\n"));
+        assert_file_contents(&dir.path().join(".mush/refs/tags/v1"), &format!("{a}\n"));
+        assert!(!dir.path().join(".mush/refs/heads/topic").exists());
+
+        let leftover_locks = ["refs/heads/main.lock", "refs/heads/topic.lock", "refs/tags/v1.lock", "packed-refs.lock"]
+            .iter()
+            .filter(|lock| dir.path().join(".mush").join(lock).exists())
+            .collect::<Vec<_>>();
+        assert!(leftover_locks.is_empty(), "{leftover_locks:?}");
+    };
+
+    // the last check fails, so nothing is applied
+    for (input, message) in [
+        (format!("update refs/heads/main {b}\ncreate refs/heads/topic {b}\ncreate refs/tags/v1 {b}\n"), "reference already exists"),
+        (format!("update refs/heads/main {b} {a}\nupdate refs/tags/v1 {b} {b}\n"), "but expected"),
+        (format!("create refs/heads/topic {b}\ndelete refs/heads/nope\n"), "no such ref"),
+        (format!("update refs/heads/main {b}\nverify refs/tags/v1\n"), "reference already exists"),
+        (format!("update refs/heads/main {b}\nupdate HEAD {a}\n"), "Multiple updates for ref `refs/heads/main`"),
+        (format!("update refs/heads/main {b}\nfrobnicate refs/tags/v1\n"), "Bad command on line 2"),
+        (String::from("create refs/heads/topic 99622a960c9f3d0232df4d90149b666c11159b9e\n"), "nonexistent object"),
+    ] {
+        let output = update_ref_stdin(&dir, &[], &input);
+
+        assert!(!output.status.success(), "{input}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(message), "{stderr}");
+        assert_unchanged();
+    }
+
+    // a ref locked by someone else aborts the whole transaction
+    create_file_with_contents(dir.path(), ".mush/refs/tags/v1.lock", "");
+    let output = update_ref_stdin(&dir, &[], &format!("update refs/heads/main {b}\nupdate refs/tags/v1 {b}\n"));
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("another mush process"));
+    assert_file_contents(&dir.path().join(".mush/refs/heads/main"), &format!("{a}\n"));
+    assert!(!dir.path().join(".mush/refs/heads/main.lock").exists());
+}
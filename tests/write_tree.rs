@@ -149,3 +149,27 @@ fn lots_of_nesting() {
     ].iter()
             .for_each(|file| assert_file_exists(&dir.path().join(file)));
 }
+
+#[test]
+fn large_binary_file() {
+    let dir = tempdir();
+    mush_init_clean_repo(&dir);
+
+    let contents = (0..1_000_000u32).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+    create_file_with_byte_contents(dir.path(), "large.bin", &contents);
+
+    let output = mush!(dir).args(["hash-object", "large.bin"]).output().unwrap();
+    assert_output_success(&output);
+    let blob = String::from_utf8(output.stdout).unwrap().trim().to_string();
+    assert_output_success(&mush!(dir).args(["update-index", "--add", &blob, "large.bin"]).output().unwrap());
+
+    let tree = write_tree(&dir);
+
+    let output = mush!(dir).args(["cat-file", "-p", &tree]).output().unwrap();
+    assert_output_success(&output);
+    assert!(String::from_utf8(output.stdout).unwrap().contains(&format!("blob {blob}\tlarge.bin")));
+
+    let output = mush!(dir).args(["cat-file", "-p", &blob]).output().unwrap();
+    assert_output_success(&output);
+    assert!(output.stdout == contents);
+}